@@ -0,0 +1,944 @@
+// Generates the method and content property codecs from the bundled
+// amqp0-9-1.extended.xml. Every class gets $OUT_DIR/<class>.rs, included by
+// the module of the same name. The tables spanning all classes get a file of
+// their own, included where they are used.
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+const SPEC: &str = "amqp0-9-1.extended.xml";
+
+fn main() {
+    println!("cargo:rerun-if-changed={}", SPEC);
+    println!("cargo:rerun-if-changed=build.rs");
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR not set");
+    let text = match fs::read_to_string(Path::new(&manifest_dir).join(SPEC)) {
+        Ok(text) => text,
+        Err(e) => panic!("can't read {}: {}", SPEC, e)
+    };
+    let spec = Spec::from_xml(&parse_xml(&text));
+
+    let out_dir = env::var("OUT_DIR").expect("OUT_DIR not set");
+    let out_dir = Path::new(&out_dir);
+    for class in &spec.classes {
+        write_file(out_dir, &format!("{}.rs", class.name), &class_codecs(class));
+    }
+    write_file(out_dir, "parse_arguments.rs", &parse_arguments(&spec));
+    write_file(out_dir, "write_arguments.rs", &write_arguments(&spec));
+    write_file(out_dir, "method_ids.rs", &method_ids(&spec));
+    write_file(out_dir, "method_names.rs", &method_names(&spec));
+    write_file(out_dir, "method_fields.rs", &method_fields(&spec));
+    write_file(out_dir, "shared_properties.rs", &shared_properties(&spec));
+}
+
+fn write_file(out_dir: &Path, name: &str, code: &str) {
+    let content = format!("// Generated by build.rs from {}, do not edit.\n\n{}", SPEC, code);
+    if let Err(e) = fs::write(out_dir.join(name), content) {
+        panic!("can't write {}: {}", name, e);
+    }
+}
+
+// Just enough XML for the spec: elements and their attributes. Text, comments,
+// processing instructions and the doctype are skipped.
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.iter().find(|(key, _)| key == name).map(|(_, value)| value.as_str())
+    }
+
+    fn required(&self, name: &str) -> &str {
+        match self.attribute(name) {
+            Some(value) => value,
+            None => panic!("<{}> without {}", self.name, name)
+        }
+    }
+
+    fn index(&self) -> u16 {
+        match self.required("index").parse() {
+            Ok(index) => index,
+            Err(_) => panic!("<{} name=\"{}\"> has a bad index", self.name, self.required("name"))
+        }
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+}
+
+fn parse_xml(text: &str) -> Element {
+    let mut stack = vec![Element { name: String::new(), attributes: Vec::new(), children: Vec::new() }];
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start..];
+        if rest.starts_with("<!--") {
+            rest = skip_past(rest, "-->");
+        } else if rest.starts_with("<?") {
+            rest = skip_past(rest, "?>");
+        } else if rest.starts_with("<!") {
+            rest = skip_past(rest, ">");
+        } else if rest.starts_with("</") {
+            let end = tag_end(rest);
+            let name = rest[2..end].trim();
+            rest = &rest[end + 1..];
+            let element = stack.pop().unwrap();
+            if element.name != name {
+                panic!("</{}> closes <{}>", name, element.name);
+            }
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(element),
+                None => panic!("unbalanced </{}>", name)
+            }
+        } else {
+            let end = tag_end(rest);
+            let (tag, empty) = match rest[1..end].trim_end() {
+                tag if tag.ends_with('/') => (&tag[..tag.len() - 1], true),
+                tag => (tag, false)
+            };
+            rest = &rest[end + 1..];
+            let element = parse_tag(tag);
+            if empty {
+                stack.last_mut().unwrap().children.push(element);
+            } else {
+                stack.push(element);
+            }
+        }
+    }
+    if stack.len() != 1 {
+        panic!("<{}> is never closed", stack.last().unwrap().name);
+    }
+    let mut document = stack.pop().unwrap();
+    match document.children.pop() {
+        Some(root) if root.name == "amqp" && document.children.is_empty() => root,
+        _ => panic!("{} must hold a single <amqp> element", SPEC)
+    }
+}
+
+fn skip_past<'a>(text: &'a str, end: &str) -> &'a str {
+    match text.find(end) {
+        Some(at) => &text[at + end.len()..],
+        None => panic!("missing {}", end)
+    }
+}
+
+// position of the '>' closing the tag, quoted attribute values may hold one
+fn tag_end(text: &str) -> usize {
+    let mut quote = None;
+    for (at, c) in text.char_indices() {
+        match quote {
+            Some(open) if c == open => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if c == '>' => return at,
+            None => {}
+        }
+    }
+    panic!("unterminated tag");
+}
+
+fn parse_tag(tag: &str) -> Element {
+    let name_end = tag.find(char::is_whitespace).unwrap_or_else(|| tag.len());
+    let name = tag[..name_end].to_string();
+    let mut attributes = Vec::new();
+    let mut rest = tag[name_end..].trim_start();
+    while !rest.is_empty() {
+        let equals = match rest.find('=') {
+            Some(equals) => equals,
+            None => panic!("<{}> has a broken attribute", name)
+        };
+        let key = rest[..equals].trim().to_string();
+        let value = rest[equals + 1..].trim_start();
+        let quote = match value.chars().next() {
+            Some(quote) if quote == '"' || quote == '\'' => quote,
+            _ => panic!("<{}> has an unquoted attribute", name)
+        };
+        let value_end = match value[1..].find(quote) {
+            Some(end) => end + 1,
+            None => panic!("<{}> has an unterminated attribute", name)
+        };
+        attributes.push((key, unescape(&value[1..value_end])));
+        rest = value[value_end + 1..].trim_start();
+    }
+    Element { name, attributes, children: Vec::new() }
+}
+
+fn unescape(value: &str) -> String {
+    value.replace("&lt;", "<").replace("&gt;", ">").replace("&quot;", "\"").replace("&apos;", "'").replace("&amp;", "&")
+}
+
+// wire encoding of a field, class-id and method-id are shorts read as types
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Bit,
+    Octet,
+    Short,
+    Long,
+    LongLong,
+    Timestamp,
+    ShortStr,
+    LongStr,
+    Table,
+    ClassId,
+    MethodId
+}
+
+struct Field {
+    name: String,
+    kind: Kind
+}
+
+struct Method {
+    name: String,
+    index: u16,
+    fields: Vec<Field>
+}
+
+struct Class {
+    name: String,
+    index: u16,
+    // content properties, in flag order
+    properties: Vec<Field>,
+    methods: Vec<Method>
+}
+
+struct Spec {
+    classes: Vec<Class>
+}
+
+impl Spec {
+    fn from_xml(root: &Element) -> Spec {
+        let mut domains = HashMap::new();
+        for domain in root.children("domain") {
+            domains.insert(domain.required("name"), domain.required("type"));
+        }
+        let fields = |parent: &Element| -> Vec<Field> {
+            parent.children("field").map(|field| {
+                let domain = match field.attribute("domain") {
+                    Some(domain) => domain,
+                    None => field.required("type")
+                };
+                Field { name: field.required("name").to_string(), kind: resolve(&domains, domain) }
+            }).collect()
+        };
+        let classes = root.children("class").map(|class| {
+            Class {
+                name: class.required("name").to_string(),
+                index: class.index(),
+                properties: fields(class),
+                methods: class.children("method").map(|method| {
+                    Method {
+                        name: method.required("name").to_string(),
+                        index: method.index(),
+                        fields: fields(method)
+                    }
+                }).collect()
+            }
+        }).collect();
+        Spec { classes }
+    }
+}
+
+fn resolve(domains: &HashMap<&str, &str>, domain: &str) -> Kind {
+    let mut name = domain;
+    for _ in 0..8 {
+        match name {
+            "class-id" => return Kind::ClassId,
+            "method-id" => return Kind::MethodId,
+            "bit" => return Kind::Bit,
+            "octet" => return Kind::Octet,
+            "short" => return Kind::Short,
+            "long" => return Kind::Long,
+            "longlong" => return Kind::LongLong,
+            "timestamp" => return Kind::Timestamp,
+            "shortstr" => return Kind::ShortStr,
+            "longstr" => return Kind::LongStr,
+            "table" => return Kind::Table,
+            _ => {}
+        }
+        name = match domains.get(name) {
+            Some(base) => base,
+            None => panic!("unknown domain {}", name)
+        };
+    }
+    panic!("domain {} does not resolve to a type", domain);
+}
+
+fn camel(name: &str) -> String {
+    name.split('-').map(|word| {
+        let mut chars = word.chars();
+        match chars.next() {
+            Some(first) => first.to_uppercase().chain(chars).collect(),
+            None => String::new()
+        }
+    }).collect()
+}
+
+fn snake(name: &str) -> String {
+    name.replace('-', "_")
+}
+
+// Accessor names in amqp-types. Reserved fields use the names they had before
+// 0-9-1 reserved them.
+fn rust_name(class: &str, method: &str, field: &str) -> String {
+    let name = match (class, method, field) {
+        ("connection", "open", "virtual-host") => "vhost",
+        ("connection", "open", "reserved-1") => "capabilities",
+        ("connection", "open", "reserved-2") => "insist",
+        ("connection", "open-ok", "reserved-1") => "known_hosts",
+        ("channel", "open", "reserved-1") => "out_of_band",
+        ("channel", "open-ok", "reserved-1") => "channel_id",
+        ("basic", "get-empty", "reserved-1") => "cluster_id",
+        ("basic", "", "reserved") => "cluster_id",
+        (_, _, "reserved-1") => "ticket",
+        ("exchange", _, "type") => "exchange_type",
+        (_, _, "type") => "message_type",
+        (_, _, "exchange") => "exchange_name",
+        (_, _, "queue") => "queue_name",
+        (_, _, "arguments") => "args",
+        (_, _, "class-id") => "class",
+        (_, _, "method-id") => "method",
+        _ => return snake(field)
+    };
+    name.to_string()
+}
+
+// name in errors and the argument tables, the spec name unless reserved
+fn wire_name(class: &str, method: &str, field: &str) -> String {
+    if field.starts_with("reserved") {
+        rust_name(class, method, field)
+    } else {
+        snake(field)
+    }
+}
+
+fn struct_name(class: &Class, method: &Method) -> String {
+    format!("{}{}", camel(&class.name), camel(&method.name))
+}
+
+fn function_name(class: &Class, method: &Method) -> String {
+    format!("{}_{}", class.name, snake(&method.name))
+}
+
+// position of each bit within its octet, runs of bits share octets
+fn bit_positions(fields: &[Field]) -> Vec<Option<u8>> {
+    let mut positions = Vec::new();
+    let mut next = None;
+    for field in fields {
+        if let Kind::Bit = field.kind {
+            let position = match next {
+                Some(position) if position < 8 => position,
+                _ => 0
+            };
+            positions.push(Some(position));
+            next = Some(position + 1);
+        } else {
+            positions.push(None);
+            next = None;
+        }
+    }
+    positions
+}
+
+// indexes of the bits sharing an octet with the one at start
+fn bit_run(positions: &[Option<u8>], start: usize) -> impl Iterator<Item = usize> + '_ {
+    (start..positions.len()).take_while(move |index| match positions[*index] {
+        Some(position) => *index == start || position != 0,
+        None => false
+    })
+}
+
+struct Codegen {
+    imports: BTreeSet<String>,
+    code: String
+}
+
+impl Codegen {
+    fn import(&mut self, path: &str) {
+        self.imports.insert(path.to_string());
+    }
+
+    fn line(&mut self, line: &str) {
+        self.code.push_str(line);
+        self.code.push('\n');
+    }
+
+    fn finish(self) -> String {
+        let mut out = String::new();
+        for path in &self.imports {
+            writeln!(out, "use {};", path).unwrap();
+        }
+        out.push('\n');
+        out.push_str(&self.code);
+        out
+    }
+
+    // `let (<rest>, <var>) = ...` for a field that is not a bit
+    fn read_field(&mut self, kind: Kind, name: &str, var: &str, rest: &str) {
+        let parser = match kind {
+            Kind::Octet | Kind::Bit => "be_u8",
+            Kind::Short | Kind::ClassId | Kind::MethodId => "be_u16",
+            Kind::Long => "be_u32",
+            Kind::LongLong | Kind::Timestamp => "be_u64",
+            Kind::ShortStr => "parse_short_string",
+            Kind::LongStr => "parse_long_string",
+            Kind::Table => "parse_field_table"
+        };
+        if parser.starts_with("be_") {
+            self.import("nom::error::ErrorKind");
+            self.import(&format!("nom::number::complete::{}", parser));
+            self.line(&format!("    let ({}, {}) = match {}::<(_, ErrorKind)>(buffer) {{", rest, var, parser));
+            self.line("        Ok(ret) => ret,");
+            self.line(&format!("        Err(e) => return Err(FrameDecodeErr::field(\"{}\", buffer, e))", name));
+        } else {
+            self.import(&format!("crate::common::{}", parser));
+            self.line(&format!("    let ({}, {}) = match {}(buffer) {{", rest, var, parser));
+            self.line("        Ok(ret) => ret,");
+            self.line(&format!("        Err(e) => return Err(e.in_field(\"{}\", buffer))", name));
+        }
+        self.line("    };");
+    }
+
+    // one value onto the buffer named dst, bits are packed by the caller
+    fn write_value(&mut self, kind: Kind, value: &str, dst: &str) {
+        // dst is either the &mut BytesMut argument or a local BytesMut
+        let target = if dst == "dst" { dst.to_string() } else { format!("&mut {}", dst) };
+        let line = match kind {
+            Kind::Octet => format!("    {}.put_u8({});", dst, value),
+            Kind::Short => format!("    {}.put_u16({});", dst, value),
+            Kind::Long => format!("    {}.put_u32({});", dst, value),
+            Kind::LongLong | Kind::Timestamp => format!("    {}.put_u64({});", dst, value),
+            Kind::ShortStr => format!("    write_short_string({}, {});", value, target),
+            Kind::LongStr => format!("    write_long_string({}, {});", value, target),
+            Kind::Table => format!("    write_field_table({}, {})?;", value, target),
+            // Class::Unknown and the Unknown methods go out as 0
+            Kind::ClassId => format!("    {}.put_u16(get_class_id({}).unwrap_or(0));", dst, value),
+            Kind::MethodId => format!("    {}.put_u16(get_method_id({}).unwrap_or(0));", dst, value),
+            Kind::Bit => unreachable!()
+        };
+        match kind {
+            Kind::ShortStr => self.import("crate::common::write_short_string"),
+            Kind::LongStr => self.import("crate::common::write_long_string"),
+            Kind::Table => self.import("crate::common::write_field_table"),
+            Kind::ClassId => {
+                self.import("crate::common::get_class_id");
+                self.import("bytes::BufMut");
+            }
+            Kind::MethodId => {
+                self.import("crate::common::get_method_id");
+                self.import("bytes::BufMut");
+            }
+            _ => self.import("bytes::BufMut")
+        }
+        self.line(&line);
+    }
+}
+
+fn class_codecs(class: &Class) -> String {
+    let mut gen = Codegen { imports: BTreeSet::new(), code: String::new() };
+    gen.import("amqp_types::frame::Arguments");
+    gen.import("bytes::BytesMut");
+    gen.import("crate::error::FrameDecodeErr");
+    gen.import("crate::error::FrameEncodeErr");
+    if !class.properties.is_empty() {
+        parse_properties(&mut gen, class);
+        write_properties(&mut gen, class);
+    }
+    for method in &class.methods {
+        parse_method(&mut gen, class, method);
+    }
+    for method in &class.methods {
+        write_method(&mut gen, class, method);
+    }
+    gen.finish()
+}
+
+fn parse_method(gen: &mut Codegen, class: &Class, method: &Method) {
+    let struct_name = struct_name(class, method);
+    let var = function_name(class, method);
+    gen.import(&format!("amqp_types::frame::{}", struct_name));
+    if method.fields.is_empty() {
+        gen.line(&format!("pub(crate) fn parse_{}(_buffer: &[u8]) -> Result<Arguments, FrameDecodeErr> {{", var));
+        gen.line(&format!("    Ok(Arguments::{}({}::default()))", struct_name, struct_name));
+        gen.line("}");
+        gen.line("");
+        return;
+    }
+
+    gen.line(&format!("pub(crate) fn parse_{}(buffer: &[u8]) -> Result<Arguments, FrameDecodeErr> {{", var));
+    let positions = bit_positions(&method.fields);
+    // the buffer left after the last read is not needed
+    let last_read = positions.iter().rposition(|position| match position {
+        Some(position) => *position == 0,
+        None => true
+    }).unwrap();
+    for (index, field) in method.fields.iter().enumerate() {
+        let name = wire_name(&class.name, &method.name, &field.name);
+        let value = rust_name(&class.name, &method.name, &field.name);
+        let rest = if index == last_read { "_" } else { "buffer" };
+        match field.kind {
+            Kind::Bit => {
+                // the whole run is unpacked when its octet is read
+                if positions[index] == Some(0) {
+                    gen.import("crate::common::unpack_bits");
+                    gen.read_field(Kind::Octet, &name, "bits", rest);
+                    let mut names: Vec<String> = bit_run(&positions, index).map(|field| {
+                        rust_name(&class.name, &method.name, &method.fields[field].name)
+                    }).collect();
+                    if names.len() < 8 {
+                        names.push("..".to_string());
+                    }
+                    gen.line(&format!("    let [{}] = unpack_bits(bits);", names.join(", ")));
+                }
+            }
+            // Plain shorts naming the method a close answers, 0/0 when no
+            // method caused it. Ids the spec does not know are not an error.
+            Kind::ClassId => {
+                gen.import("amqp_types::frame::Class");
+                gen.read_field(Kind::Short, &name, "class_id", rest);
+                gen.line(&format!("    let {} = Class::from(class_id);", value));
+            }
+            Kind::MethodId => {
+                gen.import("crate::common::get_method_type");
+                gen.import("crate::encode::no_method");
+                let class_field = match method.fields[..index].iter().find(|field| field.kind == Kind::ClassId) {
+                    Some(field) => rust_name(&class.name, &method.name, &field.name),
+                    None => panic!("{}.{}: method-id without a class-id before it", class.name, method.name)
+                };
+                gen.read_field(Kind::Short, &name, "method_id", rest);
+                gen.line(&format!("    let {} = get_method_type({}.clone(), method_id).unwrap_or_else(|| no_method().1);", value, class_field));
+            }
+            kind => gen.read_field(kind, &name, &value, rest)
+        }
+    }
+    gen.line(&format!("    let {} = {}::default();", var, struct_name));
+    for field in &method.fields {
+        let value = rust_name(&class.name, &method.name, &field.name);
+        gen.line(&format!("    {}.set_{}({});", var, value, value));
+    }
+    gen.line(&format!("    Ok(Arguments::{}({}))", struct_name, var));
+    gen.line("}");
+    gen.line("");
+}
+
+fn write_method(gen: &mut Codegen, class: &Class, method: &Method) {
+    let struct_name = struct_name(class, method);
+    let var = function_name(class, method);
+    if method.fields.is_empty() {
+        gen.line(&format!("pub(crate) fn write_{}(_args: &{}, _dst: &mut BytesMut) -> Result<(), FrameEncodeErr> {{", var, struct_name));
+        gen.line("    Ok(())");
+        gen.line("}");
+        gen.line("");
+        return;
+    }
+
+    gen.line(&format!("pub(crate) fn write_{}(args: &{}, dst: &mut BytesMut) -> Result<(), FrameEncodeErr> {{", var, struct_name));
+    let positions = bit_positions(&method.fields);
+    for (index, field) in method.fields.iter().enumerate() {
+        let value = format!("args.{}()", rust_name(&class.name, &method.name, &field.name));
+        match positions[index] {
+            Some(0) => {
+                gen.import("bytes::BufMut");
+                gen.import("crate::common::pack_bits");
+                let values: Vec<String> = bit_run(&positions, index).map(|field| {
+                    format!("args.{}()", rust_name(&class.name, &method.name, &method.fields[field].name))
+                }).collect();
+                gen.line(&format!("    dst.put_u8(pack_bits(&[{}]));", values.join(", ")));
+            }
+            // packed with the first bit of its run
+            Some(_) => {}
+            None => gen.write_value(field.kind, &value, "dst")
+        }
+    }
+    gen.line("    Ok(())");
+    gen.line("}");
+    gen.line("");
+}
+
+fn properties_name(class: &Class) -> String {
+    format!("{}Properties", camel(&class.name))
+}
+
+fn parse_properties(gen: &mut Codegen, class: &Class) {
+    let count = format!("{}_PROPERTY_COUNT", class.name.to_uppercase());
+    gen.import("amqp_types::frame::Property");
+    gen.import(&format!("amqp_types::frame::{}", properties_name(class)));
+    gen.import("crate::common::parse_property_flags");
+    gen.import("crate::error::DecodeCause");
+    gen.line(&format!("pub(crate) const {}: usize = {};", count, class.properties.len()));
+    gen.line("");
+    gen.line(&format!("pub(crate) fn parse_{}_properties(buffer: &[u8]) -> Result<Property, FrameDecodeErr> {{", class.name));
+    gen.line("    let (buffer, flags) = parse_property_flags(buffer)?;");
+    gen.line("    // a value for anything past the known properties cannot be skipped");
+    gen.line(&format!("    for index in {}..flags.capacity() {{", count));
+    gen.line("        if flags.is_set(index) {");
+    gen.line("            return Err(FrameDecodeErr::field(\"flags\", buffer, DecodeCause::UnknownProperty(index)));");
+    gen.line("        }");
+    gen.line("    }");
+    gen.line("");
+    gen.line(&format!("    let properties = {}::default();", properties_name(class)));
+    let last = class.properties.len() - 1;
+    for (index, field) in class.properties.iter().enumerate() {
+        let name = wire_name(&class.name, "", &field.name);
+        let value = rust_name(&class.name, "", &field.name);
+        if let Kind::Bit | Kind::ClassId | Kind::MethodId = field.kind {
+            panic!("{} property {}: {:?} properties are not supported", class.name, field.name, field.kind);
+        }
+        // indent the read into the branch taken when the flag is set
+        let mut branch = Codegen { imports: BTreeSet::new(), code: String::new() };
+        if index == last {
+            gen.line(&format!("    if flags.is_set({}) {{", index));
+            branch.read_field(field.kind, &name, &value, "_");
+        } else {
+            gen.line(&format!("    let buffer = if flags.is_set({}) {{", index));
+            branch.read_field(field.kind, &name, &value, "buffer");
+        }
+        for line in branch.code.lines() {
+            gen.line(&format!("    {}", line));
+        }
+        for path in branch.imports {
+            gen.import(&path);
+        }
+        gen.line(&format!("        properties.set_{}({});", value, value));
+        if index == last {
+            gen.line("    }");
+        } else {
+            gen.line("        buffer");
+            gen.line("    } else { buffer };");
+        }
+    }
+    gen.line(&format!("    Ok(Property::{}(properties))", camel(&class.name)));
+    gen.line("}");
+    gen.line("");
+}
+
+fn write_properties(gen: &mut Codegen, class: &Class) {
+    if class.properties.len() > 15 {
+        panic!("{} has more than one flag word of properties", class.name);
+    }
+    gen.import("bytes::BufMut");
+    gen.line(&format!("pub(crate) fn write_{}_properties(properties: &{}, dst: &mut BytesMut) -> Result<(), FrameEncodeErr> {{", class.name, properties_name(class)));
+    gen.line("    let mut flags = 0u16;");
+    gen.line("    let mut data = BytesMut::new();");
+    for (index, field) in class.properties.iter().enumerate() {
+        let value = rust_name(&class.name, "", &field.name);
+        gen.line(&format!("    if let Some({}) = properties.{}() {{", value, value));
+        gen.line(&format!("        flags |= 1 << {};", 15 - index));
+        let mut branch = Codegen { imports: BTreeSet::new(), code: String::new() };
+        branch.write_value(field.kind, &value, "data");
+        gen.line(&format!("    {}", branch.code.trim_end()));
+        for path in branch.imports {
+            gen.import(&path);
+        }
+        gen.line("    }");
+    }
+    gen.line("    dst.put_u16(flags);");
+    gen.line("    dst.put_slice(&data);");
+    gen.line("    Ok(())");
+    gen.line("}");
+    gen.line("");
+}
+
+fn method_variant(class: &Class, method: &Method) -> String {
+    format!("Method::{}Method({}Method::{})", camel(&class.name), camel(&class.name), camel(&method.name))
+}
+
+fn parse_arguments(spec: &Spec) -> String {
+    let mut code = String::new();
+    code.push_str("pub(crate) fn parse_arguments(method: amqp_types::frame::Method, buffer: &[u8]) -> Result<amqp_types::frame::Arguments, crate::error::FrameDecodeErr> {\n");
+    let method_types: Vec<String> = spec.classes.iter().map(|class| format!("{}Method", camel(&class.name))).collect();
+    writeln!(code, "    use amqp_types::frame::{{Method, {}}};", method_types.join(", ")).unwrap();
+    code.push_str("    use crate::error::FrameDecodeErr;\n");
+    code.push_str("    match method {\n");
+    for class in &spec.classes {
+        for method in &class.methods {
+            writeln!(code, "        {} => crate::{}::parse_{}(buffer),", method_variant(class, method), class.name, function_name(class, method)).unwrap();
+        }
+    }
+    // only reached for methods get_method_type never hands out, in_frame adds the ids
+    code.push_str("        _ => Err(FrameDecodeErr::UnknownMethodType(0, 0, 0))\n");
+    code.push_str("    }\n");
+    code.push_str("}\n");
+    code
+}
+
+fn write_arguments(spec: &Spec) -> String {
+    let mut code = String::new();
+    code.push_str("pub(crate) fn write_arguments(args: &amqp_types::frame::Arguments, dst: &mut bytes::BytesMut) -> Result<(), crate::error::FrameEncodeErr> {\n");
+    code.push_str("    use amqp_types::frame::Arguments;\n");
+    code.push_str("    match args {\n");
+    let variants: Vec<String> = spec.classes.iter().flat_map(|class| {
+        class.methods.iter().map(move |method| {
+            format!("        Arguments::{}(args) => crate::{}::write_{}(args, dst)", struct_name(class, method), class.name, function_name(class, method))
+        })
+    }).collect();
+    code.push_str(&variants.join(",\n"));
+    code.push_str("\n    }\n");
+    code.push_str("}\n");
+    code
+}
+
+// class and method ids to and from the amqp-types enums
+fn method_ids(spec: &Spec) -> String {
+    let method_types: Vec<String> = spec.classes.iter().map(|class| format!("{}Method", camel(&class.name))).collect();
+    let imports = format!("    use amqp_types::frame::{{Class, Method, {}}};\n", method_types.join(", "));
+    let mut code = String::new();
+    code.push_str("pub(crate) fn get_method_type(class: amqp_types::frame::Class, method_id: u16) -> Option<amqp_types::frame::Method> {\n");
+    code.push_str(&imports);
+    code.push_str("    let method = match (class, method_id) {\n");
+    for class in &spec.classes {
+        for method in &class.methods {
+            writeln!(code, "        (Class::{}, {}) => {},", camel(&class.name), method.index, method_variant(class, method)).unwrap();
+        }
+    }
+    code.push_str("        _ => return None\n");
+    code.push_str("    };\n");
+    code.push_str("    Some(method)\n");
+    code.push_str("}\n\n");
+
+    code.push_str("pub(crate) fn get_class_id(class: &amqp_types::frame::Class) -> Result<u16, crate::error::FrameEncodeErr> {\n");
+    code.push_str("    use amqp_types::frame::Class;\n");
+    code.push_str("    use crate::error::FrameEncodeErr;\n");
+    code.push_str("    match class {\n");
+    for class in &spec.classes {
+        writeln!(code, "        Class::{} => Ok({}),", camel(&class.name), class.index).unwrap();
+    }
+    code.push_str("        _ => Err(FrameEncodeErr::UnknownClassType)\n");
+    code.push_str("    }\n");
+    code.push_str("}\n\n");
+
+    code.push_str("pub(crate) fn get_method_id(method: &amqp_types::frame::Method) -> Result<u16, crate::error::FrameEncodeErr> {\n");
+    writeln!(code, "    use amqp_types::frame::{{Method, {}}};", method_types.join(", ")).unwrap();
+    code.push_str("    use crate::error::FrameEncodeErr;\n");
+    code.push_str("    let method_id = match method {\n");
+    for class in &spec.classes {
+        for method in &class.methods {
+            writeln!(code, "        {} => {},", method_variant(class, method), method.index).unwrap();
+        }
+    }
+    code.push_str("        _ => return Err(FrameEncodeErr::UnknownMethodType)\n");
+    code.push_str("    };\n");
+    code.push_str("    Ok(method_id)\n");
+    code.push_str("}\n");
+    code
+}
+
+fn method_names(spec: &Spec) -> String {
+    let mut code = String::new();
+    code.push_str("pub(crate) fn method_name(class_id: u16, method_id: u16) -> Option<&'static str> {\n");
+    code.push_str("    let name = match (class_id, method_id) {\n");
+    for class in &spec.classes {
+        for method in &class.methods {
+            writeln!(code, "        ({}, {}) => \"{}.{}\",", class.index, method.index, class.name, method.name).unwrap();
+        }
+    }
+    code.push_str("        _ => return None\n");
+    code.push_str("    };\n");
+    code.push_str("    Some(name)\n");
+    code.push_str("}\n");
+    code
+}
+
+fn method_fields(spec: &Spec) -> String {
+    let mut code = String::new();
+    code.push_str("pub fn method_fields(class_id: u16, method_id: u16) -> Option<&'static [(&'static str, Domain)]> {\n");
+    code.push_str("    let fields: &'static [(&'static str, Domain)] = match (class_id, method_id) {\n");
+    for class in &spec.classes {
+        for method in &class.methods {
+            let fields: Vec<String> = method.fields.iter().map(|field| {
+                let domain = match field.kind {
+                    Kind::Bit => "Bit",
+                    Kind::Octet => "Octet",
+                    Kind::Short | Kind::ClassId | Kind::MethodId => "Short",
+                    Kind::Long => "Long",
+                    Kind::LongLong | Kind::Timestamp => "LongLong",
+                    Kind::ShortStr => "ShortStr",
+                    Kind::LongStr => "LongStr",
+                    Kind::Table => "Table"
+                };
+                format!("(\"{}\", Domain::{})", wire_name(&class.name, &method.name, &field.name), domain)
+            }).collect();
+            writeln!(code, "        ({}, {}) => &[{}],", class.index, method.index, fields.join(", ")).unwrap();
+        }
+    }
+    code.push_str("        _ => return None\n");
+    code.push_str("    };\n");
+    code.push_str("    Some(fields)\n");
+    code.push_str("}\n");
+    code
+}
+
+// Content properties as views of the frame for crate::shared, one
+// Shared<Class>Properties and parse_shared_<class>_properties per class.
+fn shared_properties(spec: &Spec) -> String {
+    let mut code = String::new();
+    for class in spec.classes.iter().filter(|class| !class.properties.is_empty()) {
+        shared_properties_struct(&mut code, class);
+        shared_properties_parser(&mut code, class);
+    }
+    code
+}
+
+// `name` or `{a, b}` after a use path
+fn use_list(names: BTreeSet<&str>) -> String {
+    let names: Vec<&str> = names.into_iter().collect();
+    match names.len() {
+        1 => names[0].to_string(),
+        _ => format!("{{{}}}", names.join(", "))
+    }
+}
+
+fn shared_type(class: &Class, field: &Field) -> &'static str {
+    match field.kind {
+        Kind::Octet => "u8",
+        Kind::Short => "u16",
+        Kind::Long => "u32",
+        Kind::LongLong | Kind::Timestamp => "u64",
+        Kind::ShortStr | Kind::LongStr => "bytes::Bytes",
+        Kind::Table => "crate::shared::SharedFieldTable",
+        kind => panic!("{} property {}: {:?} properties are not supported", class.name, field.name, kind)
+    }
+}
+
+fn shared_properties_struct(code: &mut String, class: &Class) {
+    let name = format!("Shared{}", properties_name(class));
+    writeln!(code, "#[derive(Debug, Clone, PartialEq, Default)]").unwrap();
+    writeln!(code, "pub struct {} {{", name).unwrap();
+    let fields: Vec<String> = class.properties.iter().map(|field| {
+        format!("    {}: Option<{}>", rust_name(&class.name, "", &field.name), shared_type(class, field))
+    }).collect();
+    code.push_str(&fields.join(",\n"));
+    code.push_str("\n}\n\n");
+
+    writeln!(code, "impl {} {{", name).unwrap();
+    for field in &class.properties {
+        let value = rust_name(&class.name, "", &field.name);
+        let shared_type = shared_type(class, field);
+        match field.kind {
+            Kind::ShortStr | Kind::LongStr | Kind::Table => {
+                writeln!(code, "    pub fn {}(&self) -> Option<&{}> {{", value, shared_type).unwrap();
+                writeln!(code, "        self.{}.as_ref()", value).unwrap();
+            }
+            _ => {
+                writeln!(code, "    pub fn {}(&self) -> Option<{}> {{", value, shared_type).unwrap();
+                writeln!(code, "        self.{}", value).unwrap();
+            }
+        }
+        code.push_str("    }\n\n");
+    }
+
+    // the owned amqp-types form, every string and table is copied
+    let owned = properties_name(class);
+    writeln!(code, "    pub fn to_{}_properties(&self) -> Result<amqp_types::frame::{}, amqp_types::error::Error> {{", class.name, owned).unwrap();
+    let mut types = BTreeSet::new();
+    for field in &class.properties {
+        match field.kind {
+            Kind::ShortStr => types.insert("ShortStr"),
+            Kind::LongStr => types.insert("LongStr"),
+            _ => false
+        };
+    }
+    if !types.is_empty() {
+        writeln!(code, "        use amqp_types::{};", use_list(types)).unwrap();
+    }
+    writeln!(code, "        let properties = amqp_types::frame::{}::default();", owned).unwrap();
+    for field in &class.properties {
+        let value = rust_name(&class.name, "", &field.name);
+        let converted = match field.kind {
+            Kind::ShortStr => "ShortStr::with_bytes(value)?",
+            Kind::LongStr => "LongStr::with_bytes(value)?",
+            Kind::Table => "value.to_field_table()?",
+            _ => "value"
+        };
+        match field.kind {
+            Kind::ShortStr | Kind::LongStr | Kind::Table => writeln!(code, "        if let Some(value) = &self.{} {{", value).unwrap(),
+            _ => writeln!(code, "        if let Some(value) = self.{} {{", value).unwrap()
+        }
+        writeln!(code, "            properties.set_{}({});", value, converted).unwrap();
+        code.push_str("        }\n");
+    }
+    code.push_str("        Ok(properties)\n");
+    code.push_str("    }\n");
+    code.push_str("}\n\n");
+}
+
+fn shared_properties_parser(code: &mut String, class: &Class) {
+    let name = format!("Shared{}", properties_name(class));
+    writeln!(code, "pub fn parse_shared_{}_properties(buffer: &bytes::Bytes) -> Result<{}, crate::error::FrameDecodeErr> {{", class.name, name).unwrap();
+    let mut numbers = BTreeSet::new();
+    for field in &class.properties {
+        match field.kind {
+            Kind::Octet => numbers.insert("be_u8"),
+            Kind::Short => numbers.insert("be_u16"),
+            Kind::Long => numbers.insert("be_u32"),
+            Kind::LongLong | Kind::Timestamp => numbers.insert("be_u64"),
+            _ => false
+        };
+    }
+    if !numbers.is_empty() {
+        code.push_str("    use nom::error::ErrorKind;\n");
+        writeln!(code, "    use nom::number::complete::{};", use_list(numbers)).unwrap();
+    }
+    code.push_str("    use crate::common::parse_property_flags;\n");
+    code.push_str("    use crate::error::{FrameDecodeErr, DecodeCause};\n");
+    code.push_str("    let (remain, flags) = parse_property_flags(&buffer[..])?;\n");
+    code.push_str("    // a value for anything past the known properties cannot be skipped\n");
+    writeln!(code, "    for index in crate::{}::{}_PROPERTY_COUNT..flags.capacity() {{", class.name, class.name.to_uppercase()).unwrap();
+    code.push_str("        if flags.is_set(index) {\n");
+    code.push_str("            return Err(FrameDecodeErr::field(\"flags\", remain, DecodeCause::UnknownProperty(index)));\n");
+    code.push_str("        }\n");
+    code.push_str("    }\n");
+    code.push_str("    let buffer = advance(buffer, remain);\n\n");
+
+    let last = class.properties.len() - 1;
+    for (index, field) in class.properties.iter().enumerate() {
+        let wire = wire_name(&class.name, "", &field.name);
+        let value = rust_name(&class.name, "", &field.name);
+        let rest = if index == last { "_" } else { "buffer" };
+        if index == last {
+            writeln!(code, "    let {} = if flags.is_set({}) {{", value, index).unwrap();
+        } else {
+            writeln!(code, "    let (buffer, {}) = if flags.is_set({}) {{", value, index).unwrap();
+        }
+        let parser = match field.kind {
+            Kind::ShortStr => Some("parse_shared_short_string"),
+            Kind::LongStr => Some("parse_shared_long_string"),
+            Kind::Table => Some("parse_shared_field_table"),
+            _ => None
+        };
+        match parser {
+            Some(parser) => {
+                writeln!(code, "        let ({}, value) = match {}(&buffer) {{", rest, parser).unwrap();
+                code.push_str("            Ok(ret) => ret,\n");
+                writeln!(code, "            Err(e) => return Err(e.in_field(\"{}\", &buffer))", wire).unwrap();
+            }
+            None => {
+                let number = match field.kind {
+                    Kind::Octet => "be_u8",
+                    Kind::Short => "be_u16",
+                    Kind::Long => "be_u32",
+                    _ => "be_u64"
+                };
+                writeln!(code, "        let ({}, value) = match {}::<(_, ErrorKind)>(&buffer[..]) {{", rest, number).unwrap();
+                code.push_str("            Ok((remain, value)) => (advance(&buffer, remain), value),\n");
+                writeln!(code, "            Err(e) => return Err(FrameDecodeErr::field(\"{}\", &buffer[..], e))", wire).unwrap();
+            }
+        }
+        code.push_str("        };\n");
+        if index == last {
+            code.push_str("        Some(value)\n");
+            code.push_str("    } else { None };\n");
+        } else {
+            code.push_str("        (buffer, Some(value))\n");
+            code.push_str("    } else { (buffer, None) };\n");
+        }
+    }
+    let fields: Vec<String> = class.properties.iter().map(|field| rust_name(&class.name, "", &field.name)).collect();
+    writeln!(code, "    Ok({} {{ {} }})", name, fields.join(", ")).unwrap();
+    code.push_str("}\n");
+}
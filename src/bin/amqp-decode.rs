@@ -0,0 +1,766 @@
+use std::env;
+use std::fs;
+use std::io::{self, Read, Write, BufWriter};
+use std::process;
+use amqp_types::FrameType;
+use bytes::{Bytes, BytesMut, BufMut};
+use amqp_parser::error::FrameDecodeErr;
+use amqp_parser::parse::parse_amqp_protocal_header;
+use amqp_parser::frame_ref::{parse_frame_ref, FrameRef, FieldRef};
+use amqp_parser::shared::{parse_shared_field_table, parse_shared_basic_properties, SharedFieldValue, SharedFieldTable};
+
+// Decodes a dump of AMQP 0-9-1 traffic, one direction of a connection, and
+// prints every frame with its arguments or properties.
+
+const USAGE: &str = "usage: amqp-decode [-f human|json] [--hex] [FILE]
+       amqp-decode [-f human|json] -s HEX
+
+  FILE              dump to decode, stdin when missing or -
+  --hex             FILE or stdin holds hex text instead of raw bytes
+  -s, --hex-string  decode HEX given on the command line
+  -f, --format      human (default) or json for one JSON object per line
+  -h, --help        show this help
+
+Hex text may separate bytes with spaces, commas or colons and prefix them
+with 0x or \\x. Lines of hexdump -C or xxd output are accepted as well.";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    Human,
+    Json
+}
+
+#[derive(Debug, PartialEq)]
+enum Input {
+    Stdin,
+    File(String),
+    Hex(String)
+}
+
+#[derive(Debug, PartialEq)]
+struct Options {
+    format: Format,
+    // file or stdin holds hex text
+    hex: bool,
+    input: Input
+}
+
+fn parse_args(args: &[String]) -> Result<Option<Options>, String> {
+    let mut format = Format::Human;
+    let mut hex = false;
+    let mut input = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--hex" => hex = true,
+            "-f" | "--format" => {
+                format = match args.next().map(|format| format.as_str()) {
+                    Some("human") => Format::Human,
+                    Some("json") => Format::Json,
+                    Some(other) => return Err(format!("unknown format {}", other)),
+                    None => return Err(format!("{} needs human or json", arg))
+                };
+            }
+            "-s" | "--hex-string" => {
+                let text = args.next().ok_or_else(|| format!("{} needs a hex string", arg))?;
+                if input.is_some() {
+                    return Err(String::from("only one input can be decoded"));
+                }
+                input = Some(Input::Hex(text.clone()));
+            }
+            "-" => {
+                if input.is_some() {
+                    return Err(String::from("only one input can be decoded"));
+                }
+                input = Some(Input::Stdin);
+            }
+            option if option.starts_with('-') => return Err(format!("unknown option {}", option)),
+            path => {
+                if input.is_some() {
+                    return Err(String::from("only one input can be decoded"));
+                }
+                input = Some(Input::File(path.to_string()));
+            }
+        }
+    }
+    let input = input.unwrap_or(Input::Stdin);
+    if hex && matches!(input, Input::Hex(_)) {
+        return Err(String::from("--hex applies to FILE or stdin, not to -s"));
+    }
+    Ok(Some(Options { format, hex, input }))
+}
+
+// What a line of hex text turned out to be, told apart line by line
+enum HexLine<'a> {
+    // hexdump's "*" for lines equal to the one before
+    Repeat,
+    // offset and the hex after it, ASCII column dropped: hexdump -C
+    // ("offset  hex  |ascii|"), xxd ("offset: hex  ascii") and the bare
+    // offset hexdump ends with
+    Dump(usize, &'a str),
+    // hex with optional 0x or \x prefixes and separators
+    Plain(&'a str)
+}
+
+fn classify_hex_line(line: &str, in_dump: bool) -> HexLine<'_> {
+    let trimmed = line.trim();
+    if trimmed == "*" {
+        return HexLine::Repeat;
+    }
+    let offset_end = trimmed.find(|c: char| !c.is_ascii_hexdigit()).unwrap_or(trimmed.len());
+    if !(6..=16).contains(&offset_end) {
+        return HexLine::Plain(line);
+    }
+    let offset = match usize::from_str_radix(&trimmed[..offset_end], 16) {
+        Ok(offset) => offset,
+        Err(_) => return HexLine::Plain(line)
+    };
+    let rest = &trimmed[offset_end..];
+    if let Some(hex) = rest.strip_prefix(": ") {
+        // xxd pads the hex to a fixed width, the ASCII column follows two spaces
+        let hex = hex.trim_start();
+        let end = hex.find("  ").unwrap_or(hex.len());
+        HexLine::Dump(offset, &hex[..end])
+    } else if rest.starts_with("  ") && rest.contains('|') {
+        // the first '|' opens the ASCII column, later ones are data
+        HexLine::Dump(offset, &rest[..rest.find('|').unwrap()])
+    } else if rest.is_empty() && in_dump {
+        HexLine::Dump(offset, rest)
+    } else {
+        HexLine::Plain(line)
+    }
+}
+
+// hex digits of `text` onto `digits`, `column` is where text starts in its line
+fn push_hex_digits(text: &str, number: usize, column: usize, digits: &mut Vec<u8>) -> Result<(), String> {
+    let text = text.replace("0x", "  ").replace("0X", "  ").replace("\\x", "  ");
+    for (index, c) in text.chars().enumerate() {
+        match c {
+            c if c.is_ascii_hexdigit() => digits.push(c.to_digit(16).unwrap_or(0) as u8),
+            ' ' | '\t' | ',' | ':' | ';' | '-' | '[' | ']' | '"' | '\'' => {},
+            c => return Err(format!("invalid hex character {:?} on line {}, column {}", c, number + 1, column + index + 1))
+        }
+    }
+    Ok(())
+}
+
+fn hex_bytes(digits: &[u8]) -> Result<Vec<u8>, String> {
+    let pairs = digits.chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return Err(String::from("odd number of hex digits"));
+    }
+    Ok(pairs.map(|pair| pair[0] << 4 | pair[1]).collect())
+}
+
+// Bytes out of hex text as copied from logs: plain hex, hexdump -C or xxd
+// output, decided for each line on its own.
+fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    // plain hex may split a byte across lines
+    let mut digits = Vec::new();
+    // offset of the first dump line minus the bytes before it
+    let mut base = None;
+    let mut last_line: Vec<u8> = Vec::new();
+    let mut repeat = false;
+    for (number, line) in text.lines().enumerate() {
+        let in_dump = base.is_some() && digits.is_empty();
+        match classify_hex_line(line, in_dump) {
+            HexLine::Plain(line) => push_hex_digits(line, number, 0, &mut digits)?,
+            HexLine::Repeat => {
+                if last_line.is_empty() {
+                    return Err(format!("\"*\" on line {} repeats nothing", number + 1));
+                }
+                repeat = true;
+            }
+            HexLine::Dump(offset, hex) => {
+                data.extend(hex_bytes(&digits)?);
+                digits.clear();
+                let base = *base.get_or_insert(offset.saturating_sub(data.len()));
+                let position = offset.saturating_sub(base);
+                if repeat {
+                    if position < data.len() {
+                        return Err(format!("offset {:08x} on line {} is behind the data", offset, number + 1));
+                    }
+                    while data.len() < position {
+                        let missing = (position - data.len()).min(last_line.len());
+                        data.extend_from_slice(&last_line[..missing]);
+                    }
+                    repeat = false;
+                }
+                let column = hex.as_ptr() as usize - line.as_ptr() as usize;
+                let mut line_digits = Vec::new();
+                push_hex_digits(hex, number, column, &mut line_digits)?;
+                let bytes = hex_bytes(&line_digits)?;
+                data.extend_from_slice(&bytes);
+                if !bytes.is_empty() {
+                    last_line = bytes;
+                }
+            }
+        }
+    }
+    if repeat {
+        return Err(String::from("\"*\" without the offset that ends it"));
+    }
+    data.extend(hex_bytes(&digits)?);
+    Ok(data)
+}
+
+fn read_input(options: &Options) -> Result<Vec<u8>, String> {
+    let data = match &options.input {
+        Input::Hex(text) => return parse_hex(text),
+        Input::File(path) => fs::read(path).map_err(|e| format!("can't read {}: {}", path, e))?,
+        Input::Stdin => {
+            let mut data = Vec::new();
+            io::stdin().read_to_end(&mut data).map_err(|e| format!("can't read stdin: {}", e))?;
+            data
+        }
+    };
+    if options.hex {
+        let text = String::from_utf8(data).map_err(|_| String::from("hex input is not text"))?;
+        return parse_hex(&text);
+    }
+    Ok(data)
+}
+
+// what gets printed for an argument, property or table entry
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    UInt(u64),
+    Float(f64),
+    // already in decimal notation
+    Decimal(String),
+    Str(Vec<u8>),
+    Bytes(Vec<u8>),
+    Array(Vec<Value>),
+    Object(Vec<(String, Value)>)
+}
+
+fn decimal(scale: u8, value: u32) -> String {
+    let digits = value.to_string();
+    let scale = scale as usize;
+    if scale == 0 {
+        return digits;
+    }
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (integer, fraction) = digits.split_at(digits.len() - scale);
+    format!("{}.{}", integer, fraction)
+}
+
+fn shared_value(value: &SharedFieldValue) -> Value {
+    match value {
+        SharedFieldValue::Boolean(value) => Value::Bool(*value),
+        SharedFieldValue::I8(value) => Value::Int(*value as i64),
+        SharedFieldValue::U8(value) => Value::UInt(*value as u64),
+        SharedFieldValue::I16(value) => Value::Int(*value as i64),
+        SharedFieldValue::U16(value) => Value::UInt(*value as u64),
+        SharedFieldValue::I32(value) => Value::Int(*value as i64),
+        SharedFieldValue::U32(value) => Value::UInt(*value as u64),
+        SharedFieldValue::I64(value) => Value::Int(*value),
+        SharedFieldValue::U64(value) => Value::UInt(*value),
+        SharedFieldValue::F32(value) => Value::Float(*value as f64),
+        SharedFieldValue::F64(value) => Value::Float(*value),
+        SharedFieldValue::Timestamp(value) => Value::UInt(*value),
+        SharedFieldValue::Decimal(scale, value) => Value::Decimal(decimal(*scale, *value)),
+        SharedFieldValue::LongStr(value) => Value::Str(value.to_vec()),
+        SharedFieldValue::ByteArray(value) => Value::Bytes(value.to_vec()),
+        SharedFieldValue::FieldArray(values) => Value::Array(values.iter().map(shared_value).collect()),
+        SharedFieldValue::FieldTable(table) => table_value(table),
+        SharedFieldValue::Void => Value::Null
+    }
+}
+
+fn table_value(table: &SharedFieldTable) -> Value {
+    Value::Object(table.iter().map(|(name, value)| (String::from_utf8_lossy(name).into_owned(), shared_value(value))).collect())
+}
+
+// FieldRef::Table holds the table without its length
+fn parse_table(content: &[u8]) -> Result<Value, FrameDecodeErr> {
+    let mut buffer = BytesMut::with_capacity(content.len() + 4);
+    buffer.put_u32(content.len() as u32);
+    buffer.put_slice(content);
+    let (_, table) = parse_shared_field_table(&buffer.freeze())?;
+    Ok(table_value(&table))
+}
+
+fn field_value(name: &str, field: FieldRef) -> Result<Value, String> {
+    let value = match field {
+        FieldRef::Octet(value) => Value::UInt(value as u64),
+        FieldRef::Short(value) => Value::UInt(value as u64),
+        FieldRef::Long(value) => Value::UInt(value as u64),
+        FieldRef::LongLong(value) => Value::UInt(value),
+        FieldRef::Bit(value) => Value::Bool(value),
+        FieldRef::ShortStr(value) => Value::Str(value.to_vec()),
+        FieldRef::LongStr(value) => Value::Str(value.to_vec()),
+        FieldRef::Table(content) => parse_table(content).map_err(|e| format!("field {}: {}", name, e))?
+    };
+    Ok(value)
+}
+
+// one frame, ready to be printed either way
+struct Description {
+    kind: &'static str,
+    // input offset of the first byte
+    offset: usize,
+    headline: String,
+    meta: Vec<(&'static str, Value)>,
+    fields_key: &'static str,
+    fields: Vec<(String, Value)>
+}
+
+// why decoding stopped
+#[derive(Debug)]
+struct Failure {
+    frame_offset: usize,
+    // input offset of the byte the decoder choked on, when known
+    error_offset: Option<usize>,
+    message: String
+}
+
+impl Failure {
+    fn new(frame_offset: usize, message: String) -> Self {
+        Failure { frame_offset, error_offset: None, message }
+    }
+
+    fn from_err(frame_offset: usize, err: &FrameDecodeErr) -> Self {
+        let error_offset = match err {
+            FrameDecodeErr::Syntax(syntax) => syntax.offset().map(|offset| frame_offset + offset),
+            _ => None
+        };
+        Failure { frame_offset, error_offset, message: err.to_string() }
+    }
+}
+
+fn describe_frame(offset: usize, frame: &FrameRef) -> Result<Description, Failure> {
+    let channel = frame.channel();
+    let mut meta = vec![("channel", Value::UInt(channel as u64))];
+    let mut fields = Vec::new();
+    let (kind, headline, fields_key) = match frame.frame_type() {
+        FrameType::METHOD => {
+            let method = match frame.method() {
+                Some(method) => method,
+                None => return Err(Failure::new(offset, String::from("method frame without method ids")))
+            };
+            let (class_id, method_id) = (method.class_id(), method.method_id());
+            meta.push(("class_id", Value::UInt(class_id as u64)));
+            meta.push(("method_id", Value::UInt(method_id as u64)));
+            let headline = match method.name() {
+                Some(name) => {
+                    meta.push(("method", Value::Str(name.as_bytes().to_vec())));
+                    format!("channel {} {} ({}.{})", channel, name, class_id, method_id)
+                }
+                None => format!("channel {} unknown method {}.{}", channel, class_id, method_id)
+            };
+            match method.fields() {
+                Ok(iter) => {
+                    for field in iter {
+                        let (name, field) = field.map_err(|e| Failure::from_err(offset, &e))?;
+                        let value = field_value(name, field).map_err(|message| Failure::new(offset, message))?;
+                        fields.push((name.to_string(), value));
+                    }
+                }
+                // still worth showing what is there
+                Err(FrameDecodeErr::UnknownMethodType(..)) => fields.push((String::from("arguments"), Value::Bytes(method.arguments().to_vec()))),
+                Err(e) => return Err(Failure::from_err(offset, &e))
+            }
+            ("method", headline, "arguments")
+        }
+        FrameType::HEADER => {
+            let header = match frame.content_header() {
+                Some(header) => header,
+                None => return Err(Failure::new(offset, String::from("content header frame too short")))
+            };
+            meta.push(("class_id", Value::UInt(header.class_id() as u64)));
+            meta.push(("weight", Value::UInt(header.weight() as u64)));
+            meta.push(("body_size", Value::UInt(header.body_size())));
+            let properties = parse_shared_basic_properties(&Bytes::copy_from_slice(header.properties()))
+                .map_err(|e| Failure::new(offset, format!("content header properties: {}", e)))?;
+            let strings = [
+                ("content_type", properties.content_type()),
+                ("content_encoding", properties.content_encoding())
+            ];
+            for (name, value) in strings.iter() {
+                if let Some(value) = value {
+                    fields.push((name.to_string(), Value::Str(value.to_vec())));
+                }
+            }
+            if let Some(headers) = properties.headers() {
+                fields.push((String::from("headers"), table_value(headers)));
+            }
+            for (name, value) in [("delivery_mode", properties.delivery_mode()), ("priority", properties.priority())].iter() {
+                if let Some(value) = value {
+                    fields.push((name.to_string(), Value::UInt(*value as u64)));
+                }
+            }
+            let strings = [
+                ("correlation_id", properties.correlation_id()),
+                ("reply_to", properties.reply_to()),
+                ("expiration", properties.expiration()),
+                ("message_id", properties.message_id())
+            ];
+            for (name, value) in strings.iter() {
+                if let Some(value) = value {
+                    fields.push((name.to_string(), Value::Str(value.to_vec())));
+                }
+            }
+            if let Some(timestamp) = properties.timestamp() {
+                fields.push((String::from("timestamp"), Value::UInt(timestamp)));
+            }
+            let strings = [
+                ("type", properties.message_type()),
+                ("user_id", properties.user_id()),
+                ("app_id", properties.app_id()),
+                ("cluster_id", properties.cluster_id())
+            ];
+            for (name, value) in strings.iter() {
+                if let Some(value) = value {
+                    fields.push((name.to_string(), Value::Str(value.to_vec())));
+                }
+            }
+            let headline = format!("channel {} content header, class {}, body size {}", channel, header.class_id(), header.body_size());
+            ("header", headline, "properties")
+        }
+        FrameType::BODY => {
+            let payload = frame.payload();
+            meta.push(("size", Value::UInt(payload.len() as u64)));
+            meta.push(("payload", Value::Bytes(payload.to_vec())));
+            // a preview is enough in the human output
+            let preview = &payload[..payload.len().min(64)];
+            let more = if preview.len() < payload.len() { "..." } else { "" };
+            let headline = format!("channel {} content body, {} bytes: {}{}", channel, payload.len(), human(&Value::Str(preview.to_vec())), more);
+            ("body", headline, "")
+        }
+        FrameType::HEARTBEAT => ("heartbeat", String::from("heartbeat"), ""),
+        FrameType::UNKNOWN => return Err(Failure::new(offset, String::from("unknown frame type")))
+    };
+    Ok(Description { kind, offset, headline, meta, fields_key, fields })
+}
+
+fn describe_header(offset: usize, input: &[u8]) -> Result<Description, Failure> {
+    let header = parse_amqp_protocal_header(input).map_err(|e| match e {
+        FrameDecodeErr::Incomplete => Failure::new(offset, String::from("input ends inside the protocol header")),
+        e => Failure::from_err(offset, &e)
+    })?;
+    let ids = (header.major_id(), header.minor_id(), header.major_version(), header.minor_version());
+    Ok(Description {
+        kind: "protocol_header",
+        offset,
+        headline: format!("protocol header AMQP {}-{}-{}-{}", ids.0, ids.1, ids.2, ids.3),
+        meta: vec![
+            ("major_id", Value::UInt(ids.0 as u64)),
+            ("minor_id", Value::UInt(ids.1 as u64)),
+            ("major_version", Value::UInt(ids.2 as u64)),
+            ("minor_version", Value::UInt(ids.3 as u64))
+        ],
+        fields_key: "",
+        fields: Vec::new()
+    })
+}
+
+// Walks the input frame by frame, protocol headers may show up between
+// frames when several dumps were glued together.
+fn decode(input: &[u8], mut emit: impl FnMut(Description) -> io::Result<()>) -> Result<Result<usize, Failure>, io::Error> {
+    let mut offset = 0;
+    let mut count = 0;
+    while offset < input.len() {
+        let rest = &input[offset..];
+        let (length, description) = if rest.starts_with(b"AMQP") || (rest.len() < 4 && b"AMQP".starts_with(rest)) {
+            match describe_header(offset, rest) {
+                Ok(description) => (8, description),
+                Err(failure) => return Ok(Err(failure))
+            }
+        } else {
+            match parse_frame_ref(rest) {
+                Ok((length, frame)) => match describe_frame(offset, &frame) {
+                    Ok(description) => (length as usize, description),
+                    Err(failure) => return Ok(Err(failure))
+                },
+                Err(FrameDecodeErr::Incomplete) => {
+                    return Ok(Err(Failure::new(offset, format!("input ends inside a frame, {} bytes left", rest.len()))));
+                }
+                Err(e) => return Ok(Err(Failure::from_err(offset, &e)))
+            }
+        };
+        emit(description)?;
+        offset += length;
+        count += 1;
+    }
+    Ok(Ok(count))
+}
+
+fn escape_human(bytes: &[u8]) -> String {
+    let mut text = String::from("\"");
+    match std::str::from_utf8(bytes) {
+        Ok(string) => {
+            for c in string.chars() {
+                match c {
+                    '"' => text.push_str("\\\""),
+                    '\\' => text.push_str("\\\\"),
+                    '\n' => text.push_str("\\n"),
+                    '\r' => text.push_str("\\r"),
+                    '\t' => text.push_str("\\t"),
+                    c if c.is_control() => text.push_str(&format!("\\x{:02x}", c as u32)),
+                    c => text.push(c)
+                }
+            }
+        }
+        Err(_) => {
+            for byte in bytes {
+                match byte {
+                    b'"' => text.push_str("\\\""),
+                    b'\\' => text.push_str("\\\\"),
+                    0x20..=0x7e => text.push(*byte as char),
+                    byte => text.push_str(&format!("\\x{:02x}", byte))
+                }
+            }
+        }
+    }
+    text.push('"');
+    text
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn human(value: &Value) -> String {
+    match value {
+        Value::Null => String::from("void"),
+        Value::Bool(value) => value.to_string(),
+        Value::Int(value) => value.to_string(),
+        Value::UInt(value) => value.to_string(),
+        Value::Float(value) => value.to_string(),
+        Value::Decimal(value) => value.clone(),
+        Value::Str(bytes) => escape_human(bytes),
+        Value::Bytes(bytes) => format!("0x{}", to_hex(bytes)),
+        Value::Array(values) => format!("[{}]", values.iter().map(human).collect::<Vec<_>>().join(", ")),
+        Value::Object(entries) => {
+            let entries: Vec<String> = entries.iter().map(|(name, value)| format!("{}: {}", name, human(value))).collect();
+            format!("{{{}}}", entries.join(", "))
+        }
+    }
+}
+
+fn escape_json(text: &str) -> String {
+    let mut escaped = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c)
+        }
+    }
+    escaped.push('"');
+    escaped
+}
+
+// strings that are not UTF-8 lose their odd bytes, byte arrays and bodies
+// are written as hex strings
+fn json(value: &Value) -> String {
+    match value {
+        Value::Null => String::from("null"),
+        Value::Bool(value) => value.to_string(),
+        Value::Int(value) => value.to_string(),
+        Value::UInt(value) => value.to_string(),
+        Value::Float(value) if value.is_finite() => value.to_string(),
+        Value::Float(_) => String::from("null"),
+        Value::Decimal(value) => value.clone(),
+        Value::Str(bytes) => escape_json(&String::from_utf8_lossy(bytes)),
+        Value::Bytes(bytes) => escape_json(&to_hex(bytes)),
+        Value::Array(values) => format!("[{}]", values.iter().map(json).collect::<Vec<_>>().join(",")),
+        Value::Object(entries) => json_object(entries.iter().map(|(name, value)| (name.as_str(), value)))
+    }
+}
+
+fn json_object<'a>(entries: impl Iterator<Item = (&'a str, &'a Value)>) -> String {
+    let entries: Vec<String> = entries.map(|(name, value)| format!("{}:{}", escape_json(name), json(value))).collect();
+    format!("{{{}}}", entries.join(","))
+}
+
+fn json_line(description: &Description) -> String {
+    let offset = Value::UInt(description.offset as u64);
+    let kind = Value::Str(description.kind.as_bytes().to_vec());
+    let fields = Value::Object(description.fields.clone());
+    let mut entries = vec![("offset", &offset), ("type", &kind)];
+    entries.extend(description.meta.iter().map(|(name, value)| (*name, value)));
+    if !description.fields_key.is_empty() {
+        entries.push((description.fields_key, &fields));
+    }
+    json_object(entries.into_iter())
+}
+
+fn write_human(out: &mut impl Write, description: &Description) -> io::Result<()> {
+    writeln!(out, "@{} {}", description.offset, description.headline)?;
+    for (name, value) in description.fields.iter() {
+        writeln!(out, "    {}: {}", name, human(value))?;
+    }
+    Ok(())
+}
+
+// rows of 16 bytes from the start of the frame to the bad byte, with a
+// marker under it
+fn dump(input: &[u8], failure: &Failure) -> String {
+    let bad = failure.error_offset.unwrap_or(failure.frame_offset).min(input.len().saturating_sub(1));
+    let first_row = failure.frame_offset / 16;
+    // long frames only show the rows just before the bad byte
+    let first_row = first_row.max((bad / 16).saturating_sub(3));
+    let mut text = String::new();
+    for row in first_row..=bad / 16 {
+        let start = row * 16;
+        let end = (start + 16).min(input.len());
+        let bytes: Vec<String> = input[start..end].iter().map(|byte| format!("{:02x}", byte)).collect();
+        text.push_str(&format!("  {:08x}  {}\n", start, bytes.join(" ")));
+    }
+    if failure.error_offset.is_some() {
+        text.push_str(&format!("  {}{}^^\n", " ".repeat(10), " ".repeat((bad % 16) * 3)));
+    }
+    text
+}
+
+fn run(options: &Options) -> i32 {
+    let input = match read_input(options) {
+        Ok(input) => input,
+        Err(message) => {
+            eprintln!("amqp-decode: {}", message);
+            return 2;
+        }
+    };
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    let format = options.format;
+    let result = decode(&input, |description| match format {
+        Format::Human => write_human(&mut out, &description),
+        Format::Json => writeln!(out, "{}", json_line(&description))
+    });
+    let result = match result {
+        Ok(result) => result,
+        // most likely a closed pipe, nobody is reading any more
+        Err(_) => return 0
+    };
+    match result {
+        Ok(_) => {
+            let _ = out.flush();
+            0
+        }
+        Err(failure) => {
+            if format == Format::Json {
+                let mut entries = vec![
+                    ("offset", Value::UInt(failure.frame_offset as u64)),
+                    ("type", Value::Str(b"error".to_vec())),
+                    ("error", Value::Str(failure.message.as_bytes().to_vec()))
+                ];
+                if let Some(error_offset) = failure.error_offset {
+                    entries.push(("error_offset", Value::UInt(error_offset as u64)));
+                }
+                let _ = writeln!(out, "{}", json_object(entries.iter().map(|(name, value)| (*name, value))));
+            }
+            let _ = out.flush();
+            match failure.error_offset {
+                Some(error_offset) => eprintln!("amqp-decode: frame at byte {} is bad at byte {}: {}", failure.frame_offset, error_offset, failure.message),
+                None => eprintln!("amqp-decode: frame at byte {} is bad: {}", failure.frame_offset, failure.message)
+            }
+            eprint!("{}", dump(&input, &failure));
+            1
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = match parse_args(&args) {
+        Ok(Some(options)) => options,
+        Ok(None) => {
+            println!("{}", USAGE);
+            return;
+        }
+        Err(message) => {
+            eprintln!("amqp-decode: {}\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    process::exit(run(&options));
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse_hex, parse_args, decode, json_line, Options, Input, Format};
+
+    #[test]
+    fn test_parse_hex() {
+        let expected = vec![0x01u8, 0x00, 0x0a, 0xce];
+        assert_eq!(parse_hex("01 00 0a ce").unwrap(), expected);
+        assert_eq!(parse_hex("0x01, 0x00, 0x0A, 0xce").unwrap(), expected);
+        assert_eq!(parse_hex("\\x01\\x00\\x0a\\xce").unwrap(), expected);
+        assert_eq!(parse_hex("01000ace\n").unwrap(), expected);
+        assert_eq!(parse_hex("00000000  01 00 0a ce                                       |....|").unwrap(), expected);
+        assert_eq!(parse_hex("00000000: 0100 0ace                                ....").unwrap(), expected);
+        assert!(parse_hex("01 0g").is_err());
+        assert!(parse_hex("010").is_err());
+        assert!(parse_hex("*\n00000010").is_err());
+
+        // protocol header, 40 zero bytes, a heartbeat and a '|'
+        let mut expected = b"AMQP\x00\x00\x09\x01".to_vec();
+        expected.extend_from_slice(&[0u8; 40]);
+        expected.extend_from_slice(b"\x08\x00\x00\x00\x00\x00\x00\xce|");
+        let hexdump = concat!(
+            "00000000  41 4d 51 50 00 00 09 01  00 00 00 00 00 00 00 00  |AMQP............|\n",
+            "00000010  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\n",
+            "*\n",
+            "00000030  08 00 00 00 00 00 00 ce  7c                       |........||\n",
+            "00000039\n");
+        assert_eq!(parse_hex(hexdump).unwrap(), expected);
+        let xxd = concat!(
+            "00000000: 414d 5150 0000 0901 0000 0000 0000 0000  AMQP............\n",
+            "00000010: 0000 0000 0000 0000 0000 0000 0000 0000  ................\n",
+            "00000020: 0000 0000 0000 0000 0000 0000 0000 0000  ................\n",
+            "00000030: 0800 0000 0000 00ce 7c                   ........|\n");
+        assert_eq!(parse_hex(xxd).unwrap(), expected);
+
+        let args: Vec<String> = ["-f", "json", "-s", "0800"].iter().map(|arg| arg.to_string()).collect();
+        assert_eq!(parse_args(&args).unwrap(), Some(Options { format: Format::Json, hex: false, input: Input::Hex(String::from("0800")) }));
+    }
+
+    #[test]
+    fn test_decode() {
+        // protocol header, then basic.publish on channel 5 to exchange "ex"
+        // with routing key "rk" and mandatory set
+        let mut input = b"AMQP\x00\x00\x09\x01".to_vec();
+        input.extend_from_slice(&[0x01, 0x00, 0x05, 0x00, 0x00, 0x00, 0x0d,
+            0x00, 0x3c, 0x00, 0x28, 0x00, 0x00, 0x02, b'e', b'x', 0x02, b'r', b'k', 0x01, 0xce]);
+        let mut lines = Vec::new();
+        let count = decode(&input, |description| {
+            lines.push(json_line(&description));
+            Ok(())
+        }).unwrap().unwrap();
+        assert_eq!(count, 2);
+        assert_eq!(lines[0], r#"{"offset":0,"type":"protocol_header","major_id":0,"minor_id":0,"major_version":9,"minor_version":1}"#);
+        assert_eq!(lines[1], concat!(r#"{"offset":8,"type":"method","channel":5,"class_id":60,"method_id":40,"method":"basic.publish","#,
+            r#""arguments":{"ticket":0,"exchange":"ex","routing_key":"rk","mandatory":true,"immediate":false}}"#));
+
+        // routing key announced with 9 bytes, the frame only has 3 left
+        input[24] = 0x09;
+        let failure = decode(&input, |_| Ok(())).unwrap().unwrap_err();
+        assert_eq!(failure.frame_offset, 8);
+        assert_eq!(failure.error_offset, Some(24));
+
+        // the input stops 3 bytes short of the frame end
+        input[24] = 0x02;
+        input.truncate(input.len() - 3);
+        let mut count = 0;
+        let failure = decode(&input, |_| {
+            count += 1;
+            Ok(())
+        }).unwrap().unwrap_err();
+        assert_eq!(count, 1);
+        assert_eq!(failure.frame_offset, 8);
+        assert_eq!(failure.error_offset, None);
+        assert_eq!(failure.message, "input ends inside a frame, 18 bytes left");
+    }
+}
@@ -0,0 +1,546 @@
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use bytes::{Bytes, BytesMut, BufMut};
+use tokio_util::codec::{Decoder, Encoder};
+use crate::error::CaptureErr;
+use crate::frame_codec::{FrameCodec, DecodedFrame, Role};
+use crate::encode::{write_amqp_protocol_header, write_frame};
+use crate::parse::{PROTOCOL_HEADER_SIZE, FRAME_PREFIX_LENGTH, FRAME_END_LENGTH};
+
+// Capture file layout, all numbers big endian like on the wire:
+//
+// +---magic: "AMQPCAP"---+--version: u8--+
+// then per frame
+// +--direction: u8--+--timestamp: u64--+--connection id: u32--+--length: u32--+--frame--+
+//
+// The timestamp counts microseconds since the unix epoch, the frame is kept
+// in wire form, protocol headers included. Records are only ever appended.
+
+pub const CAPTURE_MAGIC: &[u8; 7] = b"AMQPCAP";
+pub const CAPTURE_VERSION: u8 = 1;
+const RECORD_HEADER_SIZE: usize = 17;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    ClientToServer,
+    ServerToClient
+}
+
+impl Direction {
+    fn from_u8(direction: u8) -> Result<Direction, CaptureErr> {
+        match direction {
+            0 => Ok(Direction::ClientToServer),
+            1 => Ok(Direction::ServerToClient),
+            _ => Err(CaptureErr::InvalidDirection(direction))
+        }
+    }
+
+    fn as_u8(&self) -> u8 {
+        match self {
+            Direction::ClientToServer => 0,
+            Direction::ServerToClient => 1
+        }
+    }
+
+    // role of the FrameCodec reading frames going this way
+    fn decoder_role(&self) -> Role {
+        match self {
+            Direction::ClientToServer => Role::Server,
+            Direction::ServerToClient => Role::Client
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    direction: Direction,
+    // microseconds since the unix epoch
+    timestamp: u64,
+    connection_id: u32,
+    data: Bytes
+}
+
+impl Record {
+    pub fn new(direction: Direction, timestamp: u64, connection_id: u32, data: Bytes) -> Self {
+        Record { direction, timestamp, connection_id, data }
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn connection_id(&self) -> u32 {
+        self.connection_id
+    }
+
+    // the frame as it went over the wire
+    pub fn data(&self) -> &Bytes {
+        &self.data
+    }
+}
+
+pub fn now_micros() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|elapsed| elapsed.as_micros() as u64).unwrap_or(0)
+}
+
+fn encode_frame(frame: &DecodedFrame) -> Result<Bytes, CaptureErr> {
+    let mut data = BytesMut::new();
+    match frame {
+        DecodedFrame::ProtocolHeader(header) => write_amqp_protocol_header(header, &mut data),
+        DecodedFrame::AmqpFrame(frame) => write_frame(frame, &mut data)?
+    }
+    Ok(data.freeze())
+}
+
+pub struct CaptureWriter<W: Write> {
+    writer: W
+}
+
+impl<W: Write> CaptureWriter<W> {
+    // starts a new capture with its file header
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(CAPTURE_MAGIC)?;
+        writer.write_all(&[CAPTURE_VERSION])?;
+        Ok(CaptureWriter { writer })
+    }
+
+    // continues a capture whose header is already written
+    pub fn append(writer: W) -> Self {
+        CaptureWriter { writer }
+    }
+
+    pub fn write_record(&mut self, record: &Record) -> io::Result<()> {
+        let mut header = BytesMut::with_capacity(RECORD_HEADER_SIZE);
+        header.put_u8(record.direction.as_u8());
+        header.put_u64(record.timestamp);
+        header.put_u32(record.connection_id);
+        header.put_u32(record.data.len() as u32);
+        self.writer.write_all(&header)?;
+        self.writer.write_all(&record.data)
+    }
+
+    pub fn record(&mut self, connection_id: u32, direction: Direction, timestamp: u64, frame: &DecodedFrame) -> Result<(), CaptureErr> {
+        let data = encode_frame(frame)?;
+        self.write_record(&Record::new(direction, timestamp, connection_id, data))?;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+// bytes of the protocol header or frame at the front of `src`, None until
+// the frame length is readable
+fn next_frame_length(src: &[u8]) -> Option<usize> {
+    match src.first() {
+        Some(b'A') => Some(PROTOCOL_HEADER_SIZE),
+        Some(_) if src.len() >= FRAME_PREFIX_LENGTH as usize => {
+            let payload_length = u32::from_be_bytes([src[3], src[4], src[5], src[6]]) as usize;
+            Some(payload_length.saturating_add((FRAME_PREFIX_LENGTH + FRAME_END_LENGTH) as usize))
+        }
+        _ => None
+    }
+}
+
+// FrameCodec that writes every frame it decodes or encodes to a capture
+pub struct FrameRecorder<W: Write> {
+    codec: FrameCodec,
+    capture: CaptureWriter<W>,
+    connection_id: u32
+}
+
+impl<W: Write> FrameRecorder<W> {
+    pub fn new(codec: FrameCodec, capture: CaptureWriter<W>, connection_id: u32) -> Self {
+        FrameRecorder { codec, capture, connection_id }
+    }
+
+    pub fn codec(&self) -> &FrameCodec {
+        &self.codec
+    }
+
+    pub fn codec_mut(&mut self) -> &mut FrameCodec {
+        &mut self.codec
+    }
+
+    pub fn capture_mut(&mut self) -> &mut CaptureWriter<W> {
+        &mut self.capture
+    }
+
+    pub fn into_capture(self) -> CaptureWriter<W> {
+        self.capture
+    }
+
+    fn incoming(&self) -> Direction {
+        match self.codec.role() {
+            Role::Server => Direction::ClientToServer,
+            Role::Client => Direction::ServerToClient
+        }
+    }
+
+    fn outgoing(&self) -> Direction {
+        match self.incoming() {
+            Direction::ClientToServer => Direction::ServerToClient,
+            Direction::ServerToClient => Direction::ClientToServer
+        }
+    }
+}
+
+impl<W: Write> Decoder for FrameRecorder<W> {
+    type Item = DecodedFrame;
+    type Error = CaptureErr;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        // the codec drops what it consumes, so a complete frame is copied
+        // first; a partial one is not, it can only be waited on or rejected
+        let frame = match next_frame_length(src) {
+            Some(length) if length <= src.len() => Some(Bytes::copy_from_slice(&src[..length])),
+            _ => None
+        };
+        let before = src.len();
+        let decoded = self.codec.decode(src);
+        let consumed = before - src.len();
+        let data = match (&decoded, frame) {
+            (Ok(None), _) => return Ok(None),
+            (_, Some(frame)) if consumed > 0 => frame.slice(..consumed),
+            // a frame rejected before it was split off is still at the front
+            (_, Some(frame)) => frame,
+            // rejected from its first bytes, src holds no more than this frame
+            (_, None) => Bytes::copy_from_slice(&src[..])
+        };
+        let record = Record::new(self.incoming(), now_micros(), self.connection_id, data);
+        self.capture.write_record(&record)?;
+        decoded.map_err(CaptureErr::from)
+    }
+}
+
+impl<W: Write> Encoder<DecodedFrame> for FrameRecorder<W> {
+    type Error = CaptureErr;
+
+    fn encode(&mut self, item: DecodedFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let start = dst.len();
+        self.codec.encode(item, dst)?;
+        let record = Record::new(self.outgoing(), now_micros(), self.connection_id, Bytes::copy_from_slice(&dst[start..]));
+        self.capture.write_record(&record)?;
+        Ok(())
+    }
+}
+
+pub struct CaptureReader<R: Read> {
+    reader: R
+}
+
+impl<R: Read> CaptureReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, CaptureErr> {
+        let mut header = [0u8; 8];
+        if read_full(&mut reader, &mut header)? < header.len() || &header[..7] != CAPTURE_MAGIC {
+            return Err(CaptureErr::InvalidHeader);
+        }
+        if header[7] != CAPTURE_VERSION {
+            return Err(CaptureErr::UnsupportedVersion(header[7]));
+        }
+        Ok(CaptureReader { reader })
+    }
+
+    // None at the end of the capture
+    pub fn next_record(&mut self) -> Result<Option<Record>, CaptureErr> {
+        let mut header = [0u8; RECORD_HEADER_SIZE];
+        match read_full(&mut self.reader, &mut header)? {
+            0 => return Ok(None),
+            RECORD_HEADER_SIZE => {},
+            _ => return Err(CaptureErr::Truncated)
+        }
+        let direction = Direction::from_u8(header[0])?;
+        let mut timestamp = [0u8; 8];
+        timestamp.copy_from_slice(&header[1..9]);
+        let mut connection_id = [0u8; 4];
+        connection_id.copy_from_slice(&header[9..13]);
+        let mut length = [0u8; 4];
+        length.copy_from_slice(&header[13..17]);
+
+        // the buffer grows with what is actually there, not with the length field
+        let length = u32::from_be_bytes(length) as usize;
+        let mut data = Vec::new();
+        self.reader.by_ref().take(length as u64).read_to_end(&mut data)?;
+        if data.len() < length {
+            return Err(CaptureErr::Truncated);
+        }
+        Ok(Some(Record::new(direction, u64::from_be_bytes(timestamp), u32::from_be_bytes(connection_id), Bytes::from(data))))
+    }
+}
+
+impl<R: Read> Iterator for CaptureReader<R> {
+    type Item = Result<Record, CaptureErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+// like read_exact, but tells a clean end of file from a short read
+pub(crate) fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {},
+            Err(e) => return Err(e)
+        }
+    }
+    Ok(filled)
+}
+
+// how long the replayer waits between records
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Timing {
+    Immediate,
+    Original,
+    // Scaled(2.0) replays twice as fast as recorded
+    Scaled(f64)
+}
+
+pub struct ReplayedFrame {
+    direction: Direction,
+    timestamp: u64,
+    connection_id: u32,
+    frame: DecodedFrame
+}
+
+impl ReplayedFrame {
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn connection_id(&self) -> u32 {
+        self.connection_id
+    }
+
+    pub fn frame(&self) -> &DecodedFrame {
+        &self.frame
+    }
+
+    pub fn into_frame(self) -> DecodedFrame {
+        self.frame
+    }
+}
+
+// Reads a capture back, either decoded with one FrameCodec per connection
+// and direction or as the raw bytes together with the wait before each.
+pub struct Replayer<R: Read> {
+    reader: CaptureReader<R>,
+    timing: Timing,
+    codecs: HashMap<(u32, Direction), FrameCodec>,
+    last_timestamp: Option<u64>
+}
+
+impl<R: Read> Replayer<R> {
+    pub fn new(reader: CaptureReader<R>, timing: Timing) -> Self {
+        Replayer {
+            reader,
+            timing,
+            codecs: HashMap::new(),
+            last_timestamp: None
+        }
+    }
+
+    pub fn next_frame(&mut self) -> Result<Option<ReplayedFrame>, CaptureErr> {
+        let record = match self.reader.next_record()? {
+            Some(record) => record,
+            None => return Ok(None)
+        };
+        let codec = self.codecs.entry((record.connection_id, record.direction)).or_insert_with(|| {
+            let mut codec = FrameCodec::with_role(record.direction.decoder_role());
+            // whatever was recorded got past the limits back then
+            codec.set_frame_max(0);
+            codec
+        });
+        let mut src = BytesMut::from(&record.data[..]);
+        match codec.decode(&mut src)? {
+            Some(frame) if src.is_empty() => Ok(Some(ReplayedFrame {
+                direction: record.direction,
+                timestamp: record.timestamp,
+                connection_id: record.connection_id,
+                frame
+            })),
+            _ => Err(CaptureErr::InvalidRecord)
+        }
+    }
+
+    // the next record and how long to wait before sending it
+    pub fn next_bytes(&mut self) -> Result<Option<(Duration, Record)>, CaptureErr> {
+        let record = match self.reader.next_record()? {
+            Some(record) => record,
+            None => return Ok(None)
+        };
+        let elapsed = match self.last_timestamp {
+            Some(last) => record.timestamp.saturating_sub(last),
+            None => 0
+        };
+        self.last_timestamp = Some(record.timestamp);
+        let delay = match self.timing {
+            Timing::Immediate => Duration::from_secs(0),
+            Timing::Original => Duration::from_micros(elapsed),
+            Timing::Scaled(speed) if speed > 0.0 => {
+                // a tiny speed stretches the gap past what a Duration holds
+                let secs = elapsed as f64 / 1_000_000.0 / speed;
+                if secs < u64::MAX as f64 {
+                    Duration::from_secs_f64(secs)
+                } else {
+                    Duration::from_secs(u64::MAX)
+                }
+            }
+            Timing::Scaled(_) => Duration::from_secs(0)
+        };
+        Ok(Some((delay, record)))
+    }
+
+    // write one direction of the capture to `dst`, sleeping the recorded gaps
+    pub fn replay_to<W: Write>(&mut self, direction: Direction, dst: &mut W) -> Result<(), CaptureErr> {
+        while let Some((delay, record)) = self.next_bytes()? {
+            if delay > Duration::from_secs(0) {
+                std::thread::sleep(delay);
+            }
+            if record.direction == direction {
+                dst.write_all(&record.data)?;
+            }
+        }
+        dst.flush()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use bytes::{Bytes, BytesMut};
+    use tokio_util::codec::{Decoder, Encoder};
+    use crate::frame_codec::{FrameCodec, DecodedFrame};
+    use crate::encode::heartbeat_frame;
+    use crate::error::CaptureErr;
+    use crate::capture::{CaptureWriter, CaptureReader, FrameRecorder, Record, Replayer, Direction, Timing};
+
+    #[test]
+    fn test_record_and_replay() {
+        let mut recorder = FrameRecorder::new(FrameCodec::new(), CaptureWriter::new(Vec::new()).unwrap(), 7);
+        let mut src = BytesMut::from(&b"AMQP\x00\x00\x09\x01\x08\x00\x00\x00\x00\x00\x00\xce"[..]);
+        assert!(recorder.decode(&mut src).unwrap().is_some());
+        assert!(recorder.decode(&mut src).unwrap().is_some());
+        let mut dst = BytesMut::new();
+        recorder.encode(DecodedFrame::AmqpFrame(heartbeat_frame()), &mut dst).unwrap();
+        let capture = recorder.into_capture().into_inner();
+
+        let mut replayer = Replayer::new(CaptureReader::new(&capture[..]).unwrap(), Timing::Immediate);
+        let mut frames = Vec::new();
+        while let Some(replayed) = replayer.next_frame().unwrap() {
+            assert_eq!(replayed.connection_id(), 7);
+            let header = match replayed.frame() {
+                DecodedFrame::ProtocolHeader(_) => true,
+                DecodedFrame::AmqpFrame(_) => false
+            };
+            frames.push((replayed.direction(), header));
+        }
+        assert_eq!(frames, vec![(Direction::ClientToServer, true), (Direction::ClientToServer, false), (Direction::ServerToClient, false)]);
+
+        let mut client_bytes = Vec::new();
+        Replayer::new(CaptureReader::new(&capture[..]).unwrap(), Timing::Immediate).replay_to(Direction::ClientToServer, &mut client_bytes).unwrap();
+        assert_eq!(&client_bytes[..], &b"AMQP\x00\x00\x09\x01\x08\x00\x00\x00\x00\x00\x00\xce"[..]);
+
+        match CaptureReader::new(&capture[..capture.len() - 1]).unwrap().last() {
+            Some(Err(CaptureErr::Truncated)) => (),
+            _ => panic!("truncated record not reported")
+        }
+
+        // a record claiming 4 GiB with 3 bytes behind it
+        let mut capture = CaptureWriter::new(Vec::new()).unwrap().into_inner();
+        capture.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0xff, 0xff, 0xff, 0xff, 1, 2, 3]);
+        match CaptureReader::new(&capture[..]).unwrap().next_record() {
+            Err(CaptureErr::Truncated) => (),
+            _ => panic!("oversized record length not reported")
+        }
+    }
+
+    #[test]
+    fn test_replay_invalid_record() {
+        let heartbeat = b"\x08\x00\x00\x00\x00\x00\x00\xce";
+        let mut twice = heartbeat.to_vec();
+        twice.extend_from_slice(heartbeat);
+        for data in [&heartbeat[..7], &twice[..]].iter() {
+            let mut capture = CaptureWriter::new(Vec::new()).unwrap();
+            capture.write_record(&Record::new(Direction::ClientToServer, 0, 1, Bytes::from(b"AMQP\x00\x00\x09\x01".to_vec()))).unwrap();
+            capture.write_record(&Record::new(Direction::ClientToServer, 0, 1, Bytes::copy_from_slice(data))).unwrap();
+            let capture = capture.into_inner();
+            let mut replayer = Replayer::new(CaptureReader::new(&capture[..]).unwrap(), Timing::Immediate);
+            assert!(replayer.next_frame().unwrap().is_some());
+            match replayer.next_frame() {
+                Err(CaptureErr::InvalidRecord) => (),
+                _ => panic!("record without exactly one frame accepted")
+            }
+        }
+    }
+
+    #[test]
+    fn test_record_rejected_frame() {
+        let mut recorder = FrameRecorder::new(FrameCodec::new(), CaptureWriter::new(Vec::new()).unwrap(), 1);
+        // method frame of class 99, which does not exist
+        let frame = b"\x01\x00\x01\x00\x00\x00\x04\x00\x63\x00\x0a\xce";
+        let heartbeat = b"\x08\x00\x00\x00\x00\x00\x00\xce";
+        let mut src = BytesMut::from(&b"AMQP\x00\x00\x09\x01"[..]);
+        src.extend_from_slice(frame);
+        src.extend_from_slice(heartbeat);
+        assert!(recorder.decode(&mut src).unwrap().is_some());
+        assert!(recorder.decode(&mut src).is_err());
+
+        // a frame arriving a byte at a time is recorded once, when it is whole
+        let mut src = BytesMut::new();
+        for byte in heartbeat.iter() {
+            src.extend_from_slice(&[*byte]);
+            let decoded = recorder.decode(&mut src).unwrap();
+            assert_eq!(decoded.is_some(), src.is_empty());
+        }
+
+        let capture = recorder.into_capture().into_inner();
+        let records: Vec<_> = CaptureReader::new(&capture[..]).unwrap().map(|record| record.unwrap()).collect();
+        assert_eq!(records.len(), 3);
+        assert_eq!(&records[0].data()[..], &b"AMQP\x00\x00\x09\x01"[..]);
+        // only the rejected frame, not the heartbeat queued behind it
+        assert_eq!(&records[1].data()[..], &frame[..]);
+        assert_eq!(&records[2].data()[..], &heartbeat[..]);
+    }
+
+    #[test]
+    fn test_replay_timing() {
+        let mut capture = CaptureWriter::new(Vec::new()).unwrap();
+        let heartbeat = DecodedFrame::AmqpFrame(heartbeat_frame());
+        for timestamp in [1_000_000u64, 1_500_000, 3_500_000].iter() {
+            capture.record(1, Direction::ServerToClient, *timestamp, &heartbeat).unwrap();
+        }
+        let capture = capture.into_inner();
+
+        for (timing, expected) in [(Timing::Original, [0, 500, 2000]), (Timing::Scaled(2.0), [0, 250, 1000]), (Timing::Immediate, [0, 0, 0])].iter() {
+            let mut replayer = Replayer::new(CaptureReader::new(&capture[..]).unwrap(), *timing);
+            for millis in expected.iter() {
+                let (delay, _) = replayer.next_bytes().unwrap().unwrap();
+                assert_eq!(delay, Duration::from_millis(*millis));
+            }
+            assert!(replayer.next_bytes().unwrap().is_none());
+        }
+
+        let mut replayer = Replayer::new(CaptureReader::new(&capture[..]).unwrap(), Timing::Scaled(1e-300));
+        replayer.next_bytes().unwrap();
+        assert_eq!(replayer.next_bytes().unwrap().unwrap().0, Duration::from_secs(u64::MAX));
+    }
+}
@@ -1,305 +1,478 @@
 use amqp_types::{FieldValue, FieldTable, FieldArray, ShortStr, Decimal, FieldName, LongStr};
-use crate::error::FrameDecodeErr;
+use crate::error::{FrameDecodeErr, DecodeCause};
 use amqp_types::basic_types::FieldValueKind;
 use nom::error::ErrorKind;
 use nom::number::complete::{be_i64, be_u32, be_i32, be_u16, be_i16, be_u8, be_i8, be_u64, be_f32, be_f64};
-use amqp_types::frame::{Method, Class, BasicMethod, TxMethod, QueueMethod, ExchangeMethod, AccessMethod, ChannelMethod, ConnectionMethod};
 use nom::bytes::complete::take;
+use bytes::{BytesMut, BufMut};
+use crate::error::{FrameEncodeErr, ArgumentErr};
 
-pub(crate) fn get_method_type(class: Class, method_id: u16) -> Result<Method, FrameDecodeErr> {
-    match class {
-        Class::Connection => {
-            let method = ConnectionMethod::from(method_id);
-            if let ConnectionMethod::Unknown = method {
-                return Err(FrameDecodeErr::UnknownMethodType);
-            } else {
-                return Ok(Method::ConnectionMethod(method));
-            }
-        }
-        Class::Channel => {
-            let method = ChannelMethod::from(method_id);
-            if let ChannelMethod::Unknown = method {
-                return Err(FrameDecodeErr::UnknownMethodType);
-            } else {
-                return Ok(Method::ChannelMethod(method));
-            }
-        }
-        Class::Access => {
-            let method = AccessMethod::from(method_id);
-            if let AccessMethod::Unknown = method {
-                return Err(FrameDecodeErr::UnknownMethodType);
-            } else {
-                return Ok(Method::AccessMethod(method));
-            }
-        }
-        Class::Exchange => {
-            let method = ExchangeMethod::from(method_id);
-            if let ExchangeMethod::Unknown = method {
-                return Err(FrameDecodeErr::UnknownMethodType);
-            } else {
-                return Ok(Method::ExchangeMethod(method));
-            }
-        }
-        Class::Queue => {
-            let method = QueueMethod::from(method_id);
-            if let QueueMethod::Unknown = method {
-                return Err(FrameDecodeErr::UnknownMethodType);
-            } else {
-                return Ok(Method::QueueMethod(method));
-            }
-        }
-        Class::Basic => {
-            let method = BasicMethod::from(method_id);
-            if let BasicMethod::Unknown = method {
-                return Err(FrameDecodeErr::UnknownMethodType);
-            } else {
-                return Ok(Method::BasicMethod(method));
-            }
-        }
-        Class::Tx => {
-            let method = TxMethod::from(method_id);
-            if let TxMethod::Unknown = method {
-                return Err(FrameDecodeErr::UnknownMethodType);
-            } else {
-                return Ok(Method::TxMethod(method));
-            }
-        }
-        Class::Unknown => return Err(FrameDecodeErr::UnknownClassType)
-    }
-}
+// class and method ids of every class in the spec, see build.rs
+include!(concat!(env!("OUT_DIR"), "/method_ids.rs"));
+
+// dotted spec name of a method, e.g. "basic.publish"
+include!(concat!(env!("OUT_DIR"), "/method_names.rs"));
 
 pub(crate) fn parse_short_string(buffer: &[u8]) -> Result<(&[u8], ShortStr), FrameDecodeErr> {
-    let (buffer, length) = match be_u8::<(_, ErrorKind)>(buffer) {
+    let (remain, length) = match be_u8::<(_, ErrorKind)>(buffer) {
         Ok(ret) => ret,
-        Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::syntax(buffer, e))
     };
-    let (buffer, data) = match take::<_,_,(_, ErrorKind)>(length)(buffer) {
+    let (remain, data) = match take::<_,_,(_, ErrorKind)>(length)(remain) {
         Ok(data) => data,
-        Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::syntax(buffer, e))
     };
-    Ok((buffer, ShortStr::with_bytes(data).unwrap()))
+    match ShortStr::with_bytes(data) {
+        Ok(value) => Ok((remain, value)),
+        Err(e) => Err(FrameDecodeErr::syntax(buffer, e))
+    }
 }
 
 pub(crate) fn parse_field_array(buffer: &[u8]) -> Result<(&[u8], FieldArray), FrameDecodeErr> {
     // array bytes length
-    let (buffer, length) = match be_u32::<(_, ErrorKind)>(buffer) {
+    let (remain, length) = match be_u32::<(_, ErrorKind)>(buffer) {
         Ok(ret) => ret,
-        Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::syntax(buffer, e))
     };
-    let (buffer, data) = match take::<_,_,(_, ErrorKind)>(length)(buffer) {
+    let (remain, data) = match take::<_,_,(_, ErrorKind)>(length)(remain) {
         Ok(ret) => ret,
-        Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::syntax(buffer, e))
     };
     let mut arr: Vec<FieldValue> = Vec::new();
 
+    // errors inside the array are positioned from the end of the array
+    let mut data = data;
     while data.len() != 0 {
-        let (data, value) = match parse_field_value(data) {
+        let (next, value) = match parse_field_value(data) {
             Ok(ret) => ret,
-            Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
+            Err(e) => return Err(e.behind(remain))
         };
         arr.push(value);
+        data = next;
     }
 
-    Ok((buffer, arr))
+    Ok((remain, arr))
 }
 
 pub(crate) fn parse_field_table(buffer: &[u8]) -> Result<(&[u8], FieldTable), FrameDecodeErr> {
-    let (buffer, length) = match be_u32::<(_, ErrorKind)>(buffer) {
+    let (remain, length) = match be_u32::<(_, ErrorKind)>(buffer) {
         Ok(ret) => ret,
-        Err(err) => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::syntax(buffer, e))
     };
-    let (buffer, data) = match take::<_,_,(_, ErrorKind)>(length)(buffer) {
+    let (remain, data) = match take::<_,_,(_, ErrorKind)>(length)(remain) {
         Ok(ret) => ret,
-        Err(err) => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::syntax(buffer, e))
     };
 
     let mut table = FieldTable::new();
 
+    let mut data = data;
     while data.len() != 0 {
-        let (data, name) = match parse_field_name(data) {
+        let (next, name) = match parse_field_name(data) {
             Ok(ret) => ret,
-            Err(err) => return Err(FrameDecodeErr::ParseFrameFailed)
+            Err(e) => return Err(e.behind(remain))
         };
-        let (data, value) = match parse_field_value(data) {
+        let (next, value) = match parse_field_value(next) {
             Ok(ret) => ret,
-            Err(err) => return Err(FrameDecodeErr::ParseFrameFailed)
+            Err(e) => return Err(e.behind(remain))
         };
         table.insert(name, value);
+        data = next;
     }
 
-    Ok((buffer, table))
+    Ok((remain, table))
 }
 
 pub(crate) fn parse_field_value(buffer: &[u8]) -> Result<(&[u8], FieldValue), FrameDecodeErr> {
-    let (buffer, field_value_type) = match be_u8::<(_, ErrorKind)>(buffer) {
+    let (remain, field_value_type) = match be_u8::<(_, ErrorKind)>(buffer) {
         Ok(ret) => ret,
-        Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::syntax(buffer, e))
     };
-    let field_value_type = FieldValueKind::from(field_value_type);
-    if let FieldValueKind::Unknown = field_value_type {
-        return Err(FrameDecodeErr::ParseFrameFailed);
+    let kind = FieldValueKind::from(field_value_type);
+    if let FieldValueKind::Unknown = kind {
+        return Err(FrameDecodeErr::syntax(buffer, DecodeCause::UnknownFieldValueType(field_value_type)));
     }
-    match field_value_type {
+    let buffer = remain;
+    match kind {
         FieldValueKind::I8 => {
-            if let Ok((buffer, value)) = be_i8::<(_, ErrorKind)>(buffer) {
-                return Ok((buffer, FieldValue::from_i8(value as i8)));
-            } else {
-                return Err(FrameDecodeErr::ParseFrameFailed);
+            match be_i8::<(_, ErrorKind)>(buffer) {
+                Ok((buffer, value)) => Ok((buffer, FieldValue::from_i8(value))),
+                Err(e) => Err(FrameDecodeErr::syntax(buffer, e))
             }
         }
         FieldValueKind::U8 => {
-            if let Ok((buffer, value)) = be_u8::<(_, ErrorKind)>(buffer) {
-                return Ok((buffer, FieldValue::from_u8(value as u8)));
-            } else {
-                return Err(FrameDecodeErr::ParseFrameFailed);
+            match be_u8::<(_, ErrorKind)>(buffer) {
+                Ok((buffer, value)) => Ok((buffer, FieldValue::from_u8(value))),
+                Err(e) => Err(FrameDecodeErr::syntax(buffer, e))
             }
         }
         FieldValueKind::Boolean => {
-            if let Ok((buffer, value)) = be_u8::<(_, ErrorKind)>(buffer) {
-                return Ok((buffer, FieldValue::from_bool(value != 0)))
-            } else {
-                return Err(FrameDecodeErr::ParseFrameFailed);
+            match be_u8::<(_, ErrorKind)>(buffer) {
+                Ok((buffer, value)) => Ok((buffer, FieldValue::from_bool(value != 0))),
+                Err(e) => Err(FrameDecodeErr::syntax(buffer, e))
             }
         }
         FieldValueKind::I16 => {
-            if let Ok((buffer, value)) = be_i16::<(_, ErrorKind)>(buffer) {
-                return Ok((buffer, FieldValue::from_i16(value)));
-            } else {
-                return Err(FrameDecodeErr::ParseFrameFailed);
+            match be_i16::<(_, ErrorKind)>(buffer) {
+                Ok((buffer, value)) => Ok((buffer, FieldValue::from_i16(value))),
+                Err(e) => Err(FrameDecodeErr::syntax(buffer, e))
             }
         }
         FieldValueKind::U16 => {
-            if let Ok((buffer, value)) = be_u16::<(_, ErrorKind)>(buffer) {
-                return Ok((buffer, FieldValue::from_u16(value)));
-            } else {
-                return Err(FrameDecodeErr::ParseFrameFailed);
+            match be_u16::<(_, ErrorKind)>(buffer) {
+                Ok((buffer, value)) => Ok((buffer, FieldValue::from_u16(value))),
+                Err(e) => Err(FrameDecodeErr::syntax(buffer, e))
             }
         }
         FieldValueKind::I32 => {
-            if let Ok((buffer, value)) = be_i32::<(_, ErrorKind)>(buffer) {
-                return Ok((buffer, FieldValue::from_i32(value)));
-            } else {
-                return Err(FrameDecodeErr::ParseFrameFailed);
+            match be_i32::<(_, ErrorKind)>(buffer) {
+                Ok((buffer, value)) => Ok((buffer, FieldValue::from_i32(value))),
+                Err(e) => Err(FrameDecodeErr::syntax(buffer, e))
             }
         }
         FieldValueKind::U32 => {
-            if let Ok((buffer, value)) = be_u32::<(_, ErrorKind)>(buffer) {
-                return Ok((buffer, FieldValue::from_u32(value)));
-            } else {
-                return Err(FrameDecodeErr::ParseFrameFailed);
+            match be_u32::<(_, ErrorKind)>(buffer) {
+                Ok((buffer, value)) => Ok((buffer, FieldValue::from_u32(value))),
+                Err(e) => Err(FrameDecodeErr::syntax(buffer, e))
             }
         }
         FieldValueKind::I64 => {
-            if let Ok((buffer, value)) = be_i64::<(_, ErrorKind)>(buffer) {
-                return Ok((buffer, FieldValue::from_i64(value)));
-            } else {
-                return Err(FrameDecodeErr::ParseFrameFailed);
+            match be_i64::<(_, ErrorKind)>(buffer) {
+                Ok((buffer, value)) => Ok((buffer, FieldValue::from_i64(value))),
+                Err(e) => Err(FrameDecodeErr::syntax(buffer, e))
             }
         }
         FieldValueKind::U64 => {
-            if let Ok((buffer, value)) = be_u64::<(_, ErrorKind)>(buffer) {
-                return Ok((buffer, FieldValue::from_u64(value)));
-            } else {
-                return Err(FrameDecodeErr::ParseFrameFailed);
+            match be_u64::<(_, ErrorKind)>(buffer) {
+                Ok((buffer, value)) => Ok((buffer, FieldValue::from_u64(value))),
+                Err(e) => Err(FrameDecodeErr::syntax(buffer, e))
             }
         }
         FieldValueKind::F32 => {
-            if let Ok((buffer, value)) = be_f32::<(_, ErrorKind)>(buffer) {
-                return Ok((buffer, FieldValue::from_f32(value)));
-            } else {
-                return Err(FrameDecodeErr::ParseFrameFailed);
+            match be_f32::<(_, ErrorKind)>(buffer) {
+                Ok((buffer, value)) => Ok((buffer, FieldValue::from_f32(value))),
+                Err(e) => Err(FrameDecodeErr::syntax(buffer, e))
             }
         }
         FieldValueKind::F64 => {
-            if let Ok((buffer, value)) = be_f64::<(_, ErrorKind)>(buffer) {
-                return Ok((buffer, FieldValue::from_f64(value)));
-            } else {
-                return Err(FrameDecodeErr::ParseFrameFailed);
+            match be_f64::<(_, ErrorKind)>(buffer) {
+                Ok((buffer, value)) => Ok((buffer, FieldValue::from_f64(value))),
+                Err(e) => Err(FrameDecodeErr::syntax(buffer, e))
             }
         }
         FieldValueKind::Timestamp => {
-            if let Ok((buffer, value)) = be_u64::<(_, ErrorKind)>(buffer) {
-                return Ok((buffer, FieldValue::from_timestamp(value)));
-            } else {
-                return Err(FrameDecodeErr::ParseFrameFailed);
+            match be_u64::<(_, ErrorKind)>(buffer) {
+                Ok((buffer, value)) => Ok((buffer, FieldValue::from_timestamp(value))),
+                Err(e) => Err(FrameDecodeErr::syntax(buffer, e))
             }
         }
         FieldValueKind::Decimal => {
-            let scale = be_u8::<(_, ErrorKind)>(buffer);
-            let (buffer, scale) = if scale.is_ok() { scale.unwrap() } else { return Err(FrameDecodeErr::ParseFrameFailed); };
-            if let Ok((buffer, value)) = be_u32::<(_, ErrorKind)>(buffer) {
-                return Ok((buffer, FieldValue::from_decimal(Decimal::new(scale, value))));
-            } else {
-                return Err(FrameDecodeErr::ParseFrameFailed);
+            let (remain, scale) = match be_u8::<(_, ErrorKind)>(buffer) {
+                Ok(ret) => ret,
+                Err(e) => return Err(FrameDecodeErr::syntax(buffer, e))
+            };
+            match be_u32::<(_, ErrorKind)>(remain) {
+                Ok((remain, value)) => Ok((remain, FieldValue::from_decimal(Decimal::new(scale, value)))),
+                Err(e) => Err(FrameDecodeErr::syntax(buffer, e))
             }
         }
         FieldValueKind::LongStr => {
-            match parse_long_string(buffer) {
-                Ok((buffer, value)) => return Ok((buffer, FieldValue::from_long_string(value))),
-                Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
-            }
+            let (buffer, value) = parse_long_string(buffer)?;
+            Ok((buffer, FieldValue::from_long_string(value)))
         }
         FieldValueKind::ByteArray => {
-            match parse_long_string(buffer) {
-                Ok((buffer, value)) => return Ok((buffer, FieldValue::from_bytes_array(value))),
-                Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
-            }
+            let (buffer, value) = parse_long_string(buffer)?;
+            Ok((buffer, FieldValue::from_bytes_array(value)))
         }
-        FieldValueKind::Void => return Ok((buffer, FieldValue::from_void())),
+        FieldValueKind::Void => Ok((buffer, FieldValue::from_void())),
         FieldValueKind::FieldArray => {
-            match parse_field_array(buffer) {
-                Ok((buffer, value)) => return Ok((buffer, FieldValue::from_field_array(value))),
-                Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
-            }
+            let (buffer, value) = parse_field_array(buffer)?;
+            Ok((buffer, FieldValue::from_field_array(value)))
         }
         FieldValueKind::FieldTable => {
-            match parse_field_table(buffer) {
-                Ok((buffer, value)) => return Ok((buffer, FieldValue::from_field_table(value))),
-                Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
-            }
+            let (buffer, value) = parse_field_table(buffer)?;
+            Ok((buffer, FieldValue::from_field_table(value)))
         }
-        _ => return Err(FrameDecodeErr::ParseFrameFailed)
+        _ => Err(FrameDecodeErr::syntax(buffer, DecodeCause::UnknownFieldValueType(field_value_type)))
     }
 
 }
 
 pub(crate) fn parse_field_name(buffer: &[u8]) -> Result<(&[u8], FieldName), FrameDecodeErr> {
-    let (buffer, length) = match be_u8::<(_, ErrorKind)>(buffer) {
+    let (remain, length) = match be_u8::<(_, ErrorKind)>(buffer) {
         Ok(ret) => ret,
-        Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::syntax(buffer, e))
     };
-    let (buffer, data) = match take::<_,_,(_, ErrorKind)>(length)(buffer) {
+    let (remain, data) = match take::<_,_,(_, ErrorKind)>(length)(remain) {
         Ok(data) => data,
-        Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::syntax(buffer, e))
     };
     let name = match FieldName::with_bytes(data) {
         Ok(name) => name,
-        Err(e) => return Err(FrameDecodeErr::Amqp(e))
+        Err(e) => return Err(FrameDecodeErr::syntax(buffer, e))
     };
-    Ok((buffer, name))
+    Ok((remain, name))
 }
 
 pub(crate) fn parse_long_string(buffer: &[u8]) -> Result<(&[u8], LongStr), FrameDecodeErr> {
-    let (buffer, length) = match be_u32::<(_, ErrorKind)>(buffer) {
+    let (remain, length) = match be_u32::<(_, ErrorKind)>(buffer) {
         Ok(ret) => ret,
-        Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::syntax(buffer, e))
     };
-    let (buffer, data) = match take::<_,_,(_, ErrorKind)>(length)(buffer) {
+    let (remain, data) = match take::<_,_,(_, ErrorKind)>(length)(remain) {
         Ok(ret) => ret,
-        Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::syntax(buffer, e))
     };
-    Ok((buffer, LongStr::with_bytes(data).unwrap()))
+    match LongStr::with_bytes(data) {
+        Ok(value) => Ok((remain, value)),
+        Err(e) => Err(FrameDecodeErr::syntax(buffer, e))
+    }
+}
+
+// Consecutive bit arguments share an octet, the first one in the lowest bit.
+// A run of more than 8 bits continues in the next octet.
+pub(crate) fn unpack_bits(octet: u8) -> [bool; 8] {
+    let mut bits = [false; 8];
+    for (index, bit) in bits.iter_mut().enumerate() {
+        *bit = octet & (1 << index) != 0;
+    }
+    bits
 }
 
+pub(crate) fn pack_bits(bits: &[bool]) -> u8 {
+    debug_assert!(bits.len() <= 8);
+    bits.iter().enumerate().fold(0, |octet, (index, bit)| if *bit { octet | 1 << index } else { octet })
+}
+
+// Each 16 bit flag word carries 15 property bits, most significant first, the
+// lowest bit tells whether another flag word follows.
+pub(crate) struct PropertyFlags {
+    words: Vec<u16>
+}
+
+impl PropertyFlags {
+    pub(crate) fn is_set(&self, index: usize) -> bool {
+        match self.words.get(index / 15) {
+            Some(word) => word & (1 << (15 - index % 15)) != 0,
+            None => false
+        }
+    }
+
+    // number of property bits carried by the flag words
+    pub(crate) fn capacity(&self) -> usize {
+        self.words.len() * 15
+    }
+}
+
+pub(crate) fn parse_property_flags(buffer: &[u8]) -> Result<(&[u8], PropertyFlags), FrameDecodeErr> {
+    let mut words = Vec::new();
+    let mut buffer = buffer;
+    loop {
+        let (remain, word) = match be_u16::<(_, ErrorKind)>(buffer) {
+            Ok(ret) => ret,
+            Err(e) => return Err(FrameDecodeErr::field("flags", buffer, e))
+        };
+        buffer = remain;
+        words.push(word);
+        if word & 1 == 0 {
+            break;
+        }
+    }
+    Ok((buffer, PropertyFlags { words }))
+}
 
 pub(crate) fn parse_channel_id_and_length(buffer: &[u8]) -> Result<(&[u8], u16, u32), FrameDecodeErr> {
+    // channel id and length are read before the frame is known to be complete
+    if buffer.len() < 6 {
+        return Err(FrameDecodeErr::Incomplete);
+    }
     let (buffer, channel_id) = match be_u16::<(_, ErrorKind)>(buffer) {
         Ok(ret) => ret,
-        Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::field("channel", buffer, e))
     };
     let (buffer, length) = match be_u32::<(_, ErrorKind)>(buffer) {
         Ok(ret) => ret,
-        Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::field("length", buffer, e))
     };
     Ok((buffer, channel_id, length))
-}
\ No newline at end of file
+}
+
+pub(crate) fn write_short_string(value: &ShortStr, dst: &mut BytesMut) {
+    let data = value.as_bytes();
+    dst.put_u8(data.len() as u8);
+    dst.put_slice(data);
+}
+
+pub(crate) fn write_long_string(value: &LongStr, dst: &mut BytesMut) {
+    let data = value.as_bytes();
+    dst.put_u32(data.len() as u32);
+    dst.put_slice(data);
+}
+
+pub(crate) fn write_field_name(name: &FieldName, dst: &mut BytesMut) {
+    let data = name.as_bytes();
+    dst.put_u8(data.len() as u8);
+    dst.put_slice(data);
+}
+
+pub(crate) fn write_field_array(arr: &FieldArray, dst: &mut BytesMut) -> Result<(), FrameEncodeErr> {
+    let mut data = BytesMut::new();
+    for value in arr.iter() {
+        write_field_value(value, &mut data)?;
+    }
+    dst.put_u32(data.len() as u32);
+    dst.put_slice(&data);
+    Ok(())
+}
+
+pub(crate) fn write_field_table(table: &FieldTable, dst: &mut BytesMut) -> Result<(), FrameEncodeErr> {
+    let mut data = BytesMut::new();
+    for (name, value) in table.iter() {
+        write_field_name(name, &mut data);
+        write_field_value(value, &mut data)?;
+    }
+    dst.put_u32(data.len() as u32);
+    dst.put_slice(&data);
+    Ok(())
+}
+
+// field value type tags, RabbitMQ flavour of the 0-9-1 errata table
+pub(crate) fn write_field_value(value: &FieldValue, dst: &mut BytesMut) -> Result<(), FrameEncodeErr> {
+    match value.kind() {
+        FieldValueKind::Boolean => {
+            dst.put_u8(b't');
+            dst.put_u8(if value.as_bool() { 1 } else { 0 });
+        }
+        FieldValueKind::I8 => {
+            dst.put_u8(b'b');
+            dst.put_i8(value.as_i8());
+        }
+        FieldValueKind::U8 => {
+            dst.put_u8(b'B');
+            dst.put_u8(value.as_u8());
+        }
+        FieldValueKind::I16 => {
+            dst.put_u8(b's');
+            dst.put_i16(value.as_i16());
+        }
+        FieldValueKind::U16 => {
+            dst.put_u8(b'u');
+            dst.put_u16(value.as_u16());
+        }
+        FieldValueKind::I32 => {
+            dst.put_u8(b'I');
+            dst.put_i32(value.as_i32());
+        }
+        FieldValueKind::U32 => {
+            dst.put_u8(b'i');
+            dst.put_u32(value.as_u32());
+        }
+        FieldValueKind::I64 => {
+            dst.put_u8(b'l');
+            dst.put_i64(value.as_i64());
+        }
+        FieldValueKind::U64 => {
+            dst.put_u8(b'L');
+            dst.put_u64(value.as_u64());
+        }
+        FieldValueKind::F32 => {
+            dst.put_u8(b'f');
+            dst.put_f32(value.as_f32());
+        }
+        FieldValueKind::F64 => {
+            dst.put_u8(b'd');
+            dst.put_f64(value.as_f64());
+        }
+        FieldValueKind::Timestamp => {
+            dst.put_u8(b'T');
+            dst.put_u64(value.as_timestamp());
+        }
+        FieldValueKind::Decimal => {
+            let decimal = value.as_decimal();
+            dst.put_u8(b'D');
+            dst.put_u8(decimal.scale());
+            dst.put_u32(decimal.value());
+        }
+        FieldValueKind::LongStr => {
+            dst.put_u8(b'S');
+            write_long_string(value.as_long_string(), dst);
+        }
+        FieldValueKind::ByteArray => {
+            dst.put_u8(b'x');
+            write_long_string(value.as_bytes_array(), dst);
+        }
+        FieldValueKind::Void => dst.put_u8(b'V'),
+        FieldValueKind::FieldArray => {
+            dst.put_u8(b'A');
+            write_field_array(value.as_field_array(), dst)?;
+        }
+        FieldValueKind::FieldTable => {
+            dst.put_u8(b'F');
+            write_field_table(value.as_field_table(), dst)?;
+        }
+        _ => return Err(FrameEncodeErr::UnknownFieldValueType)
+    }
+    Ok(())
+}
+
+// Helpers for the typed views over field tables, a well known key holding
+// the wrong type is reported under that key.
+pub(crate) fn read_table_string(key: &str, value: &FieldValue) -> Result<String, ArgumentErr> {
+    match value.kind() {
+        FieldValueKind::LongStr => {
+            let bytes = value.as_long_string().as_bytes();
+            String::from_utf8(bytes.to_vec()).map_err(|_| ArgumentErr::InvalidValue(key.to_string(), String::from_utf8_lossy(bytes).into_owned()))
+        }
+        _ => Err(ArgumentErr::InvalidType(key.to_string(), "a string"))
+    }
+}
+
+pub(crate) fn read_table_bool(key: &str, value: &FieldValue) -> Result<bool, ArgumentErr> {
+    match value.kind() {
+        FieldValueKind::Boolean => Ok(value.as_bool()),
+        _ => Err(ArgumentErr::InvalidType(key.to_string(), "a boolean"))
+    }
+}
+
+pub(crate) fn table_string(value: &str) -> Result<FieldValue, ArgumentErr> {
+    Ok(FieldValue::from_long_string(LongStr::with_bytes(value.as_bytes())?))
+}
+
+pub(crate) fn insert_table_entry(table: &mut FieldTable, key: &str, value: FieldValue) -> Result<(), ArgumentErr> {
+    table.insert(FieldName::with_bytes(key.as_bytes())?, value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{parse_property_flags, pack_bits, unpack_bits};
+
+    #[test]
+    fn test_bits_all_combinations() {
+        for count in 1..=8 {
+            for octet in 0..(1u16 << count) {
+                let octet = octet as u8;
+                let bits: Vec<bool> = (0..count).map(|index| octet & (1 << index) != 0).collect();
+                assert_eq!(pack_bits(&bits), octet);
+                assert_eq!(&unpack_bits(octet)[..count], &bits[..]);
+                assert!(unpack_bits(octet)[count..].iter().all(|bit| !bit));
+            }
+        }
+    }
+
+    #[test]
+    fn test_property_flags_continuation() {
+        // content-type and cluster-id in the first word, continuation bit set,
+        // then the first bit of the second word
+        let buffer = [0x80u8, 0x05, 0x80, 0x00, 0xff];
+        let (remain, flags) = parse_property_flags(&buffer).unwrap();
+        assert_eq!(remain, &[0xffu8][..]);
+        assert_eq!(flags.capacity(), 30);
+        assert!(flags.is_set(0));
+        assert!(!flags.is_set(1));
+        assert!(flags.is_set(13));
+        assert!(!flags.is_set(14));
+        assert!(flags.is_set(15));
+        assert!(!flags.is_set(16));
+    }
+}
@@ -0,0 +1,109 @@
+use std::result::Result;
+use amqp_types::frame::{ProtocolHeader, Arguments, Class, Method, ConnectionMethod, MethodPayload, ContentHeaderPayload, Payload, Property};
+use amqp_types::{Frame, FrameType};
+use bytes::{BytesMut, BufMut};
+use crate::error::FrameEncodeErr;
+use crate::common::{get_class_id, get_method_id};
+use crate::basic::write_basic_properties;
+use crate::parse::{PROTOCOL_HEADER_SIZE, FRAME_PREFIX_LENGTH, FRAME_END, FRAME_END_LENGTH};
+
+pub fn protocol_header(major_id: u8, minor_id: u8, major_version: u8, minor_version: u8) -> ProtocolHeader {
+    let mut header = ProtocolHeader::default();
+    header.set_protocol(Vec::from(&b"AMQP"[..]));
+    header.set_major_id(major_id);
+    header.set_minor_id(minor_id);
+    header.set_major_version(major_version);
+    header.set_minor_version(minor_version);
+    header
+}
+
+pub fn write_amqp_protocol_header(header: &ProtocolHeader, dst: &mut BytesMut) {
+    dst.reserve(PROTOCOL_HEADER_SIZE);
+    dst.put_slice(b"AMQP");
+    dst.put_u8(header.major_id());
+    dst.put_u8(header.minor_id());
+    dst.put_u8(header.major_version());
+    dst.put_u8(header.minor_version());
+}
+
+// one write_<class>_<method> per method, see build.rs
+include!(concat!(env!("OUT_DIR"), "/write_arguments.rs"));
+
+pub(crate) fn write_method_payload(payload: &MethodPayload, dst: &mut BytesMut) -> Result<(), FrameEncodeErr> {
+    dst.put_u16(get_class_id(payload.class())?);
+    dst.put_u16(get_method_id(payload.method())?);
+    write_arguments(payload.args(), dst)
+}
+
+pub(crate) fn write_content_header_payload(payload: &ContentHeaderPayload, dst: &mut BytesMut) -> Result<(), FrameEncodeErr> {
+    dst.put_u16(get_class_id(payload.class())?);
+    dst.put_u16(payload.weight());
+    dst.put_u64(payload.body_size());
+    match payload.properties() {
+        Property::Basic(properties) => write_basic_properties(properties, dst),
+        // only the basic class defines content properties
+        _ => {
+            dst.put_u16(0);
+            Ok(())
+        }
+    }
+}
+
+// +-frame type: u8-+---channel id: u16---+-----length: u32-----+----payload---+--frame end--+
+// |   1|2|3|4      |       0x0000        |     payload length  |              |  0xce       |
+// +----------------+---------------------+---------------------+--------------+-------------+
+pub fn write_frame(frame: &Frame, dst: &mut BytesMut) -> Result<(), FrameEncodeErr> {
+    let mut payload = BytesMut::new();
+    let frame_type = match (frame.frame_type(), frame.payload()) {
+        (FrameType::METHOD, Payload::Method(method)) => {
+            write_method_payload(method, &mut payload)?;
+            1u8
+        }
+        (FrameType::HEADER, Payload::ContentHeader(header)) => {
+            write_content_header_payload(header, &mut payload)?;
+            2u8
+        }
+        (FrameType::BODY, Payload::ContentBody(body)) => {
+            payload.put_slice(body.payload());
+            3u8
+        }
+        (FrameType::HEARTBEAT, Payload::Heartbeat) => 8u8,
+        _ => return Err(FrameEncodeErr::UnsupportedPayload)
+    };
+
+    dst.reserve((FRAME_PREFIX_LENGTH + FRAME_END_LENGTH) as usize + payload.len());
+    dst.put_u8(frame_type);
+    dst.put_u16(frame.channel());
+    dst.put_u32(payload.len() as u32);
+    dst.put_slice(&payload);
+    dst.put_u8(FRAME_END);
+    Ok(())
+}
+
+// method frame ready for write_frame, which works out the length itself
+pub fn method_frame(channel: u16, class: Class, method: Method, args: Arguments) -> Frame {
+    let method_payload = MethodPayload::default();
+    method_payload.set_class(class);
+    method_payload.set_method(method);
+    method_payload.set_args(args);
+
+    let frame = Frame::default();
+    frame.set_frame_type(FrameType::METHOD);
+    frame.set_channel(channel);
+    frame.set_payload(Payload::Method(method_payload));
+    frame
+}
+
+// class and method of a Connection.Close or Channel.Close that no method
+// caused, they go out as 0/0
+pub fn no_method() -> (Class, Method) {
+    (Class::Unknown, Method::ConnectionMethod(ConnectionMethod::Unknown))
+}
+
+pub fn heartbeat_frame() -> Frame {
+    let frame = Frame::default();
+    frame.set_frame_type(FrameType::HEARTBEAT);
+    frame.set_channel(0);
+    frame.set_payload(Payload::Heartbeat);
+    frame
+}
@@ -1,15 +1,138 @@
 use std::{io, fmt};
 use std::fmt::{Display, Formatter};
 use std::io::Error;
+use nom::error::ErrorKind;
+use crate::common::method_name;
+use crate::parse::{FRAME_PREFIX_LENGTH, ProtocolVersion};
+use crate::handshake::Tuning;
+
+// what went wrong inside a field
+#[derive(Debug)]
+pub enum DecodeCause {
+    // the payload ended in the middle of the field
+    UnexpectedEnd,
+    UnknownFieldValueType(u8),
+    UnknownProperty(usize),
+    Nom(ErrorKind),
+    Amqp(amqp_types::error::Error)
+}
+
+impl Display for DecodeCause {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeCause::UnexpectedEnd => write!(f, "unexpected end of payload"),
+            DecodeCause::UnknownFieldValueType(kind) => write!(f, "unknown field value type 0x{:02x}", kind),
+            DecodeCause::UnknownProperty(index) => write!(f, "unknown property flag {}", index),
+            DecodeCause::Nom(kind) => write!(f, "{}", kind.description()),
+            DecodeCause::Amqp(err) => write!(f, "{}", err)
+        }
+    }
+}
+
+impl<'a> From<nom::Err<(&'a [u8], ErrorKind)>> for DecodeCause {
+    fn from(err: nom::Err<(&'a [u8], ErrorKind)>) -> Self {
+        match err {
+            nom::Err::Incomplete(_) => DecodeCause::UnexpectedEnd,
+            nom::Err::Error((_, ErrorKind::Eof)) | nom::Err::Failure((_, ErrorKind::Eof)) => DecodeCause::UnexpectedEnd,
+            nom::Err::Error((_, kind)) | nom::Err::Failure((_, kind)) => DecodeCause::Nom(kind)
+        }
+    }
+}
+
+impl From<amqp_types::error::Error> for DecodeCause {
+    fn from(err: amqp_types::error::Error) -> Self {
+        DecodeCause::Amqp(err)
+    }
+}
+
+// A malformed frame, with as much of its position as was known where it
+// failed. Context is filled in while the error travels up the parsers.
+#[derive(Debug)]
+pub struct SyntaxErr {
+    channel: Option<u16>,
+    class_id: Option<u16>,
+    method_id: Option<u16>,
+    field: Option<&'static str>,
+    // bytes left in the buffer being parsed when it failed
+    remaining: usize,
+    offset: Option<usize>,
+    cause: DecodeCause
+}
+
+impl SyntaxErr {
+    pub fn channel(&self) -> Option<u16> {
+        self.channel
+    }
+
+    pub fn class_id(&self) -> Option<u16> {
+        self.class_id
+    }
+
+    pub fn method_id(&self) -> Option<u16> {
+        self.method_id
+    }
+
+    pub fn field(&self) -> Option<&'static str> {
+        self.field
+    }
+
+    // offset from the first byte of the frame
+    pub fn offset(&self) -> Option<usize> {
+        self.offset
+    }
+
+    pub fn cause(&self) -> &DecodeCause {
+        &self.cause
+    }
+}
+
+impl Display for SyntaxErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "syntax error")?;
+        if let Some(channel) = self.channel {
+            write!(f, " on channel {}", channel)?;
+        }
+        match (self.class_id, self.method_id) {
+            (Some(class_id), Some(method_id)) => {
+                match method_name(class_id, method_id) {
+                    Some(name) => write!(f, " in {} ({}.{})", name, class_id, method_id)?,
+                    None => write!(f, " in method {}.{}", class_id, method_id)?
+                }
+            }
+            (Some(class_id), None) => write!(f, " in content header of class {}", class_id)?,
+            _ => {}
+        }
+        if let Some(field) = self.field {
+            write!(f, ", field {}", field)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " at offset {}", offset)?;
+        }
+        write!(f, ": {}", self.cause)
+    }
+}
 
 #[derive(Debug)]
 pub enum FrameDecodeErr {
     Incomplete,
     UnknowFrameType,
-    UnknownClassType,
-    UnknownMethodType,
+    // channel, class id
+    UnknownClassType(u16, u16),
+    // channel, class id, method id
+    UnknownMethodType(u16, u16, u16),
     ParseAmqpHeaderFailed,
-    ParseFrameFailed,
+    // the broker answered with the protocol header it supports instead
+    ProtocolRejected(u8, u8, u8, u8),
+    // a client announced a protocol other than 0-9-1
+    UnsupportedProtocol(ProtocolVersion),
+    Syntax(Box<SyntaxErr>),
+    InvalidHeartbeatFrame,
+    InvalidFrameEnd(u8),
+    FrameTooLarge(u64),
+    // content frame out of sequence on the channel
+    UnexpectedFrame(u16),
+    // channel, body_size from the header, body bytes received
+    BodySizeMismatch(u16, u64, u64),
     Amqp(amqp_types::error::Error),
     Io(io::Error)
 }
@@ -19,18 +142,360 @@ impl Display for FrameDecodeErr {
         match self {
             FrameDecodeErr::Incomplete => write!(f, "Incomplete"),
             FrameDecodeErr::UnknowFrameType => write!(f, "unknow frame type"),
-            FrameDecodeErr::UnknownClassType => write!(f, "unknown class type"),
-            FrameDecodeErr::UnknownMethodType => write!(f, "unknown method type"),
+            FrameDecodeErr::UnknownClassType(channel, class_id) => write!(f, "unknown class {} on channel {}", class_id, channel),
+            FrameDecodeErr::UnknownMethodType(channel, class_id, method_id) => write!(f, "unknown method {}.{} on channel {}", class_id, method_id, channel),
             FrameDecodeErr::ParseAmqpHeaderFailed => write!(f, "parse Amqp header failed"),
-            FrameDecodeErr::ParseFrameFailed => write!(f, "parse frame failed"),
+            FrameDecodeErr::ProtocolRejected(major_id, minor_id, major_version, minor_version) => {
+                write!(f, "protocol rejected, broker speaks AMQP {}-{}-{}-{}", major_id, minor_id, major_version, minor_version)
+            }
+            FrameDecodeErr::UnsupportedProtocol(version) => write!(f, "unsupported protocol {:?}", version),
+            FrameDecodeErr::Syntax(err) => write!(f, "{}", err),
+            FrameDecodeErr::InvalidHeartbeatFrame => write!(f, "heartbeat frame must be empty and on channel 0"),
+            FrameDecodeErr::InvalidFrameEnd(found) => write!(f, "invalid frame end 0x{:02x}, expect 0xce", found),
+            FrameDecodeErr::FrameTooLarge(size) => write!(f, "frame of {} bytes exceeds frame_max", size),
+            FrameDecodeErr::UnexpectedFrame(channel) => write!(f, "unexpected frame on channel {}", channel),
+            FrameDecodeErr::BodySizeMismatch(channel, expected, received) => write!(f, "content body on channel {} is {} bytes, header announced {}", channel, received, expected),
             FrameDecodeErr::Amqp(err) => write!(f, "amqp error: {}", err),
             FrameDecodeErr::Io(err) => write!(f, "{}", err)
         }
     }
 }
 
+// reply codes from the AMQP 0-9-1 spec, all of them close the connection
+pub const ACCESS_REFUSED: u16 = 403;
+pub const FRAME_ERROR: u16 = 501;
+pub const SYNTAX_ERROR: u16 = 502;
+pub const COMMAND_INVALID: u16 = 503;
+pub const UNEXPECTED_FRAME: u16 = 505;
+pub const NOT_ALLOWED: u16 = 530;
+pub const NOT_IMPLEMENTED: u16 = 540;
+
+impl FrameDecodeErr {
+    // reply code for the Connection.Close sent when giving up on the peer,
+    // None for errors that are not the peer's fault
+    pub fn reply_code(&self) -> Option<u16> {
+        match self {
+            FrameDecodeErr::Incomplete => None,
+            FrameDecodeErr::UnknowFrameType => Some(FRAME_ERROR),
+            FrameDecodeErr::InvalidHeartbeatFrame => Some(FRAME_ERROR),
+            FrameDecodeErr::InvalidFrameEnd(_) => Some(FRAME_ERROR),
+            FrameDecodeErr::FrameTooLarge(_) => Some(FRAME_ERROR),
+            FrameDecodeErr::UnexpectedFrame(_) => Some(UNEXPECTED_FRAME),
+            FrameDecodeErr::BodySizeMismatch(..) => Some(FRAME_ERROR),
+            FrameDecodeErr::UnknownClassType(..) => Some(NOT_IMPLEMENTED),
+            FrameDecodeErr::UnknownMethodType(..) => Some(NOT_IMPLEMENTED),
+            FrameDecodeErr::ParseAmqpHeaderFailed => None,
+            FrameDecodeErr::ProtocolRejected(..) => None,
+            FrameDecodeErr::UnsupportedProtocol(_) => None,
+            FrameDecodeErr::Syntax(_) => Some(SYNTAX_ERROR),
+            FrameDecodeErr::Amqp(_) => Some(SYNTAX_ERROR),
+            FrameDecodeErr::Io(_) => None
+        }
+    }
+}
+
+impl FrameDecodeErr {
+    // parsing stopped with `buffer` left unread
+    pub(crate) fn syntax<C: Into<DecodeCause>>(buffer: &[u8], cause: C) -> FrameDecodeErr {
+        FrameDecodeErr::Syntax(Box::new(SyntaxErr {
+            channel: None,
+            class_id: None,
+            method_id: None,
+            field: None,
+            remaining: buffer.len(),
+            offset: None,
+            cause: cause.into()
+        }))
+    }
+
+    // reading `field` failed, `buffer` starts at the field
+    pub(crate) fn field<C: Into<DecodeCause>>(field: &'static str, buffer: &[u8], cause: C) -> FrameDecodeErr {
+        FrameDecodeErr::syntax(buffer, cause).in_field(field, buffer)
+    }
+
+    // name the field an error from a nested parser belongs to, the position
+    // found by the nested parser is kept as it is the more precise one
+    pub(crate) fn in_field(self, field: &'static str, buffer: &[u8]) -> FrameDecodeErr {
+        match self {
+            FrameDecodeErr::Syntax(mut err) => {
+                err.field = Some(field);
+                FrameDecodeErr::Syntax(err)
+            }
+            FrameDecodeErr::Amqp(err) => FrameDecodeErr::field(field, buffer, err),
+            err => err
+        }
+    }
+
+    // an error from a nested buffer that is followed by `after` in the
+    // enclosing one
+    pub(crate) fn behind(self, after: &[u8]) -> FrameDecodeErr {
+        match self {
+            FrameDecodeErr::Syntax(mut err) => {
+                err.remaining += after.len();
+                FrameDecodeErr::Syntax(err)
+            }
+            err => err
+        }
+    }
+
+    // place an error from the payload parsers within its frame
+    pub(crate) fn in_frame(self, channel: u16, class_id: Option<u16>, method_id: Option<u16>, payload: &[u8]) -> FrameDecodeErr {
+        match self {
+            FrameDecodeErr::Syntax(mut err) => {
+                err.channel = Some(channel);
+                err.class_id = class_id;
+                err.method_id = method_id;
+                err.offset = Some(FRAME_PREFIX_LENGTH as usize + payload.len().saturating_sub(err.remaining));
+                FrameDecodeErr::Syntax(err)
+            }
+            FrameDecodeErr::UnknownClassType(_, _) => FrameDecodeErr::UnknownClassType(channel, class_id.unwrap_or(0)),
+            FrameDecodeErr::UnknownMethodType(_, _, _) => FrameDecodeErr::UnknownMethodType(channel, class_id.unwrap_or(0), method_id.unwrap_or(0)),
+            err => err
+        }
+    }
+}
+
 impl From<io::Error> for FrameDecodeErr {
     fn from(err: Error) -> Self {
         FrameDecodeErr::Io(err)
     }
 }
+
+#[derive(Debug)]
+pub enum FrameEncodeErr {
+    UnknownClassType,
+    UnknownMethodType,
+    UnknownFieldValueType,
+    UnsupportedPayload,
+    Io(io::Error)
+}
+
+impl Display for FrameEncodeErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameEncodeErr::UnknownClassType => write!(f, "unknown class type"),
+            FrameEncodeErr::UnknownMethodType => write!(f, "unknown method type"),
+            FrameEncodeErr::UnknownFieldValueType => write!(f, "unknown field value type"),
+            FrameEncodeErr::UnsupportedPayload => write!(f, "payload does not match frame type"),
+            FrameEncodeErr::Io(err) => write!(f, "{}", err)
+        }
+    }
+}
+
+impl From<io::Error> for FrameEncodeErr {
+    fn from(err: Error) -> Self {
+        FrameEncodeErr::Io(err)
+    }
+}
+
+#[derive(Debug)]
+pub enum CaptureErr {
+    // the file does not start with the capture magic
+    InvalidHeader,
+    UnsupportedVersion(u8),
+    InvalidDirection(u8),
+    // the file ends in the middle of a record
+    Truncated,
+    // a record that does not hold exactly one frame
+    InvalidRecord,
+    Decode(FrameDecodeErr),
+    Encode(FrameEncodeErr),
+    Io(io::Error)
+}
+
+impl Display for CaptureErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            CaptureErr::InvalidHeader => write!(f, "not a frame capture"),
+            CaptureErr::UnsupportedVersion(version) => write!(f, "unsupported capture version {}", version),
+            CaptureErr::InvalidDirection(direction) => write!(f, "invalid direction {} in capture record", direction),
+            CaptureErr::Truncated => write!(f, "capture ends inside a record"),
+            CaptureErr::InvalidRecord => write!(f, "capture record does not hold exactly one frame"),
+            CaptureErr::Decode(err) => write!(f, "{}", err),
+            CaptureErr::Encode(err) => write!(f, "{}", err),
+            CaptureErr::Io(err) => write!(f, "{}", err)
+        }
+    }
+}
+
+impl From<io::Error> for CaptureErr {
+    fn from(err: Error) -> Self {
+        CaptureErr::Io(err)
+    }
+}
+
+impl From<FrameDecodeErr> for CaptureErr {
+    fn from(err: FrameDecodeErr) -> Self {
+        CaptureErr::Decode(err)
+    }
+}
+
+impl From<FrameEncodeErr> for CaptureErr {
+    fn from(err: FrameEncodeErr) -> Self {
+        CaptureErr::Encode(err)
+    }
+}
+
+#[derive(Debug)]
+pub enum PcapErr {
+    // neither a pcap nor a pcapng file
+    InvalidHeader,
+    // pcapng block type with a length that cannot be right
+    InvalidBlock(u32),
+    // the file ends in the middle of a packet or block
+    Truncated,
+    // pcap record length no packet can have
+    InvalidRecord(u32),
+    // stream offset of bytes missing from the capture
+    Gap(u64),
+    Decode(FrameDecodeErr),
+    Io(io::Error)
+}
+
+impl Display for PcapErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PcapErr::InvalidHeader => write!(f, "not a pcap or pcapng file"),
+            PcapErr::InvalidBlock(block_type) => write!(f, "invalid pcapng block 0x{:08x}", block_type),
+            PcapErr::Truncated => write!(f, "capture ends inside a packet"),
+            PcapErr::InvalidRecord(length) => write!(f, "invalid pcap record of {} bytes", length),
+            PcapErr::Gap(offset) => write!(f, "segment missing from the capture at stream offset {}", offset),
+            PcapErr::Decode(err) => write!(f, "{}", err),
+            PcapErr::Io(err) => write!(f, "{}", err)
+        }
+    }
+}
+
+impl From<io::Error> for PcapErr {
+    fn from(err: Error) -> Self {
+        PcapErr::Io(err)
+    }
+}
+
+impl From<FrameDecodeErr> for PcapErr {
+    fn from(err: FrameDecodeErr) -> Self {
+        PcapErr::Decode(err)
+    }
+}
+
+// a well known key of an argument or property table that did not hold what
+// the key asks for
+#[derive(Debug)]
+pub enum ArgumentErr {
+    // key, type the key expects
+    InvalidType(String, &'static str),
+    // key, value the key does not accept
+    InvalidValue(String, String),
+    Amqp(amqp_types::error::Error)
+}
+
+impl Display for ArgumentErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            ArgumentErr::InvalidType(key, expected) => write!(f, "{} must be {}", key, expected),
+            ArgumentErr::InvalidValue(key, value) => write!(f, "invalid value {} for {}", value, key),
+            ArgumentErr::Amqp(err) => write!(f, "{}", err)
+        }
+    }
+}
+
+impl From<amqp_types::error::Error> for ArgumentErr {
+    fn from(err: amqp_types::error::Error) -> Self {
+        ArgumentErr::Amqp(err)
+    }
+}
+
+#[derive(Debug)]
+pub enum SaslErr {
+    UnknownMechanism(String),
+    // mechanism whose layout the response does not follow
+    MalformedResponse(&'static str),
+    // PLAIN and AMQPLAIN need one, EXTERNAL takes none
+    MissingPassword,
+    Amqp(amqp_types::error::Error)
+}
+
+impl Display for SaslErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            SaslErr::UnknownMechanism(mechanism) => write!(f, "unknown sasl mechanism {}", mechanism),
+            SaslErr::MalformedResponse(mechanism) => write!(f, "malformed {} response", mechanism),
+            SaslErr::MissingPassword => write!(f, "password missing"),
+            SaslErr::Amqp(err) => write!(f, "{}", err)
+        }
+    }
+}
+
+impl From<amqp_types::error::Error> for SaslErr {
+    fn from(err: amqp_types::error::Error) -> Self {
+        SaslErr::Amqp(err)
+    }
+}
+
+#[derive(Debug)]
+pub enum HandshakeErr {
+    // the peer answered with the protocol header it supports instead
+    ProtocolRejected(u8, u8, u8, u8),
+    // version_major and version_minor from Connection.Start
+    UnsupportedVersion(u8, u8),
+    // the mechanisms we asked for, space separated
+    MechanismNotOffered(String),
+    // the server sent Connection.Secure but no handler was configured
+    SecureNotSupported,
+    // mechanism whose response was refused
+    AuthenticationFailed(String),
+    VhostRefused(String),
+    // TuneOk values beyond the limits sent in Connection.Tune, or a frame_max
+    // below the 4096 minimum
+    InvalidTuning(Tuning),
+    // class id and method id of a method that does not fit the handshake step
+    UnexpectedMethod(u16, u16),
+    UnexpectedFrame(u16),
+    // reply code and text of the Connection.Close ending the handshake
+    ConnectionClosed(u16, String)
+}
+
+impl Display for HandshakeErr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            HandshakeErr::ProtocolRejected(major_id, minor_id, major_version, minor_version) => {
+                write!(f, "protocol rejected, peer speaks AMQP {}-{}-{}-{}", major_id, minor_id, major_version, minor_version)
+            }
+            HandshakeErr::UnsupportedVersion(major, minor) => write!(f, "unsupported protocol version {}-{}", major, minor),
+            HandshakeErr::MechanismNotOffered(mechanism) => write!(f, "mechanism {} not offered by peer", mechanism),
+            HandshakeErr::SecureNotSupported => write!(f, "no handler for connection.secure challenge"),
+            HandshakeErr::AuthenticationFailed(mechanism) => write!(f, "{} authentication failed", mechanism),
+            HandshakeErr::VhostRefused(vhost) => write!(f, "access to vhost {} refused", vhost),
+            HandshakeErr::InvalidTuning(tuning) => {
+                write!(f, "invalid tuning channel_max {}, frame_max {}", tuning.channel_max(), tuning.frame_max())
+            }
+            HandshakeErr::UnexpectedMethod(class_id, method_id) => {
+                match method_name(*class_id, *method_id) {
+                    Some(name) => write!(f, "unexpected method {} during handshake", name),
+                    None => write!(f, "unexpected method {}.{} during handshake", class_id, method_id)
+                }
+            }
+            HandshakeErr::UnexpectedFrame(channel) => write!(f, "unexpected frame on channel {} during handshake", channel),
+            HandshakeErr::ConnectionClosed(reply_code, reply_text) => write!(f, "connection closed by peer: {} {}", reply_code, reply_text)
+        }
+    }
+}
+
+impl HandshakeErr {
+    // reply code for the Connection.Close refusing the handshake, None when
+    // there is nobody left to send it to or the peer does not speak 0-9-1
+    pub fn reply_code(&self) -> Option<u16> {
+        match self {
+            HandshakeErr::ProtocolRejected(..) => None,
+            HandshakeErr::UnsupportedVersion(..) => None,
+            HandshakeErr::MechanismNotOffered(_) => Some(ACCESS_REFUSED),
+            HandshakeErr::SecureNotSupported => Some(NOT_IMPLEMENTED),
+            HandshakeErr::AuthenticationFailed(_) => Some(ACCESS_REFUSED),
+            HandshakeErr::VhostRefused(_) => Some(NOT_ALLOWED),
+            HandshakeErr::InvalidTuning(_) => Some(NOT_ALLOWED),
+            HandshakeErr::UnexpectedMethod(..) => Some(COMMAND_INVALID),
+            HandshakeErr::UnexpectedFrame(_) => Some(UNEXPECTED_FRAME),
+            HandshakeErr::ConnectionClosed(..) => None
+        }
+    }
+}
@@ -1,10 +1,13 @@
-use tokio_util::codec::Decoder;
+use tokio_util::codec::{Decoder, Encoder};
 use amqp_types::{Frame, FrameType};
 use bytes::BytesMut;
 use nom::number::streaming::be_u8;
 use amqp_types::frame::ProtocolHeader;
-use crate::error::FrameDecodeErr;
-use crate::parse::{parse_amqp_protocal_header, parse_method_frame, parse_content_header_frame, parse_content_body_frame, parse_heartbeat_frame};
+use crate::error::{FrameDecodeErr, FrameEncodeErr};
+use crate::encode::{write_amqp_protocol_header, write_frame};
+use crate::common::parse_channel_id_and_length;
+use crate::parse::{FRAME_PREFIX_LENGTH, FRAME_END_LENGTH, FRAME_MIN_SIZE, parse_amqp_protocal_header, classify_protocol_header, parse_method_frame, parse_content_header_frame, split_content_body_frame, parse_heartbeat_frame};
+use crate::shared::{split_shared_frame, SharedFrame};
 use crate::frame_codec::DecodedFrame::AmqpFrame;
 use nom::error::ErrorKind;
 
@@ -13,93 +16,186 @@ pub enum DecodedFrame {
     AmqpFrame(Frame)
 }
 
+// which end of the connection the codec decodes for
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Role {
+    // expects frames straight away, the broker only sends a protocol header to refuse ours
+    Client,
+    // expects the client's protocol header first
+    Server
+}
+
 pub struct FrameCodec {
+    role: Role,
     header_received: bool,
+    frame_max: u32,
+}
+
+// what decode_preamble found at the front of the buffer
+enum Preamble {
+    Incomplete,
+    Header(ProtocolHeader),
+    // the header exchange is over, frames follow
+    Frames
 }
 
 impl FrameCodec {
     pub fn new() -> Self {
+        FrameCodec::with_role(Role::Server)
+    }
+
+    pub fn with_role(role: Role) -> Self {
         FrameCodec {
+            role,
             header_received: false,
+            frame_max: FRAME_MIN_SIZE,
         }
     }
-}
 
-impl Decoder for FrameCodec {
-    type Item = DecodedFrame;
-    type Error = FrameDecodeErr;
+    pub fn role(&self) -> Role {
+        self.role
+    }
 
-    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
-        // parse amqp header
+    pub fn frame_max(&self) -> u32 {
+        self.frame_max
+    }
+
+    // apply the frame_max agreed by Connection.Tune/TuneOk, 0 means no limit
+    pub fn set_frame_max(&mut self, frame_max: u32) {
+        self.frame_max = match frame_max {
+            0 => u32::MAX,
+            frame_max if frame_max < FRAME_MIN_SIZE => FRAME_MIN_SIZE,
+            frame_max => frame_max
+        };
+    }
+
+    // reject an oversized frame as soon as its length is readable
+    fn check_frame_size(&self, src: &[u8]) -> Result<(), FrameDecodeErr> {
+        if src.len() < FRAME_PREFIX_LENGTH as usize {
+            return Ok(());
+        }
+        let (_, _, payload_length) = parse_channel_id_and_length(&src[1..])?;
+        let frame_size = FRAME_PREFIX_LENGTH as u64 + payload_length as u64 + FRAME_END_LENGTH as u64;
+        if frame_size > self.frame_max as u64 {
+            return Err(FrameDecodeErr::FrameTooLarge(frame_size));
+        }
+        Ok(())
+    }
+
+    // the protocol header exchange in front of the first frame
+    fn decode_preamble(&mut self, src: &mut BytesMut) -> Result<Preamble, FrameDecodeErr> {
+        // a broker refusing our protocol header answers with the one it supports
+        if self.role == Role::Client && !self.header_received {
+            match src.first() {
+                Some(b'A') => {
+                    match parse_amqp_protocal_header(src) {
+                        Ok(header) => {
+                            src.split_to(crate::parse::PROTOCOL_HEADER_SIZE);
+                            return Err(FrameDecodeErr::ProtocolRejected(header.major_id(), header.minor_id(), header.major_version(), header.minor_version()));
+                        }
+                        Err(FrameDecodeErr::Incomplete) => return Ok(Preamble::Incomplete),
+                        Err(e) => return Err(e)
+                    }
+                }
+                Some(_) => self.header_received = true,
+                None => return Ok(Preamble::Incomplete)
+            }
+        }
+
+        // parse amqp header, a version we do not speak is refused with the
+        // header from ProtocolVersion::response_header
         if !self.header_received {
+            match classify_protocol_header(src) {
+                Ok(version) if version.is_supported() => {},
+                Ok(version) => return Err(FrameDecodeErr::UnsupportedProtocol(version)),
+                Err(FrameDecodeErr::Incomplete) => return Ok(Preamble::Incomplete),
+                Err(e) => return Err(e)
+            }
             match parse_amqp_protocal_header(src) {
                 Ok(header) => {
                     src.split_to(crate::parse::PROTOCOL_HEADER_SIZE);
-                    return Ok(Some(DecodedFrame::ProtocolHeader(header)))
+                    self.header_received = true;
+                    return Ok(Preamble::Header(header));
                 },
                 Err(e) => {
                     match e {
-                        FrameDecodeErr::Incomplete => return Ok(None),
+                        FrameDecodeErr::Incomplete => return Ok(Preamble::Incomplete),
                         _ => return Err(e)
                     }
                 }
             }
         }
+        Ok(Preamble::Frames)
+    }
+}
+
+impl Decoder for FrameCodec {
+    type Item = DecodedFrame;
+    type Error = FrameDecodeErr;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.decode_preamble(src)? {
+            Preamble::Incomplete => return Ok(None),
+            Preamble::Header(header) => return Ok(Some(DecodedFrame::ProtocolHeader(header))),
+            Preamble::Frames => {}
+        }
 
         // +-frame type: u8-+---channel id: u16---+-----length: u32-----+----payload---+--frame end--+
         // |   1|2|3|4      |       0x0000        |     payload length  |              |  0xce       |
         // +----------------+---------------------+---------------------+--------------+-------------+
-        let (buffer, frame_type_id) = match be_u8::<(_, ErrorKind)>(src) {
+        let (_, frame_type_id) = match be_u8::<(_, ErrorKind)>(&src[..]) {
             Ok(ret) => ret,
             Err(e) => {
                 match e {
                     nom::Err::Incomplete(_) => return Ok(None),
-                    _ => return Err(FrameDecodeErr::ParseFrameFailed)
+                    _ => return Err(FrameDecodeErr::syntax(&src[..], e))
                 }
             }
         };
         let frame_type: FrameType = FrameType::from(frame_type_id);
+        self.check_frame_size(src)?;
         let (frame_length, frame) = match frame_type {
             FrameType::METHOD => {
-                match parse_method_frame(buffer) {
+                match parse_method_frame(src) {
                     Ok(ret) => ret,
                     Err(e) => {
                         match e {
                             FrameDecodeErr::Incomplete => return Ok(None),
-                            _ => return Err(FrameDecodeErr::ParseFrameFailed)
+                            _ => return Err(e)
                         }
                     }
                 }
             },
             FrameType::HEADER => {
-                match parse_content_header_frame(buffer) {
+                match parse_content_header_frame(src) {
                     Ok(ret) => ret,
                     Err(e) => {
                         match e {
                             FrameDecodeErr::Incomplete => return Ok(None),
-                            _ => return Err(FrameDecodeErr::ParseFrameFailed)
+                            _ => return Err(e)
                         }
                     }
                 }
             },
             FrameType::BODY => {
-                match parse_content_body_frame(buffer) {
-                    Ok(ret) => ret,
+                // body payload is handed out as a view into src, nothing left to split
+                match split_content_body_frame(src) {
+                    Ok(frame) => return Ok(Some(AmqpFrame(frame))),
                     Err(e) => {
                         match e {
                             FrameDecodeErr::Incomplete => return Ok(None),
-                            _ => return Err(FrameDecodeErr::ParseFrameFailed)
+                            _ => return Err(e)
                         }
                     }
                 }
             },
             FrameType::HEARTBEAT => {
-                match parse_heartbeat_frame(buffer) {
+                match parse_heartbeat_frame(src) {
                     Ok(ret) => ret,
                     Err(e) => {
                         match e {
                             FrameDecodeErr::Incomplete => return Ok(None),
-                            _ => return Err(FrameDecodeErr::ParseFrameFailed)
+                            _ => return Err(e)
                         }
                     }
                 }
@@ -110,3 +206,166 @@ impl Decoder for FrameCodec {
         Ok(Some(AmqpFrame(frame)))
     }
 }
+
+
+impl Encoder<DecodedFrame> for FrameCodec {
+    type Error = FrameEncodeErr;
+
+    fn encode(&mut self, item: DecodedFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        match item {
+            DecodedFrame::ProtocolHeader(header) => {
+                write_amqp_protocol_header(&header, dst);
+                Ok(())
+            },
+            DecodedFrame::AmqpFrame(frame) => write_frame(&frame, dst)
+        }
+    }
+}
+
+impl Encoder<Frame> for FrameCodec {
+    type Error = FrameEncodeErr;
+
+    fn encode(&mut self, item: Frame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        write_frame(&item, dst)
+    }
+}
+
+pub enum SharedDecodedFrame {
+    ProtocolHeader(ProtocolHeader),
+    Frame(SharedFrame)
+}
+
+// FrameCodec handing out frames as views into the read buffer, nothing is
+// copied until a to_* method on the shared types asks for it
+pub struct SharedFrameCodec {
+    codec: FrameCodec
+}
+
+impl SharedFrameCodec {
+    pub fn new() -> Self {
+        SharedFrameCodec::with_role(Role::Server)
+    }
+
+    pub fn with_role(role: Role) -> Self {
+        SharedFrameCodec { codec: FrameCodec::with_role(role) }
+    }
+
+    pub fn role(&self) -> Role {
+        self.codec.role()
+    }
+
+    pub fn frame_max(&self) -> u32 {
+        self.codec.frame_max()
+    }
+
+    pub fn set_frame_max(&mut self, frame_max: u32) {
+        self.codec.set_frame_max(frame_max);
+    }
+}
+
+impl Default for SharedFrameCodec {
+    fn default() -> Self {
+        SharedFrameCodec::new()
+    }
+}
+
+impl Decoder for SharedFrameCodec {
+    type Item = SharedDecodedFrame;
+    type Error = FrameDecodeErr;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        match self.codec.decode_preamble(src)? {
+            Preamble::Incomplete => return Ok(None),
+            Preamble::Header(header) => return Ok(Some(SharedDecodedFrame::ProtocolHeader(header))),
+            Preamble::Frames => {}
+        }
+        self.codec.check_frame_size(src)?;
+        match split_shared_frame(src) {
+            Ok(frame) => Ok(Some(SharedDecodedFrame::Frame(frame))),
+            Err(FrameDecodeErr::Incomplete) => Ok(None),
+            Err(e) => Err(e)
+        }
+    }
+}
+
+impl Encoder<DecodedFrame> for SharedFrameCodec {
+    type Error = FrameEncodeErr;
+
+    fn encode(&mut self, item: DecodedFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.codec.encode(item, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frame_codec::{FrameCodec, DecodedFrame, Role};
+    use crate::error::FrameDecodeErr;
+    use crate::parse::ProtocolVersion;
+    use tokio_util::codec::Decoder;
+    use bytes::BytesMut;
+
+    #[test]
+    fn test_frame_max() {
+        let mut codec = FrameCodec::new();
+        let mut src = BytesMut::from(&b"AMQP\x00\x00\x09\x01"[..]);
+        match codec.decode(&mut src) {
+            Ok(Some(DecodedFrame::ProtocolHeader(_))) => {},
+            _ => panic!("protocol header expected")
+        }
+
+        // body frame announcing 8192 bytes, only the prefix has arrived
+        src.extend_from_slice(&[0x03, 0x00, 0x01, 0x00, 0x00, 0x20, 0x00]);
+        match codec.decode(&mut src) {
+            Err(FrameDecodeErr::FrameTooLarge(8200)) => {},
+            _ => panic!("oversized frame accepted before tune")
+        }
+
+        codec.set_frame_max(131072);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+
+        codec.set_frame_max(512);
+        assert_eq!(codec.frame_max(), 4096);
+    }
+
+    #[test]
+    fn test_client_role() {
+        let mut codec = FrameCodec::with_role(Role::Client);
+        let mut src = BytesMut::from(&[0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xce][..]);
+        match codec.decode(&mut src) {
+            Ok(Some(DecodedFrame::AmqpFrame(_))) => {},
+            _ => panic!("heartbeat expected without protocol header")
+        }
+
+        let mut codec = FrameCodec::with_role(Role::Client);
+        let mut src = BytesMut::from(&b"AMQP\x00\x00\x09"[..]);
+        assert!(codec.decode(&mut src).unwrap().is_none());
+        src.extend_from_slice(b"\x01");
+        match codec.decode(&mut src) {
+            Err(FrameDecodeErr::ProtocolRejected(0, 0, 9, 1)) => {},
+            _ => panic!("header rejection not reported")
+        }
+    }
+
+    #[test]
+    fn test_unsupported_protocol() {
+        let cases: [(&[u8], ProtocolVersion); 5] = [
+            (b"AMQP\x01\x01\x00\x09", ProtocolVersion::Amqp09),
+            (b"AMQP\x01\x01\x08\x00", ProtocolVersion::Amqp08),
+            (b"AMQP\x00\x01\x00\x00", ProtocolVersion::Amqp10),
+            (b"AMQP\x03\x01\x00\x00", ProtocolVersion::Amqp10Sasl),
+            (b"GET ", ProtocolVersion::NotAmqp)
+        ];
+        for (header, version) in cases.iter() {
+            let mut codec = FrameCodec::new();
+            let mut src = BytesMut::from(*header);
+            match codec.decode(&mut src) {
+                Err(FrameDecodeErr::UnsupportedProtocol(found)) => {
+                    assert_eq!(found, *version);
+                    let response = found.response_header().unwrap();
+                    assert_eq!((response.major_id(), response.minor_id(), response.major_version(), response.minor_version()), (0, 0, 9, 1));
+                }
+                _ => panic!("{:?} accepted", version)
+            }
+        }
+    }
+}
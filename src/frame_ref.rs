@@ -0,0 +1,323 @@
+use amqp_types::FrameType;
+use nom::IResult;
+use nom::error::ErrorKind;
+use nom::bytes::complete::take;
+use nom::number::complete::{be_u8, be_u16, be_u32, be_u64};
+use crate::error::{FrameDecodeErr, DecodeCause};
+use crate::common::method_name;
+use crate::parse::parse_frame_payload;
+use crate::spec::{method_fields, Domain};
+
+// A frame looked at in place. Only the framing is checked up front, method
+// arguments are read from the buffer when asked for and nothing is copied.
+pub struct FrameRef<'a> {
+    frame_type: u8,
+    channel: u16,
+    payload: &'a [u8],
+    raw: &'a [u8]
+}
+
+impl<'a> FrameRef<'a> {
+    pub fn frame_type(&self) -> FrameType {
+        FrameType::from(self.frame_type)
+    }
+
+    pub fn channel(&self) -> u16 {
+        self.channel
+    }
+
+    pub fn payload(&self) -> &'a [u8] {
+        self.payload
+    }
+
+    // the whole frame as received, frame end included, for forwarding
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.raw
+    }
+
+    pub fn method(&self) -> Option<MethodRef<'a>> {
+        if self.frame_type != 1 {
+            return None;
+        }
+        Some(MethodRef {
+            channel: self.channel,
+            class_id: u16::from_be_bytes([self.payload[0], self.payload[1]]),
+            method_id: u16::from_be_bytes([self.payload[2], self.payload[3]]),
+            payload: self.payload
+        })
+    }
+
+    pub fn content_header(&self) -> Option<ContentHeaderRef<'a>> {
+        if self.frame_type != 2 {
+            return None;
+        }
+        Some(ContentHeaderRef { payload: self.payload })
+    }
+}
+
+// +-frame type: u8-+---channel id: u16---+-----length: u32-----+----payload---+--frame end--+
+// |   1|2|3|4      |       0x0000        |     payload length  |              |  0xce       |
+// +----------------+---------------------+---------------------+--------------+-------------+
+pub fn parse_frame_ref(buffer: &[u8]) -> Result<(u32, FrameRef), FrameDecodeErr> {
+    let frame_type = match buffer.first() {
+        Some(frame_type) => *frame_type,
+        None => return Err(FrameDecodeErr::Incomplete)
+    };
+    if let FrameType::UNKNOWN = FrameType::from(frame_type) {
+        return Err(FrameDecodeErr::UnknowFrameType);
+    }
+    let (channel, payload, frame_length) = parse_frame_payload(buffer)?;
+    // the fixed part of method and header payloads must be there
+    let fixed = match frame_type {
+        1 => 4,
+        2 => 14,
+        8 => {
+            if channel != 0 || !payload.is_empty() {
+                return Err(FrameDecodeErr::InvalidHeartbeatFrame);
+            }
+            0
+        }
+        _ => 0
+    };
+    if payload.len() < fixed {
+        return Err(FrameDecodeErr::syntax(&[], DecodeCause::UnexpectedEnd).in_frame(channel, None, None, payload));
+    }
+    Ok((frame_length, FrameRef {
+        frame_type,
+        channel,
+        payload,
+        raw: &buffer[..frame_length as usize]
+    }))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FieldRef<'a> {
+    Octet(u8),
+    Short(u16),
+    Long(u32),
+    LongLong(u64),
+    Bit(bool),
+    ShortStr(&'a [u8]),
+    LongStr(&'a [u8]),
+    // encoded table content, without the length
+    Table(&'a [u8])
+}
+
+pub struct MethodRef<'a> {
+    channel: u16,
+    class_id: u16,
+    method_id: u16,
+    payload: &'a [u8]
+}
+
+impl<'a> MethodRef<'a> {
+    pub fn class_id(&self) -> u16 {
+        self.class_id
+    }
+
+    pub fn method_id(&self) -> u16 {
+        self.method_id
+    }
+
+    pub fn name(&self) -> Option<&'static str> {
+        method_name(self.class_id, self.method_id)
+    }
+
+    pub fn arguments(&self) -> &'a [u8] {
+        &self.payload[4..]
+    }
+
+    // arguments in wire order, each one read as the iterator gets to it
+    pub fn fields(&self) -> Result<FieldIter<'a>, FrameDecodeErr> {
+        let fields = match method_fields(self.class_id, self.method_id) {
+            Some(fields) => fields,
+            None => return Err(FrameDecodeErr::UnknownMethodType(self.channel, self.class_id, self.method_id))
+        };
+        Ok(FieldIter {
+            channel: self.channel,
+            class_id: self.class_id,
+            method_id: self.method_id,
+            payload: self.payload,
+            fields,
+            index: 0,
+            buffer: self.arguments(),
+            bits: None
+        })
+    }
+
+    // reads the arguments up to `name` only
+    pub fn field(&self, name: &str) -> Result<Option<FieldRef<'a>>, FrameDecodeErr> {
+        for field in self.fields()? {
+            let (field_name, value) = field?;
+            if field_name == name {
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    pub fn short_str(&self, name: &str) -> Result<Option<&'a [u8]>, FrameDecodeErr> {
+        match self.field(name)? {
+            Some(FieldRef::ShortStr(value)) => Ok(Some(value)),
+            _ => Ok(None)
+        }
+    }
+
+    pub fn exchange(&self) -> Result<Option<&'a [u8]>, FrameDecodeErr> {
+        self.short_str("exchange")
+    }
+
+    pub fn routing_key(&self) -> Result<Option<&'a [u8]>, FrameDecodeErr> {
+        self.short_str("routing_key")
+    }
+
+    pub fn queue(&self) -> Result<Option<&'a [u8]>, FrameDecodeErr> {
+        self.short_str("queue")
+    }
+}
+
+pub struct FieldIter<'a> {
+    channel: u16,
+    class_id: u16,
+    method_id: u16,
+    payload: &'a [u8],
+    fields: &'static [(&'static str, Domain)],
+    index: usize,
+    buffer: &'a [u8],
+    // octet holding the current run of bits and the next bit to read
+    bits: Option<(u8, u8)>
+}
+
+impl<'a> FieldIter<'a> {
+    fn read(&mut self, name: &'static str, domain: Domain) -> Result<FieldRef<'a>, FrameDecodeErr> {
+        let buffer = self.buffer;
+        if let Domain::Bit = domain {
+            if let Some((octet, bit)) = self.bits {
+                if bit < 8 {
+                    self.bits = Some((octet, bit + 1));
+                    return Ok(FieldRef::Bit(octet & (1 << bit) != 0));
+                }
+            }
+        } else {
+            self.bits = None;
+        }
+        let parsed: IResult<&'a [u8], FieldRef<'a>, (&'a [u8], ErrorKind)> = match domain {
+            Domain::Octet => be_u8(buffer).map(|(remain, value)| (remain, FieldRef::Octet(value))),
+            Domain::Short => be_u16(buffer).map(|(remain, value)| (remain, FieldRef::Short(value))),
+            Domain::Long => be_u32(buffer).map(|(remain, value)| (remain, FieldRef::Long(value))),
+            Domain::LongLong => be_u64(buffer).map(|(remain, value)| (remain, FieldRef::LongLong(value))),
+            Domain::Bit => be_u8(buffer).map(|(remain, octet)| (remain, FieldRef::Octet(octet))),
+            Domain::ShortStr => {
+                be_u8(buffer).and_then(|(remain, length)| take(length)(remain))
+                    .map(|(remain, value)| (remain, FieldRef::ShortStr(value)))
+            }
+            Domain::LongStr => {
+                be_u32(buffer).and_then(|(remain, length)| take(length)(remain))
+                    .map(|(remain, value)| (remain, FieldRef::LongStr(value)))
+            }
+            Domain::Table => {
+                be_u32(buffer).and_then(|(remain, length)| take(length)(remain))
+                    .map(|(remain, value)| (remain, FieldRef::Table(value)))
+            }
+        };
+        let (remain, value) = match parsed {
+            Ok(ret) => ret,
+            Err(e) => {
+                return Err(FrameDecodeErr::field(name, buffer, e).in_frame(self.channel, Some(self.class_id), Some(self.method_id), self.payload));
+            }
+        };
+        self.buffer = remain;
+        match value {
+            // a fresh octet of bits, this field is its lowest one
+            FieldRef::Octet(octet) if domain == Domain::Bit => {
+                self.bits = Some((octet, 1));
+                Ok(FieldRef::Bit(octet & 1 != 0))
+            }
+            value => Ok(value)
+        }
+    }
+}
+
+impl<'a> Iterator for FieldIter<'a> {
+    type Item = Result<(&'static str, FieldRef<'a>), FrameDecodeErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (name, domain) = *self.fields.get(self.index)?;
+        self.index += 1;
+        match self.read(name, domain) {
+            Ok(value) => Some(Ok((name, value))),
+            Err(e) => {
+                // nothing after a broken field can be located
+                self.index = self.fields.len();
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+pub struct ContentHeaderRef<'a> {
+    payload: &'a [u8]
+}
+
+impl<'a> ContentHeaderRef<'a> {
+    pub fn class_id(&self) -> u16 {
+        u16::from_be_bytes([self.payload[0], self.payload[1]])
+    }
+
+    pub fn weight(&self) -> u16 {
+        u16::from_be_bytes([self.payload[2], self.payload[3]])
+    }
+
+    pub fn body_size(&self) -> u64 {
+        let mut size = [0u8; 8];
+        size.copy_from_slice(&self.payload[4..12]);
+        u64::from_be_bytes(size)
+    }
+
+    // property flags followed by the properties that are set
+    pub fn properties(&self) -> &'a [u8] {
+        &self.payload[12..]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frame_ref::{parse_frame_ref, FieldRef};
+    use crate::error::FrameDecodeErr;
+
+    // basic.publish on channel 5, exchange "ex", routing key "rk", mandatory
+    const PUBLISH: [u8; 21] = [0x01, 0x00, 0x05, 0x00, 0x00, 0x00, 0x0d,
+        0x00, 0x3c, 0x00, 0x28, 0x00, 0x00, 0x02, b'e', b'x', 0x02, b'r', b'k', 0x01, 0xce];
+
+    #[test]
+    fn test_frame_ref() {
+        let (length, frame) = parse_frame_ref(&PUBLISH).unwrap();
+        assert_eq!(length, 21);
+        assert_eq!(frame.channel(), 5);
+        assert_eq!(frame.as_bytes().as_ptr(), PUBLISH.as_ptr());
+
+        let method = frame.method().unwrap();
+        assert_eq!(method.name(), Some("basic.publish"));
+        assert_eq!(method.routing_key().unwrap(), Some(&b"rk"[..]));
+        assert_eq!(method.field("mandatory").unwrap(), Some(FieldRef::Bit(true)));
+        assert_eq!(method.field("immediate").unwrap(), Some(FieldRef::Bit(false)));
+    }
+
+    #[test]
+    fn test_frame_ref_truncated_arguments() {
+        // same frame without the bits octet
+        let mut frame = Vec::from(&PUBLISH[..19]);
+        frame[6] = 0x0c;
+        frame.push(0xce);
+        let (_, frame) = parse_frame_ref(&frame).unwrap();
+        let method = frame.method().unwrap();
+        assert_eq!(method.exchange().unwrap(), Some(&b"ex"[..]));
+        match method.field("immediate") {
+            Err(FrameDecodeErr::Syntax(err)) => {
+                assert_eq!(err.field(), Some("mandatory"));
+                assert_eq!(err.offset(), Some(19));
+            }
+            _ => panic!("truncated arguments accepted")
+        }
+    }
+}
@@ -0,0 +1,863 @@
+use amqp_types::frame::{Arguments, Class, Method, ConnectionMethod, ConnectionStart, ConnectionStartOk, ConnectionSecure, ConnectionSecureOk, ConnectionTune, ConnectionTuneOk, ConnectionOpen, ConnectionOpenOk, ConnectionClose, ConnectionCloseOk, Payload};
+use amqp_types::{Frame, FieldTable, ShortStr, LongStr};
+use crate::error::{HandshakeErr, SaslErr};
+use crate::sasl::{select_mechanism, Credentials, Mechanism};
+use crate::frame_codec::DecodedFrame;
+use crate::encode::{protocol_header, method_frame, no_method};
+use crate::common::{get_class_id, get_method_id};
+use crate::parse::{FRAME_MIN_SIZE, ProtocolVersion};
+
+// Parameters agreed by Connection.Tune and Connection.TuneOk, 0 means no limit
+// for channel_max and frame_max and no heartbeats for heartbeat.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tuning {
+    channel_max: u16,
+    frame_max: u32,
+    heartbeat: u16
+}
+
+impl Tuning {
+    pub fn new(channel_max: u16, frame_max: u32, heartbeat: u16) -> Self {
+        Tuning { channel_max, frame_max, heartbeat }
+    }
+
+    pub fn channel_max(&self) -> u16 {
+        self.channel_max
+    }
+
+    pub fn frame_max(&self) -> u32 {
+        self.frame_max
+    }
+
+    pub fn heartbeat(&self) -> u16 {
+        self.heartbeat
+    }
+
+    // the lower of the two limits, unless one side has none
+    pub(crate) fn negotiate(&self, other: &Tuning) -> Tuning {
+        Tuning {
+            channel_max: negotiate(self.channel_max as u32, other.channel_max as u32) as u16,
+            frame_max: negotiate(self.frame_max, other.frame_max),
+            heartbeat: negotiate(self.heartbeat as u32, other.heartbeat as u32) as u16
+        }
+    }
+}
+
+fn negotiate(ours: u32, theirs: u32) -> u32 {
+    if ours == 0 || theirs == 0 {
+        ours.max(theirs)
+    } else {
+        ours.min(theirs)
+    }
+}
+
+pub(crate) fn connection_method(method: ConnectionMethod) -> Method {
+    Method::ConnectionMethod(method)
+}
+
+// class and method id of a method frame, for error reports
+pub(crate) fn method_ids(frame: &Frame) -> (u16, u16) {
+    match frame.payload() {
+        Payload::Method(method) => (get_class_id(method.class()).unwrap_or(0), get_method_id(method.method()).unwrap_or(0)),
+        _ => (0, 0)
+    }
+}
+
+pub(crate) fn connection_closed(close: &ConnectionClose) -> HandshakeErr {
+    HandshakeErr::ConnectionClosed(close.reply_code(), String::from_utf8_lossy(close.reply_text().as_bytes()).into_owned())
+}
+
+pub struct ClientConfig {
+    vhost: ShortStr,
+    // mechanism and response, in order of preference
+    mechanisms: Vec<(Mechanism, LongStr)>,
+    locale: ShortStr,
+    client_properties: FieldTable,
+    tuning: Tuning,
+    // answers the challenge of each Connection.Secure round
+    secure_handler: Option<Box<dyn FnMut(&LongStr) -> LongStr>>
+}
+
+impl ClientConfig {
+    pub fn new(vhost: ShortStr, mechanism: Mechanism, response: LongStr) -> Self {
+        ClientConfig {
+            vhost,
+            mechanisms: vec![(mechanism, response)],
+            locale: ShortStr::with_bytes(b"en_US").unwrap(),
+            client_properties: FieldTable::new(),
+            tuning: Tuning::new(0, 0, 60),
+            secure_handler: None
+        }
+    }
+
+    // answers with the first of `mechanisms` the server offers
+    pub fn with_credentials(vhost: ShortStr, credentials: &Credentials, mechanisms: &[Mechanism]) -> Result<Self, SaslErr> {
+        let mut config = ClientConfig::new(vhost, Mechanism::Plain, LongStr::default());
+        config.mechanisms.clear();
+        for mechanism in mechanisms.iter() {
+            config.mechanisms.push((*mechanism, mechanism.response(credentials)?));
+        }
+        Ok(config)
+    }
+
+    pub fn set_locale(&mut self, locale: ShortStr) {
+        self.locale = locale;
+    }
+
+    pub fn set_client_properties(&mut self, client_properties: FieldTable) {
+        self.client_properties = client_properties;
+    }
+
+    // the client side limits, negotiated against Connection.Tune
+    pub fn set_tuning(&mut self, tuning: Tuning) {
+        self.tuning = tuning;
+    }
+
+    pub fn set_secure_handler(&mut self, handler: Box<dyn FnMut(&LongStr) -> LongStr>) {
+        self.secure_handler = Some(handler);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ClientState {
+    Initial,
+    AwaitStart,
+    AwaitSecureOrTune,
+    AwaitOpenOk,
+    Open,
+    // the server closed the connection, Close-Ok is owed
+    Closed,
+    Failed
+}
+
+// Client side of the connection handshake. Feed it everything decoded from
+// the server and write out whatever it returns, it does no IO itself.
+pub struct ClientHandshake {
+    config: ClientConfig,
+    state: ClientState,
+    server_properties: Option<FieldTable>,
+    tuning: Option<Tuning>,
+    close_ok: Option<DecodedFrame>
+}
+
+impl ClientHandshake {
+    pub fn new(config: ClientConfig) -> Self {
+        ClientHandshake {
+            config,
+            state: ClientState::Initial,
+            server_properties: None,
+            tuning: None,
+            close_ok: None
+        }
+    }
+
+    // the protocol header opening the connection
+    pub fn start(&mut self) -> DecodedFrame {
+        self.state = ClientState::AwaitStart;
+        DecodedFrame::ProtocolHeader(protocol_header(0, 0, 9, 1))
+    }
+
+    pub fn handle(&mut self, frame: DecodedFrame) -> Result<Vec<DecodedFrame>, HandshakeErr> {
+        let result = self.step(frame);
+        match &result {
+            Err(HandshakeErr::ConnectionClosed(..)) => {
+                self.state = ClientState::Closed;
+                self.close_ok = Some(self.reply(ConnectionMethod::CloseOk, Arguments::ConnectionCloseOk(ConnectionCloseOk::default())));
+            }
+            Err(_) => self.state = ClientState::Failed,
+            Ok(_) => {}
+        }
+        result
+    }
+
+    fn step(&mut self, frame: DecodedFrame) -> Result<Vec<DecodedFrame>, HandshakeErr> {
+        let frame = match frame {
+            DecodedFrame::ProtocolHeader(header) => {
+                return Err(HandshakeErr::ProtocolRejected(header.major_id(), header.minor_id(), header.major_version(), header.minor_version()));
+            }
+            DecodedFrame::AmqpFrame(frame) => frame
+        };
+        let args = match frame.payload() {
+            Payload::Method(method) if frame.channel() == 0 => method.args(),
+            // the server may start heartbeats as soon as it has sent Tune
+            Payload::Heartbeat => return Ok(Vec::new()),
+            _ => return Err(HandshakeErr::UnexpectedFrame(frame.channel()))
+        };
+        match (self.state, args) {
+            (_, Arguments::ConnectionClose(close)) => Err(connection_closed(close)),
+            (ClientState::AwaitStart, Arguments::ConnectionStart(start)) => self.start_ok(start),
+            (ClientState::AwaitSecureOrTune, Arguments::ConnectionSecure(secure)) => {
+                let handler = match self.config.secure_handler.as_mut() {
+                    Some(handler) => handler,
+                    None => return Err(HandshakeErr::SecureNotSupported)
+                };
+                let secure_ok = ConnectionSecureOk::default();
+                secure_ok.set_response(handler(secure.challenge()));
+                Ok(vec![self.reply(ConnectionMethod::SecureOk, Arguments::ConnectionSecureOk(secure_ok))])
+            }
+            (ClientState::AwaitSecureOrTune, Arguments::ConnectionTune(tune)) => self.tune_ok(tune),
+            (ClientState::AwaitOpenOk, Arguments::ConnectionOpenOk(_)) => {
+                self.state = ClientState::Open;
+                Ok(Vec::new())
+            }
+            _ => {
+                let (class_id, method_id) = method_ids(&frame);
+                Err(HandshakeErr::UnexpectedMethod(class_id, method_id))
+            }
+        }
+    }
+
+    fn start_ok(&mut self, start: &ConnectionStart) -> Result<Vec<DecodedFrame>, HandshakeErr> {
+        if start.version_major() != 0 || start.version_minor() != 9 {
+            return Err(HandshakeErr::UnsupportedVersion(start.version_major(), start.version_minor()));
+        }
+        let preferred: Vec<Mechanism> = self.config.mechanisms.iter().map(|(mechanism, _)| *mechanism).collect();
+        let (mechanism, response) = match select_mechanism(start.mechanisms(), &preferred) {
+            Some(choice) => self.config.mechanisms.iter().find(|(mechanism, _)| *mechanism == choice).unwrap().clone(),
+            None => {
+                let names: Vec<&str> = preferred.iter().map(|mechanism| mechanism.name()).collect();
+                return Err(HandshakeErr::MechanismNotOffered(names.join(" ")));
+            }
+        };
+        self.server_properties = Some(start.server_properties().clone());
+
+        let start_ok = ConnectionStartOk::default();
+        start_ok.set_client_properties(self.config.client_properties.clone());
+        start_ok.set_mechanism(mechanism.short_str());
+        start_ok.set_response(response);
+        start_ok.set_locale(self.config.locale.clone());
+        self.state = ClientState::AwaitSecureOrTune;
+        Ok(vec![self.reply(ConnectionMethod::StartOk, Arguments::ConnectionStartOk(start_ok))])
+    }
+
+    fn tune_ok(&mut self, tune: &ConnectionTune) -> Result<Vec<DecodedFrame>, HandshakeErr> {
+        let offered = Tuning::new(tune.channel_max(), tune.frame_max(), tune.heartbeat());
+        let tuning = self.config.tuning.negotiate(&offered);
+        if tuning.frame_max() != 0 && tuning.frame_max() < FRAME_MIN_SIZE {
+            return Err(HandshakeErr::InvalidTuning(tuning));
+        }
+        self.tuning = Some(tuning);
+
+        let tune_ok = ConnectionTuneOk::default();
+        tune_ok.set_channel_max(tuning.channel_max());
+        tune_ok.set_frame_max(tuning.frame_max());
+        tune_ok.set_heartbeat(tuning.heartbeat());
+
+        let open = ConnectionOpen::default();
+        open.set_vhost(self.config.vhost.clone());
+        open.set_capabilities(ShortStr::default());
+        open.set_insist(false);
+        self.state = ClientState::AwaitOpenOk;
+        Ok(vec![
+            self.reply(ConnectionMethod::TuneOk, Arguments::ConnectionTuneOk(tune_ok)),
+            self.reply(ConnectionMethod::Open, Arguments::ConnectionOpen(open))
+        ])
+    }
+
+    fn reply(&self, method: ConnectionMethod, args: Arguments) -> DecodedFrame {
+        DecodedFrame::AmqpFrame(method_frame(0, Class::Connection, connection_method(method), args))
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state == ClientState::Open
+    }
+
+    pub fn is_failed(&self) -> bool {
+        self.state == ClientState::Failed
+    }
+
+    // the server closed the connection, see close_ok
+    pub fn is_closed(&self) -> bool {
+        self.state == ClientState::Closed
+    }
+
+    // Close-Ok answering the server's Connection.Close, handed out once and
+    // to be written before dropping the socket
+    pub fn close_ok(&mut self) -> Option<DecodedFrame> {
+        self.close_ok.take()
+    }
+
+    // known once Connection.Tune has been answered
+    pub fn tuning(&self) -> Option<&Tuning> {
+        self.tuning.as_ref()
+    }
+
+    pub fn server_properties(&self) -> Option<&FieldTable> {
+        self.server_properties.as_ref()
+    }
+}
+
+// outcome of checking a StartOk or SecureOk response
+pub enum Authentication {
+    // the identity the client authenticated as
+    Accepted(String),
+    // run another Connection.Secure round with this challenge
+    Challenge(LongStr),
+    Refused
+}
+
+pub struct ServerConfig {
+    server_properties: FieldTable,
+    mechanisms: LongStr,
+    locales: LongStr,
+    tuning: Tuning,
+    // called with the mechanism and each response the client sends
+    authenticate: Box<dyn FnMut(&ShortStr, &LongStr) -> Authentication>,
+    // called with the vhost of Connection.Open and the authenticated user
+    vhost_check: Box<dyn FnMut(&ShortStr, &str) -> bool>
+}
+
+impl ServerConfig {
+    pub fn new(authenticate: Box<dyn FnMut(&ShortStr, &LongStr) -> Authentication>) -> Self {
+        ServerConfig {
+            server_properties: FieldTable::new(),
+            mechanisms: LongStr::with_bytes(b"PLAIN").unwrap(),
+            locales: LongStr::with_bytes(b"en_US").unwrap(),
+            tuning: Tuning::new(2047, 131072, 60),
+            authenticate,
+            vhost_check: Box::new(|_, _| true)
+        }
+    }
+
+    pub fn set_server_properties(&mut self, server_properties: FieldTable) {
+        self.server_properties = server_properties;
+    }
+
+    // space separated list, as sent in Connection.Start
+    pub fn set_mechanisms(&mut self, mechanisms: LongStr) {
+        self.mechanisms = mechanisms;
+    }
+
+    pub fn set_locales(&mut self, locales: LongStr) {
+        self.locales = locales;
+    }
+
+    // the limits offered in Connection.Tune
+    pub fn set_tuning(&mut self, tuning: Tuning) {
+        self.tuning = tuning;
+    }
+
+    pub fn set_vhost_check(&mut self, vhost_check: Box<dyn FnMut(&ShortStr, &str) -> bool>) {
+        self.vhost_check = vhost_check;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ServerState {
+    AwaitHeader,
+    AwaitStartOk,
+    AwaitSecureOk,
+    AwaitTuneOk,
+    AwaitOpen,
+    Open,
+    // Connection.Close sent, waiting for Close-Ok
+    Closing,
+    Closed
+}
+
+// Server side of the connection handshake, starting from the protocol header
+// FrameCodec hands out. The peer is always answered, so refusals come back as
+// frames to send and the reason is kept in failure().
+pub struct ServerHandshake {
+    config: ServerConfig,
+    state: ServerState,
+    mechanism: Option<ShortStr>,
+    user: Option<String>,
+    client_properties: Option<FieldTable>,
+    tuning: Option<Tuning>,
+    vhost: Option<ShortStr>,
+    failure: Option<HandshakeErr>
+}
+
+impl ServerHandshake {
+    pub fn new(config: ServerConfig) -> Self {
+        ServerHandshake {
+            config,
+            state: ServerState::AwaitHeader,
+            mechanism: None,
+            user: None,
+            client_properties: None,
+            tuning: None,
+            vhost: None,
+            failure: None
+        }
+    }
+
+    pub fn handle(&mut self, frame: DecodedFrame) -> Vec<DecodedFrame> {
+        // a refusal names the method that caused it, 0/0 for other frames
+        let (class, method) = match &frame {
+            DecodedFrame::AmqpFrame(frame) => match frame.payload() {
+                Payload::Method(method) => (method.class().clone(), method.method().clone()),
+                _ => no_method()
+            },
+            DecodedFrame::ProtocolHeader(_) => no_method()
+        };
+        match self.step(frame) {
+            Ok(frames) => frames,
+            Err(err) => self.refuse(err, class, method)
+        }
+    }
+
+    fn step(&mut self, frame: DecodedFrame) -> Result<Vec<DecodedFrame>, HandshakeErr> {
+        match self.state {
+            ServerState::Closing | ServerState::Closed => return Ok(self.closing(frame)),
+            _ => {}
+        }
+        let frame = match (self.state, frame) {
+            (ServerState::AwaitHeader, DecodedFrame::ProtocolHeader(header)) => return self.start(header.major_id(), header.minor_id(), header.major_version(), header.minor_version()),
+            (_, DecodedFrame::AmqpFrame(frame)) => frame,
+            (_, DecodedFrame::ProtocolHeader(_)) => return Err(HandshakeErr::UnexpectedFrame(0))
+        };
+        let args = match frame.payload() {
+            Payload::Method(method) if frame.channel() == 0 => method.args(),
+            // the client may start heartbeats as soon as it has sent TuneOk
+            Payload::Heartbeat => return Ok(Vec::new()),
+            _ => return Err(HandshakeErr::UnexpectedFrame(frame.channel()))
+        };
+        match (self.state, args) {
+            (_, Arguments::ConnectionClose(close)) => Err(connection_closed(close)),
+            (ServerState::AwaitStartOk, Arguments::ConnectionStartOk(start_ok)) => {
+                let mechanism = start_ok.mechanism().as_bytes();
+                if !self.config.mechanisms.as_bytes().split(|c| *c == b' ').any(|offered| offered == mechanism) {
+                    return Err(HandshakeErr::MechanismNotOffered(String::from_utf8_lossy(mechanism).into_owned()));
+                }
+                self.mechanism = Some(start_ok.mechanism().clone());
+                self.client_properties = Some(start_ok.client_properties().clone());
+                self.authenticate(start_ok.response())
+            }
+            (ServerState::AwaitSecureOk, Arguments::ConnectionSecureOk(secure_ok)) => self.authenticate(secure_ok.response()),
+            (ServerState::AwaitTuneOk, Arguments::ConnectionTuneOk(tune_ok)) => {
+                let tuning = Tuning::new(tune_ok.channel_max(), tune_ok.frame_max(), tune_ok.heartbeat());
+                let offered = self.config.tuning;
+                if exceeds(tuning.channel_max() as u32, offered.channel_max() as u32)
+                    || exceeds(tuning.frame_max(), offered.frame_max())
+                    || (tuning.frame_max() != 0 && tuning.frame_max() < FRAME_MIN_SIZE) {
+                    return Err(HandshakeErr::InvalidTuning(tuning));
+                }
+                self.tuning = Some(tuning);
+                self.state = ServerState::AwaitOpen;
+                Ok(Vec::new())
+            }
+            (ServerState::AwaitOpen, Arguments::ConnectionOpen(open)) => {
+                let user = self.user.clone().unwrap_or_default();
+                if !(self.config.vhost_check)(open.vhost(), &user) {
+                    return Err(HandshakeErr::VhostRefused(String::from_utf8_lossy(open.vhost().as_bytes()).into_owned()));
+                }
+                self.vhost = Some(open.vhost().clone());
+                self.state = ServerState::Open;
+                let open_ok = ConnectionOpenOk::default();
+                open_ok.set_known_hosts(ShortStr::default());
+                Ok(vec![self.reply(ConnectionMethod::OpenOk, Arguments::ConnectionOpenOk(open_ok))])
+            }
+            _ => {
+                let (class_id, method_id) = method_ids(&frame);
+                Err(HandshakeErr::UnexpectedMethod(class_id, method_id))
+            }
+        }
+    }
+
+    // once Connection.Close went out only the client's Close-Ok counts, or its
+    // own Close when both sides closed at once; anything else is dropped
+    fn closing(&mut self, frame: DecodedFrame) -> Vec<DecodedFrame> {
+        let args = match &frame {
+            DecodedFrame::AmqpFrame(frame) if frame.channel() == 0 => match frame.payload() {
+                Payload::Method(method) => method.args(),
+                _ => return Vec::new()
+            },
+            _ => return Vec::new()
+        };
+        match (self.state, args) {
+            (ServerState::Closing, Arguments::ConnectionCloseOk(_)) => {
+                self.state = ServerState::Closed;
+                Vec::new()
+            }
+            (ServerState::Closing, Arguments::ConnectionClose(_)) => {
+                self.state = ServerState::Closed;
+                vec![self.reply(ConnectionMethod::CloseOk, Arguments::ConnectionCloseOk(ConnectionCloseOk::default()))]
+            }
+            _ => Vec::new()
+        }
+    }
+
+    fn start(&mut self, major_id: u8, minor_id: u8, major_version: u8, minor_version: u8) -> Result<Vec<DecodedFrame>, HandshakeErr> {
+        if !ProtocolVersion::from_ids(major_id, minor_id, major_version, minor_version).is_supported() {
+            return Err(HandshakeErr::UnsupportedVersion(major_version, minor_version));
+        }
+        let start = ConnectionStart::default();
+        start.set_version_major(0);
+        start.set_version_minor(9);
+        start.set_server_properties(self.config.server_properties.clone());
+        start.set_mechanisms(self.config.mechanisms.clone());
+        start.set_locales(self.config.locales.clone());
+        self.state = ServerState::AwaitStartOk;
+        Ok(vec![self.reply(ConnectionMethod::Start, Arguments::ConnectionStart(start))])
+    }
+
+    fn authenticate(&mut self, response: &LongStr) -> Result<Vec<DecodedFrame>, HandshakeErr> {
+        let mechanism = self.mechanism.clone().unwrap_or_default();
+        match (self.config.authenticate)(&mechanism, response) {
+            Authentication::Accepted(user) => {
+                self.user = Some(user);
+                let offered = self.config.tuning;
+                let tune = ConnectionTune::default();
+                tune.set_channel_max(offered.channel_max());
+                tune.set_frame_max(offered.frame_max());
+                tune.set_heartbeat(offered.heartbeat());
+                self.state = ServerState::AwaitTuneOk;
+                Ok(vec![self.reply(ConnectionMethod::Tune, Arguments::ConnectionTune(tune))])
+            }
+            Authentication::Challenge(challenge) => {
+                let secure = ConnectionSecure::default();
+                secure.set_challenge(challenge);
+                self.state = ServerState::AwaitSecureOk;
+                Ok(vec![self.reply(ConnectionMethod::Secure, Arguments::ConnectionSecure(secure))])
+            }
+            Authentication::Refused => Err(HandshakeErr::AuthenticationFailed(String::from_utf8_lossy(mechanism.as_bytes()).into_owned()))
+        }
+    }
+
+    // answer a failed step: our protocol header for a version mismatch,
+    // Close-Ok when the client closed, Connection.Close otherwise
+    fn refuse(&mut self, err: HandshakeErr, class: Class, method: Method) -> Vec<DecodedFrame> {
+        let frames = match (&err, err.reply_code()) {
+            (HandshakeErr::ConnectionClosed(..), _) => {
+                self.state = ServerState::Closed;
+                vec![self.reply(ConnectionMethod::CloseOk, Arguments::ConnectionCloseOk(ConnectionCloseOk::default()))]
+            }
+            (_, Some(reply_code)) => {
+                let close = ConnectionClose::default();
+                close.set_reply_code(reply_code);
+                close.set_reply_text(ShortStr::with_bytes(err.to_string().as_bytes()).unwrap_or_default());
+                close.set_class(class);
+                close.set_method(method);
+                self.state = ServerState::Closing;
+                vec![self.reply(ConnectionMethod::Close, Arguments::ConnectionClose(close))]
+            }
+            (_, None) => {
+                self.state = ServerState::Closed;
+                vec![DecodedFrame::ProtocolHeader(protocol_header(0, 0, 9, 1))]
+            }
+        };
+        self.failure = Some(err);
+        frames
+    }
+
+    fn reply(&self, method: ConnectionMethod, args: Arguments) -> DecodedFrame {
+        DecodedFrame::AmqpFrame(method_frame(0, Class::Connection, connection_method(method), args))
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.state == ServerState::Open
+    }
+
+    // the socket can be dropped once the returned frames are written
+    pub fn is_closed(&self) -> bool {
+        self.state == ServerState::Closed
+    }
+
+    pub fn failure(&self) -> Option<&HandshakeErr> {
+        self.failure.as_ref()
+    }
+
+    pub fn user(&self) -> Option<&str> {
+        self.user.as_deref()
+    }
+
+    pub fn client_properties(&self) -> Option<&FieldTable> {
+        self.client_properties.as_ref()
+    }
+
+    // what the client settled on in Connection.TuneOk
+    pub fn tuning(&self) -> Option<&Tuning> {
+        self.tuning.as_ref()
+    }
+
+    pub fn vhost(&self) -> Option<&ShortStr> {
+        self.vhost.as_ref()
+    }
+}
+
+// a client value above a server limit, 0 being the largest value of all
+fn exceeds(value: u32, limit: u32) -> bool {
+    limit != 0 && (value == 0 || value > limit)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::handshake::{ClientConfig, ClientHandshake, Tuning, ServerConfig, ServerHandshake, Authentication};
+    use crate::frame_codec::DecodedFrame;
+    use crate::encode::{method_frame, protocol_header, heartbeat_frame, no_method};
+    use crate::frame_codec::FrameCodec;
+    use crate::parse::parse_frame;
+    use tokio_util::codec::Encoder;
+    use bytes::BytesMut;
+    use crate::error::{HandshakeErr, NOT_ALLOWED};
+    use amqp_types::frame::{Arguments, Class, Method, ConnectionMethod, ConnectionStart, ConnectionTune, ConnectionOpenOk, ConnectionClose};
+    use amqp_types::{FieldTable, ShortStr, LongStr};
+    use crate::sasl::{self, Credentials, Mechanism};
+
+    fn server_frame(method: ConnectionMethod, args: Arguments) -> DecodedFrame {
+        DecodedFrame::AmqpFrame(method_frame(0, Class::Connection, Method::ConnectionMethod(method), args))
+    }
+
+    fn connection_start(mechanisms: &[u8]) -> DecodedFrame {
+        let start = ConnectionStart::default();
+        start.set_version_major(0);
+        start.set_version_minor(9);
+        start.set_server_properties(FieldTable::new());
+        start.set_mechanisms(LongStr::with_bytes(mechanisms).unwrap());
+        start.set_locales(LongStr::with_bytes(b"en_US").unwrap());
+        server_frame(ConnectionMethod::Start, Arguments::ConnectionStart(start))
+    }
+
+    fn client() -> ClientHandshake {
+        let mut config = ClientConfig::new(ShortStr::with_bytes(b"/").unwrap(),
+            Mechanism::Plain, LongStr::with_bytes(b"\x00guest\x00guest").unwrap());
+        config.set_tuning(Tuning::new(2047, 131072, 60));
+        ClientHandshake::new(config)
+    }
+
+    #[test]
+    fn test_client_handshake() {
+        let mut handshake = client();
+        match handshake.start() {
+            DecodedFrame::ProtocolHeader(header) => assert_eq!(header.minor_version(), 1),
+            _ => panic!("protocol header expected")
+        }
+        assert_eq!(handshake.handle(connection_start(b"AMQPLAIN PLAIN")).unwrap().len(), 1);
+
+        let tune = ConnectionTune::default();
+        tune.set_channel_max(0);
+        tune.set_frame_max(65536);
+        tune.set_heartbeat(30);
+        assert_eq!(handshake.handle(server_frame(ConnectionMethod::Tune, Arguments::ConnectionTune(tune))).unwrap().len(), 2);
+        assert_eq!(handshake.tuning(), Some(&Tuning::new(2047, 65536, 30)));
+        assert!(!handshake.is_open());
+
+        let open_ok = ConnectionOpenOk::default();
+        open_ok.set_known_hosts(ShortStr::default());
+        assert!(handshake.handle(server_frame(ConnectionMethod::OpenOk, Arguments::ConnectionOpenOk(open_ok))).unwrap().is_empty());
+        assert!(handshake.is_open());
+    }
+
+    #[test]
+    fn test_client_tuning() {
+        let mut handshake = client();
+        handshake.start();
+        handshake.handle(connection_start(b"PLAIN")).unwrap();
+        // heartbeats may arrive before TuneOk went out
+        assert!(handshake.handle(DecodedFrame::AmqpFrame(heartbeat_frame())).unwrap().is_empty());
+
+        let tune = ConnectionTune::default();
+        tune.set_channel_max(0);
+        tune.set_frame_max(1024);
+        tune.set_heartbeat(30);
+        match handshake.handle(server_frame(ConnectionMethod::Tune, Arguments::ConnectionTune(tune))) {
+            Err(HandshakeErr::InvalidTuning(tuning)) => assert_eq!(tuning.frame_max(), 1024),
+            _ => panic!("frame_max below the minimum accepted")
+        }
+        assert!(handshake.is_failed());
+    }
+
+    #[test]
+    fn test_client_mechanism_not_offered() {
+        let mut handshake = client();
+        handshake.start();
+        match handshake.handle(connection_start(b"EXTERNAL")) {
+            Err(HandshakeErr::MechanismNotOffered(mechanism)) => assert_eq!(mechanism, "PLAIN"),
+            _ => panic!("mechanism check missing")
+        }
+        assert!(handshake.is_failed());
+    }
+
+    fn server() -> ServerHandshake {
+        let mut config = ServerConfig::new(Box::new(|_, response| {
+            if response.as_bytes() == b"\x00guest\x00guest" {
+                Authentication::Accepted(String::from("guest"))
+            } else {
+                Authentication::Refused
+            }
+        }));
+        config.set_vhost_check(Box::new(|vhost, _| vhost.as_bytes() == b"/"));
+        ServerHandshake::new(config)
+    }
+
+    // run a client against the server until neither has anything to send
+    fn connect(client: &mut ClientHandshake, server: &mut ServerHandshake) -> Result<(), HandshakeErr> {
+        let mut to_server = vec![client.start()];
+        while !to_server.is_empty() {
+            let mut to_client = Vec::new();
+            for frame in to_server.drain(..) {
+                to_client.extend(server.handle(frame));
+            }
+            for frame in to_client {
+                match client.handle(frame) {
+                    Ok(frames) => to_server.extend(frames),
+                    Err(err) => {
+                        // the server waits for Close-Ok before dropping us
+                        if let Some(close_ok) = client.close_ok() {
+                            assert!(server.handle(close_ok).is_empty());
+                        }
+                        return Err(err);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sasl_handshake() {
+        let mut config = ServerConfig::new(Box::new(|mechanism, response| {
+            match sasl::parse_response(mechanism, response) {
+                Ok(credentials) if credentials.password() == Some("secret") => Authentication::Accepted(credentials.username().to_string()),
+                _ => Authentication::Refused
+            }
+        }));
+        config.set_mechanisms(sasl::mechanism_list(&[Mechanism::Plain, Mechanism::AmqPlain]));
+        let mut server = ServerHandshake::new(config);
+
+        // EXTERNAL is not offered, so the client falls back to AMQPLAIN
+        let credentials = Credentials::new(String::from("alice"), String::from("secret"));
+        let config = ClientConfig::with_credentials(ShortStr::with_bytes(b"/").unwrap(), &credentials,
+            &[Mechanism::External, Mechanism::AmqPlain, Mechanism::Plain]).unwrap();
+        connect(&mut ClientHandshake::new(config), &mut server).unwrap();
+        assert!(server.is_open());
+        assert_eq!(server.user(), Some("alice"));
+    }
+
+    #[test]
+    fn test_server_handshake() {
+        let mut handshake = server();
+        connect(&mut client(), &mut handshake).unwrap();
+        assert!(handshake.is_open());
+        assert_eq!(handshake.user(), Some("guest"));
+        assert_eq!(handshake.tuning(), Some(&Tuning::new(2047, 131072, 60)));
+    }
+
+    #[test]
+    fn test_server_refuses_vhost() {
+        let mut config = ClientConfig::new(ShortStr::with_bytes(b"/private").unwrap(),
+            Mechanism::Plain, LongStr::with_bytes(b"\x00guest\x00guest").unwrap());
+        config.set_tuning(Tuning::new(0, 0, 0));
+        let mut handshake = server();
+        let mut client = ClientHandshake::new(config);
+        match connect(&mut client, &mut handshake) {
+            Err(HandshakeErr::ConnectionClosed(reply_code, _)) => assert_eq!(reply_code, NOT_ALLOWED),
+            _ => panic!("vhost accepted")
+        }
+        assert!(client.is_closed());
+        assert!(client.close_ok().is_none());
+        assert!(handshake.is_closed());
+        match handshake.failure() {
+            Some(HandshakeErr::VhostRefused(vhost)) => assert_eq!(vhost, "/private"),
+            _ => panic!("failure not recorded")
+        }
+    }
+
+    // everything the server answers with must make it onto the wire
+    fn encode(frames: Vec<DecodedFrame>) -> Vec<BytesMut> {
+        let mut codec = FrameCodec::new();
+        frames.into_iter().map(|frame| {
+            let mut dst = BytesMut::new();
+            codec.encode(frame, &mut dst).unwrap();
+            dst
+        }).collect()
+    }
+
+    fn started() -> ServerHandshake {
+        let mut handshake = server();
+        assert_eq!(encode(handshake.handle(DecodedFrame::ProtocolHeader(protocol_header(0, 0, 9, 1)))).len(), 1);
+        handshake
+    }
+
+    #[test]
+    fn test_server_refusals() {
+        // a second protocol header and a body frame are refused naming no method
+        let body = [0x03u8, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, b'h', b'i', 0xce];
+        let (_, body) = parse_frame(&body).unwrap();
+        for frame in vec![DecodedFrame::ProtocolHeader(protocol_header(0, 0, 9, 1)), DecodedFrame::AmqpFrame(body)] {
+            let mut handshake = started();
+            let close = encode(handshake.handle(frame));
+            assert_eq!(close.len(), 1);
+            // connection.close ... class_id 0, method_id 0, frame end
+            assert_eq!(&close[0][7..11], &[0x00, 0x0a, 0x00, 0x32][..]);
+            assert_eq!(&close[0][close[0].len() - 5..], &[0x00, 0x00, 0x00, 0x00, 0xce][..]);
+            assert!(!handshake.is_closed());
+        }
+
+        // heartbeats are skipped, a method out of turn is refused naming it
+        let mut handshake = started();
+        assert!(handshake.handle(DecodedFrame::AmqpFrame(heartbeat_frame())).is_empty());
+        let open_ok = ConnectionOpenOk::default();
+        open_ok.set_known_hosts(ShortStr::default());
+        let close = encode(handshake.handle(server_frame(ConnectionMethod::OpenOk, Arguments::ConnectionOpenOk(open_ok))));
+        assert_eq!(&close[0][close[0].len() - 5..], &[0x00, 0x0a, 0x00, 0x29, 0xce][..]);
+        match handshake.failure() {
+            Some(HandshakeErr::UnexpectedMethod(10, 41)) => (),
+            _ => panic!("failure not recorded")
+        }
+
+        // while closing, nothing but the client's Close or Close-Ok is answered
+        let body = [0x03u8, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, b'h', b'i', 0xce];
+        let (_, body) = parse_frame(&body).unwrap();
+        let open_ok = ConnectionOpenOk::default();
+        open_ok.set_known_hosts(ShortStr::default());
+        let on_channel = method_frame(1, Class::Connection, Method::ConnectionMethod(ConnectionMethod::OpenOk), Arguments::ConnectionOpenOk(open_ok));
+        for frame in vec![DecodedFrame::ProtocolHeader(protocol_header(0, 0, 9, 1)), DecodedFrame::AmqpFrame(body), DecodedFrame::AmqpFrame(on_channel)] {
+            assert!(handshake.handle(frame).is_empty());
+        }
+        match handshake.failure() {
+            Some(HandshakeErr::UnexpectedMethod(10, 41)) => (),
+            _ => panic!("failure overwritten while closing")
+        }
+        // the client closed at the same time, its Close gets Close-Ok
+        let close = ConnectionClose::default();
+        close.set_reply_code(200);
+        close.set_reply_text(ShortStr::with_bytes(b"bye").unwrap());
+        let (class, method) = no_method();
+        close.set_class(class);
+        close.set_method(method);
+        let close_ok = encode(handshake.handle(server_frame(ConnectionMethod::Close, Arguments::ConnectionClose(close))));
+        assert_eq!(&close_ok[0][7..11], &[0x00, 0x0a, 0x00, 0x33][..]);
+        assert!(handshake.is_closed());
+        assert!(handshake.handle(DecodedFrame::ProtocolHeader(protocol_header(0, 0, 9, 1))).is_empty());
+
+        // a refused login and the client closing first
+        let mut config = ClientConfig::new(ShortStr::with_bytes(b"/").unwrap(),
+            Mechanism::Plain, LongStr::with_bytes(b"\x00guest\x00wrong").unwrap());
+        config.set_tuning(Tuning::new(0, 0, 0));
+        let mut client = ClientHandshake::new(config);
+        client.start();
+        let mut handshake = started();
+        for frame in client.handle(connection_start(b"PLAIN")).unwrap() {
+            assert_eq!(encode(handshake.handle(frame)).len(), 1);
+        }
+        match handshake.failure() {
+            Some(HandshakeErr::AuthenticationFailed(_)) => (),
+            _ => panic!("login accepted")
+        }
+
+        let mut handshake = started();
+        let close = ConnectionClose::default();
+        close.set_reply_code(200);
+        close.set_reply_text(ShortStr::with_bytes(b"bye").unwrap());
+        let (class, method) = no_method();
+        close.set_class(class);
+        close.set_method(method);
+        assert_eq!(encode(handshake.handle(server_frame(ConnectionMethod::Close, Arguments::ConnectionClose(close)))).len(), 1);
+        assert!(handshake.is_closed());
+    }
+
+    #[test]
+    fn test_server_version_mismatch() {
+        let mut handshake = server();
+        let reply = handshake.handle(DecodedFrame::ProtocolHeader(protocol_header(1, 1, 0, 9)));
+        match reply.first() {
+            Some(DecodedFrame::ProtocolHeader(header)) => assert_eq!((header.major_version(), header.minor_version()), (9, 1)),
+            _ => panic!("protocol header expected")
+        }
+        assert_eq!(encode(reply).len(), 1);
+        assert!(handshake.is_closed());
+    }
+}
@@ -0,0 +1,155 @@
+use std::cell::Cell;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+use crate::handshake::Tuning;
+
+pub trait Clock {
+    fn now(&self) -> Instant;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+// a clock that only moves when told to, clones share the same time
+#[derive(Clone)]
+pub struct ManualClock {
+    now: Rc<Cell<Instant>>
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        ManualClock {
+            now: Rc::new(Cell::new(Instant::now()))
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.now.set(self.now.get() + duration);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HeartbeatStatus {
+    Idle,
+    // nothing was sent for a whole interval, send a heartbeat frame
+    SendHeartbeat,
+    // nothing arrived for two intervals, close the connection
+    PeerDead
+}
+
+// Tracks traffic against the negotiated heartbeat interval. It does no IO:
+// report frames with frame_sent and frame_received, and call poll by
+// next_deadline at the latest.
+pub struct HeartbeatMonitor<C: Clock = SystemClock> {
+    clock: C,
+    // None when heartbeats are disabled
+    interval: Option<Duration>,
+    last_sent: Instant,
+    last_received: Instant
+}
+
+impl<C: Clock> HeartbeatMonitor<C> {
+    // heartbeat in seconds as agreed in Connection.Tune/TuneOk, 0 disables it
+    pub fn new(heartbeat: u16, clock: C) -> Self {
+        let now = clock.now();
+        HeartbeatMonitor {
+            clock,
+            interval: if heartbeat == 0 { None } else { Some(Duration::from_secs(heartbeat as u64)) },
+            last_sent: now,
+            last_received: now
+        }
+    }
+
+    pub fn with_tuning(tuning: &Tuning, clock: C) -> Self {
+        HeartbeatMonitor::new(tuning.heartbeat(), clock)
+    }
+
+    pub fn interval(&self) -> Option<Duration> {
+        self.interval
+    }
+
+    // any frame counts, not only heartbeats
+    pub fn frame_received(&mut self) {
+        self.last_received = self.clock.now();
+    }
+
+    pub fn frame_sent(&mut self) {
+        self.last_sent = self.clock.now();
+    }
+
+    pub fn poll(&self) -> HeartbeatStatus {
+        let interval = match self.interval {
+            Some(interval) => interval,
+            None => return HeartbeatStatus::Idle
+        };
+        let now = self.clock.now();
+        if now.duration_since(self.last_received) >= interval * 2 {
+            HeartbeatStatus::PeerDead
+        } else if now.duration_since(self.last_sent) >= interval {
+            HeartbeatStatus::SendHeartbeat
+        } else {
+            HeartbeatStatus::Idle
+        }
+    }
+
+    // when poll can next change its answer
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let interval = self.interval?;
+        Some((self.last_sent + interval).min(self.last_received + interval * 2))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::heartbeat::{HeartbeatMonitor, HeartbeatStatus, ManualClock, Clock};
+    use std::time::Duration;
+
+    #[test]
+    fn test_heartbeat_monitor() {
+        let clock = ManualClock::new();
+        let mut monitor = HeartbeatMonitor::new(10, clock.clone());
+        assert_eq!(monitor.poll(), HeartbeatStatus::Idle);
+
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(monitor.poll(), HeartbeatStatus::SendHeartbeat);
+        monitor.frame_sent();
+        assert_eq!(monitor.poll(), HeartbeatStatus::Idle);
+
+        // traffic from the peer keeps it alive
+        clock.advance(Duration::from_secs(9));
+        monitor.frame_received();
+        clock.advance(Duration::from_secs(19));
+        monitor.frame_sent();
+        assert_eq!(monitor.poll(), HeartbeatStatus::Idle);
+        assert_eq!(monitor.next_deadline(), Some(clock.now() + Duration::from_secs(1)));
+
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(monitor.poll(), HeartbeatStatus::PeerDead);
+    }
+
+    #[test]
+    fn test_heartbeat_disabled() {
+        let clock = ManualClock::new();
+        let monitor = HeartbeatMonitor::new(0, clock.clone());
+        clock.advance(Duration::from_secs(3600));
+        assert_eq!(monitor.poll(), HeartbeatStatus::Idle);
+        assert_eq!(monitor.next_deadline(), None);
+    }
+}
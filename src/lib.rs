@@ -7,13 +7,30 @@ mod access;
 mod channel;
 mod connection;
 mod common;
-mod frame_codec;
+pub mod frame_codec;
 pub mod error;
 pub mod parse;
+pub mod encode;
+pub mod message;
+pub mod handshake;
+pub mod shared;
+pub mod spec;
+pub mod frame_ref;
+pub mod heartbeat;
+pub mod x_arguments;
+pub mod peer_properties;
+pub mod sasl;
+pub mod capture;
+pub mod pcap;
 
 #[cfg(test)]
 mod tests {
-    use crate::parse::parse_amqp_protocal_header;
+    use crate::parse::{parse_amqp_protocal_header, parse_frame};
+    use crate::error::{FrameDecodeErr, FRAME_ERROR};
+    use crate::encode::{write_amqp_protocol_header, write_frame, method_frame, heartbeat_frame, no_method};
+    use amqp_types::frame::{Payload, Arguments, Class, Method, BasicMethod, BasicPublish, ConnectionMethod, ConnectionClose, BasicProperties, Property, ContentHeaderPayload, ContentBodyPayload};
+    use amqp_types::{Frame, FrameType, ShortStr};
+    use bytes::{Bytes, BytesMut};
 
     #[test]
     fn test_protocol_header() {
@@ -21,4 +38,184 @@ mod tests {
         let protocol_header = parse_amqp_protocal_header(&header).unwrap();
         assert_eq!(protocol_header.major_version(), 0x9u8);
     }
+
+    #[test]
+    fn test_encode_protocol_header() {
+        let header = [0x41u8, 0x4d, 0x51, 0x50, 0x00, 0x00, 0x09, 0x01];
+        let protocol_header = parse_amqp_protocal_header(&header).unwrap();
+        let mut dst = BytesMut::new();
+        write_amqp_protocol_header(&protocol_header, &mut dst);
+        assert_eq!(&dst[..], &header[..]);
+    }
+
+    #[test]
+    fn test_encode_method_frame() {
+        // basic.publish on channel 5, exchange "ex", routing key "rk", mandatory
+        let publish = BasicPublish::default();
+        publish.set_ticket(0);
+        publish.set_exchange_name(ShortStr::with_bytes(b"ex").unwrap());
+        publish.set_routing_key(ShortStr::with_bytes(b"rk").unwrap());
+        publish.set_mandatory(true);
+        publish.set_immediate(false);
+        let frame = method_frame(5, Class::Basic, Method::BasicMethod(BasicMethod::Publish), Arguments::BasicPublish(publish));
+        let mut dst = BytesMut::new();
+        write_frame(&frame, &mut dst).unwrap();
+        assert_eq!(&dst[..], &[0x01u8, 0x00, 0x05, 0x00, 0x00, 0x00, 0x0d,
+            0x00, 0x3c, 0x00, 0x28, 0x00, 0x00, 0x02, b'e', b'x', 0x02, b'r', b'k', 0x01, 0xce][..]);
+    }
+
+    #[test]
+    fn test_encode_content_frames() {
+        // content header with content_type "text/plain" and delivery_mode 2
+        let properties = BasicProperties::default();
+        properties.set_content_type(ShortStr::with_bytes(b"text/plain").unwrap());
+        properties.set_delivery_mode(2);
+        let header = ContentHeaderPayload::default();
+        header.set_class(Class::Basic);
+        header.set_weight(0);
+        header.set_body_size(5);
+        header.set_properties(Property::Basic(properties));
+        let frame = Frame::default();
+        frame.set_frame_type(FrameType::HEADER);
+        frame.set_channel(1);
+        frame.set_payload(Payload::ContentHeader(header));
+        let mut dst = BytesMut::new();
+        write_frame(&frame, &mut dst).unwrap();
+        let mut expected = vec![0x02u8, 0x00, 0x01, 0x00, 0x00, 0x00, 0x1a,
+            0x00, 0x3c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x90, 0x00, 0x0a];
+        expected.extend_from_slice(b"text/plain");
+        expected.extend_from_slice(&[0x02, 0xce]);
+        assert_eq!(&dst[..], &expected[..]);
+
+        let body = ContentBodyPayload::default();
+        body.set_payload(Bytes::from_static(b"hello"));
+        let frame = Frame::default();
+        frame.set_frame_type(FrameType::BODY);
+        frame.set_channel(1);
+        frame.set_payload(Payload::ContentBody(body));
+        let mut dst = BytesMut::new();
+        write_frame(&frame, &mut dst).unwrap();
+        assert_eq!(&dst[..], &b"\x03\x00\x01\x00\x00\x00\x05hello\xce"[..]);
+
+        let mut dst = BytesMut::new();
+        write_frame(&heartbeat_frame(), &mut dst).unwrap();
+        assert_eq!(&dst[..], &[0x08u8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xce][..]);
+    }
+
+    #[test]
+    fn test_frame_end() {
+        let heartbeat = [0x08u8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xce];
+        let (frame_length, _) = parse_frame(&heartbeat).unwrap();
+        assert_eq!(frame_length, 8);
+
+        let heartbeat = [0x08u8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x41];
+        match parse_frame(&heartbeat) {
+            Err(e @ FrameDecodeErr::InvalidFrameEnd(0x41)) => assert_eq!(e.reply_code(), Some(FRAME_ERROR)),
+            _ => panic!("frame end not checked")
+        }
+    }
+
+    #[test]
+    fn test_syntax_error_context() {
+        // queue.purge-ok on channel 1 with a truncated message_count
+        let frame = [0x01u8, 0x00, 0x01, 0x00, 0x00, 0x00, 0x06, 0x00, 0x32, 0x00, 0x1f, 0x00, 0x01, 0xce];
+        match parse_frame(&frame) {
+            Err(FrameDecodeErr::Syntax(err)) => {
+                assert_eq!(err.channel(), Some(1));
+                assert_eq!(err.class_id(), Some(50));
+                assert_eq!(err.method_id(), Some(31));
+                assert_eq!(err.field(), Some("message_count"));
+                assert_eq!(err.offset(), Some(11));
+                assert_eq!(err.to_string(), "syntax error on channel 1 in queue.purge-ok (50.31), field message_count at offset 11: unexpected end of payload");
+            }
+            _ => panic!("syntax error expected")
+        }
+    }
+
+    #[test]
+    fn test_unknown_method_context() {
+        // class 70 does not exist
+        let frame = [0x01u8, 0x00, 0x03, 0x00, 0x00, 0x00, 0x04, 0x00, 0x46, 0x00, 0x0a, 0xce];
+        match parse_frame(&frame) {
+            Err(e @ FrameDecodeErr::UnknownClassType(3, 70)) => assert_eq!(e.to_string(), "unknown class 70 on channel 3"),
+            _ => panic!("unknown class expected")
+        }
+        // basic method 99 does not exist
+        let frame = [0x01u8, 0x00, 0x03, 0x00, 0x00, 0x00, 0x04, 0x00, 0x3c, 0x00, 0x63, 0xce];
+        match parse_frame(&frame) {
+            Err(e @ FrameDecodeErr::UnknownMethodType(3, 60, 99)) => assert_eq!(e.to_string(), "unknown method 60.99 on channel 3"),
+            _ => panic!("unknown method expected")
+        }
+        // content header for the queue class, which has no content
+        let frame = [0x02u8, 0x00, 0x07, 0x00, 0x00, 0x00, 0x0e,
+            0x00, 0x32, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xce];
+        match parse_frame(&frame) {
+            Err(FrameDecodeErr::UnknownClassType(7, 50)) => (),
+            _ => panic!("content class not checked")
+        }
+    }
+
+    #[test]
+    fn test_access_request_flags() {
+        // access.request for realm "/data" with passive, write and read set
+        let frame = [0x01u8, 0x00, 0x01, 0x00, 0x00, 0x00, 0x0b,
+            0x00, 0x1e, 0x00, 0x0a, 0x05, b'/', b'd', b'a', b't', b'a', 0x1a, 0xce];
+        let (_, decoded) = parse_frame(&frame).unwrap();
+        match decoded.payload() {
+            Payload::Method(method) => match method.args() {
+                Arguments::AccessRequest(args) => {
+                    assert_eq!((args.exclusive(), args.passive(), args.active(), args.write(), args.read()), (false, true, false, true, true));
+                }
+                _ => panic!("access.request expected")
+            },
+            _ => panic!("method frame expected")
+        }
+        let mut dst = BytesMut::new();
+        write_frame(&decoded, &mut dst).unwrap();
+        assert_eq!(&dst[..], &frame[..]);
+    }
+
+    #[test]
+    fn test_close_without_method() {
+        // connection.close 320 "bye" and channel.close on channel 1, both 0/0
+        let connection_close = [0x01u8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x0e,
+            0x00, 0x0a, 0x00, 0x32, 0x01, 0x40, 0x03, b'b', b'y', b'e', 0x00, 0x00, 0x00, 0x00, 0xce];
+        let channel_close = [0x01u8, 0x00, 0x01, 0x00, 0x00, 0x00, 0x0e,
+            0x00, 0x14, 0x00, 0x28, 0x01, 0x40, 0x03, b'b', b'y', b'e', 0x00, 0x00, 0x00, 0x00, 0xce];
+        for frame in [&connection_close, &channel_close].iter() {
+            let (_, decoded) = parse_frame(&frame[..]).unwrap();
+            let mut dst = BytesMut::new();
+            write_frame(&decoded, &mut dst).unwrap();
+            assert_eq!(&dst[..], &frame[..]);
+        }
+
+        let close = ConnectionClose::default();
+        close.set_reply_code(320);
+        close.set_reply_text(ShortStr::with_bytes(b"bye").unwrap());
+        let (class, method) = no_method();
+        close.set_class(class);
+        close.set_method(method);
+        let frame = method_frame(0, Class::Connection, Method::ConnectionMethod(ConnectionMethod::Close), Arguments::ConnectionClose(close));
+        let mut dst = BytesMut::new();
+        write_frame(&frame, &mut dst).unwrap();
+        assert_eq!(&dst[..], &connection_close[..]);
+    }
+
+    #[test]
+    fn test_connection_blocked() {
+        let mut frame = vec![0x01u8, 0x00, 0x00, 0x00, 0x00, 0x00, 0x12, 0x00, 0x0a, 0x00, 0x3c, 0x0d];
+        frame.extend_from_slice(b"low on memory");
+        frame.push(0xce);
+        let (_, decoded) = parse_frame(&frame).unwrap();
+        match decoded.payload() {
+            Payload::Method(method) => match method.args() {
+                Arguments::ConnectionBlocked(args) => assert_eq!(args.reason().as_bytes(), &b"low on memory"[..]),
+                _ => panic!("connection.blocked expected")
+            },
+            _ => panic!("method frame expected")
+        }
+        let mut dst = BytesMut::new();
+        write_frame(&decoded, &mut dst).unwrap();
+        assert_eq!(&dst[..], &frame[..]);
+    }
 }
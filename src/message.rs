@@ -0,0 +1,531 @@
+use std::collections::HashMap;
+use tokio_util::codec::{Decoder, Encoder};
+use amqp_types::frame::{Arguments, BasicProperties, Payload, Property, ProtocolHeader};
+use amqp_types::Frame;
+use bytes::{Bytes, BytesMut};
+use crate::error::{FrameDecodeErr, FrameEncodeErr};
+use crate::frame_codec::{FrameCodec, DecodedFrame};
+
+// A published or delivered message: the Basic method that announced it,
+// the content header properties and the whole body.
+pub struct Message {
+    channel: u16,
+    method: Arguments,
+    properties: BasicProperties,
+    body: Bytes
+}
+
+impl Message {
+    pub fn channel(&self) -> u16 {
+        self.channel
+    }
+
+    // one of BasicPublish, BasicDeliver, BasicReturn or BasicGetOk
+    pub fn method(&self) -> &Arguments {
+        &self.method
+    }
+
+    pub fn properties(&self) -> &BasicProperties {
+        &self.properties
+    }
+
+    pub fn body(&self) -> &Bytes {
+        &self.body
+    }
+}
+
+pub enum AssembledFrame {
+    ProtocolHeader(ProtocolHeader),
+    // any frame that is not part of a message
+    AmqpFrame(Frame),
+    Message(Message)
+}
+
+// content in flight on one channel
+struct PendingMessage {
+    method: Arguments,
+    header: Option<(BasicProperties, u64)>,
+    chunks: Vec<Bytes>,
+    received: u64
+}
+
+impl PendingMessage {
+    fn into_message(self, channel: u16) -> Message {
+        let (properties, body_size) = self.header.unwrap_or_default();
+        // a single body frame is handed over as is, several are joined
+        let body = if self.chunks.len() == 1 {
+            self.chunks.into_iter().next().unwrap()
+        } else {
+            let mut body = BytesMut::with_capacity(body_size as usize);
+            for chunk in self.chunks.iter() {
+                body.extend_from_slice(chunk);
+            }
+            body.freeze()
+        };
+        Message {
+            channel,
+            method: self.method,
+            properties,
+            body
+        }
+    }
+}
+
+fn carries_content(args: &Arguments) -> bool {
+    match args {
+        Arguments::BasicPublish(_) | Arguments::BasicDeliver(_) | Arguments::BasicReturn(_) | Arguments::BasicGetOk(_) => true,
+        _ => false
+    }
+}
+
+// Joins method, content header and body frames into messages, tracking
+// each channel separately so content on different channels can interleave.
+#[derive(Default)]
+pub struct MessageAssembler {
+    pending: HashMap<u16, PendingMessage>,
+    // method frame owed after a method cut content short
+    queued: Option<AssembledFrame>
+}
+
+impl MessageAssembler {
+    pub fn new() -> Self {
+        MessageAssembler {
+            pending: HashMap::new(),
+            queued: None
+        }
+    }
+
+    pub fn push(&mut self, frame: Frame) -> Result<Option<AssembledFrame>, FrameDecodeErr> {
+        let channel = frame.channel();
+        match frame.payload() {
+            Payload::Method(method) => {
+                let cut_short = self.pending.remove(&channel).is_some();
+                let item = if carries_content(method.args()) {
+                    self.pending.insert(channel, PendingMessage {
+                        method: method.args().clone(),
+                        header: None,
+                        chunks: Vec::new(),
+                        received: 0
+                    });
+                    None
+                } else {
+                    Some(AssembledFrame::AmqpFrame(frame))
+                };
+                // the content is lost, the method itself still counts and a
+                // frame it produces is waiting in take_queued
+                if cut_short {
+                    self.queued = item;
+                    return Err(FrameDecodeErr::UnexpectedFrame(channel));
+                }
+                Ok(item)
+            }
+            Payload::ContentHeader(header) => {
+                let pending = match self.pending.get_mut(&channel) {
+                    Some(pending) if pending.header.is_none() => pending,
+                    _ => return Err(FrameDecodeErr::UnexpectedFrame(channel))
+                };
+                let properties = match header.properties() {
+                    Property::Basic(properties) => properties.clone(),
+                    _ => return Err(FrameDecodeErr::UnexpectedFrame(channel))
+                };
+                pending.header = Some((properties, header.body_size()));
+                self.complete(channel)
+            }
+            Payload::ContentBody(body) => {
+                let pending = match self.pending.get_mut(&channel) {
+                    Some(pending) if pending.header.is_some() => pending,
+                    _ => return Err(FrameDecodeErr::UnexpectedFrame(channel))
+                };
+                pending.received += body.payload().len() as u64;
+                pending.chunks.push(body.payload().clone());
+                self.complete(channel)
+            }
+            Payload::Heartbeat => Ok(Some(AssembledFrame::AmqpFrame(frame)))
+        }
+    }
+
+    // hand out the message on `channel` once its body is all there
+    fn complete(&mut self, channel: u16) -> Result<Option<AssembledFrame>, FrameDecodeErr> {
+        let (received, body_size) = match self.pending.get(&channel) {
+            Some(PendingMessage { header: Some((_, body_size)), received, .. }) => (*received, *body_size),
+            _ => return Ok(None)
+        };
+        if received > body_size {
+            self.pending.remove(&channel);
+            return Err(FrameDecodeErr::BodySizeMismatch(channel, body_size, received));
+        }
+        if received < body_size {
+            return Ok(None);
+        }
+        let pending = self.pending.remove(&channel).unwrap();
+        Ok(Some(AssembledFrame::Message(pending.into_message(channel))))
+    }
+
+    // drop content in flight, e.g. after Channel.Close
+    pub fn reset_channel(&mut self, channel: u16) {
+        self.pending.remove(&channel);
+    }
+
+    // the method frame behind an UnexpectedFrame error from push, if it was
+    // not the start of another message
+    pub fn take_queued(&mut self) -> Option<AssembledFrame> {
+        self.queued.take()
+    }
+}
+
+// FrameCodec with a MessageAssembler on top
+pub struct MessageCodec {
+    codec: FrameCodec,
+    assembler: MessageAssembler
+}
+
+impl MessageCodec {
+    pub fn new(codec: FrameCodec) -> Self {
+        MessageCodec {
+            codec,
+            assembler: MessageAssembler::new()
+        }
+    }
+
+    pub fn codec(&self) -> &FrameCodec {
+        &self.codec
+    }
+
+    pub fn codec_mut(&mut self) -> &mut FrameCodec {
+        &mut self.codec
+    }
+
+    pub fn assembler_mut(&mut self) -> &mut MessageAssembler {
+        &mut self.assembler
+    }
+}
+
+impl Decoder for MessageCodec {
+    type Item = AssembledFrame;
+    type Error = FrameDecodeErr;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if let Some(item) = self.assembler.take_queued() {
+            return Ok(Some(item));
+        }
+        loop {
+            let frame = match self.codec.decode(src)? {
+                Some(DecodedFrame::ProtocolHeader(header)) => return Ok(Some(AssembledFrame::ProtocolHeader(header))),
+                Some(DecodedFrame::AmqpFrame(frame)) => frame,
+                None => return Ok(None)
+            };
+            if let Some(item) = self.assembler.push(frame)? {
+                return Ok(Some(item));
+            }
+        }
+    }
+}
+
+impl Encoder<DecodedFrame> for MessageCodec {
+    type Error = FrameEncodeErr;
+
+    fn encode(&mut self, item: DecodedFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.codec.encode(item, dst)
+    }
+}
+
+// what StreamingCodec hands out instead of whole messages
+pub enum StreamedFrame {
+    ProtocolHeader(ProtocolHeader),
+    // methods, including the one announcing the content, and heartbeats
+    AmqpFrame(Frame),
+    ContentStart { channel: u16, properties: BasicProperties, body_size: u64 },
+    ContentChunk { channel: u16, chunk: Bytes },
+    ContentEnd { channel: u16 }
+}
+
+enum ContentState {
+    AwaitHeader,
+    Body { body_size: u64, received: u64 }
+}
+
+// FrameCodec that passes content through in pieces as it arrives, so a body
+// never has to be held in memory as a whole
+pub struct StreamingCodec {
+    codec: FrameCodec,
+    content: HashMap<u16, ContentState>,
+    // ContentEnd owed after the last chunk or an empty body, or the method
+    // frame owed after it cut content short
+    queued: Option<StreamedFrame>
+}
+
+impl StreamingCodec {
+    pub fn new(codec: FrameCodec) -> Self {
+        StreamingCodec {
+            codec,
+            content: HashMap::new(),
+            queued: None
+        }
+    }
+
+    pub fn codec(&self) -> &FrameCodec {
+        &self.codec
+    }
+
+    pub fn codec_mut(&mut self) -> &mut FrameCodec {
+        &mut self.codec
+    }
+
+    // drop content in flight, e.g. after Channel.Close
+    pub fn reset_channel(&mut self, channel: u16) {
+        self.content.remove(&channel);
+    }
+
+    fn push(&mut self, frame: Frame) -> Result<StreamedFrame, FrameDecodeErr> {
+        let channel = frame.channel();
+        match frame.payload() {
+            Payload::Method(method) => {
+                let cut_short = self.content.remove(&channel).is_some();
+                if carries_content(method.args()) {
+                    self.content.insert(channel, ContentState::AwaitHeader);
+                }
+                // the content is lost, the method still comes out next
+                if cut_short {
+                    self.queued = Some(StreamedFrame::AmqpFrame(frame));
+                    return Err(FrameDecodeErr::UnexpectedFrame(channel));
+                }
+                Ok(StreamedFrame::AmqpFrame(frame))
+            }
+            Payload::ContentHeader(header) => {
+                let properties = match (self.content.get(&channel), header.properties()) {
+                    (Some(ContentState::AwaitHeader), Property::Basic(properties)) => properties.clone(),
+                    _ => return Err(FrameDecodeErr::UnexpectedFrame(channel))
+                };
+                let body_size = header.body_size();
+                if body_size == 0 {
+                    self.content.remove(&channel);
+                    self.queued = Some(StreamedFrame::ContentEnd { channel });
+                } else {
+                    self.content.insert(channel, ContentState::Body { body_size, received: 0 });
+                }
+                Ok(StreamedFrame::ContentStart { channel, properties, body_size })
+            }
+            Payload::ContentBody(body) => {
+                let (body_size, received) = match self.content.get_mut(&channel) {
+                    Some(ContentState::Body { body_size, received }) => {
+                        *received += body.payload().len() as u64;
+                        (*body_size, *received)
+                    }
+                    _ => return Err(FrameDecodeErr::UnexpectedFrame(channel))
+                };
+                if received > body_size {
+                    self.content.remove(&channel);
+                    return Err(FrameDecodeErr::BodySizeMismatch(channel, body_size, received));
+                }
+                if received == body_size {
+                    self.content.remove(&channel);
+                    self.queued = Some(StreamedFrame::ContentEnd { channel });
+                }
+                Ok(StreamedFrame::ContentChunk { channel, chunk: body.payload().clone() })
+            }
+            Payload::Heartbeat => Ok(StreamedFrame::AmqpFrame(frame))
+        }
+    }
+}
+
+impl Decoder for StreamingCodec {
+    type Item = StreamedFrame;
+    type Error = FrameDecodeErr;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if let Some(item) = self.queued.take() {
+            return Ok(Some(item));
+        }
+        match self.codec.decode(src)? {
+            Some(DecodedFrame::ProtocolHeader(header)) => Ok(Some(StreamedFrame::ProtocolHeader(header))),
+            Some(DecodedFrame::AmqpFrame(frame)) => self.push(frame).map(Some),
+            None => Ok(None)
+        }
+    }
+}
+
+impl Encoder<DecodedFrame> for StreamingCodec {
+    type Error = FrameEncodeErr;
+
+    fn encode(&mut self, item: DecodedFrame, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.codec.encode(item, dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::message::{MessageCodec, AssembledFrame, StreamingCodec, StreamedFrame};
+    use crate::frame_codec::FrameCodec;
+    use crate::error::FrameDecodeErr;
+    use amqp_types::frame::{Arguments, Payload};
+    use tokio_util::codec::Decoder;
+    use bytes::BytesMut;
+
+    // protocol header, basic.deliver on channel 1, "hello" in two body frames
+    fn delivery() -> BytesMut {
+        let mut src = BytesMut::from(&b"AMQP\x00\x00\x09\x01"[..]);
+        src.extend_from_slice(&[0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x12,
+            0x00, 0x3c, 0x00, 0x3c, 0x01, b'c', 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x01, b'q', 0xce]);
+        // content header, body_size 5, no properties
+        src.extend_from_slice(&[0x02, 0x00, 0x01, 0x00, 0x00, 0x00, 0x0e,
+            0x00, 0x3c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0xce]);
+        src.extend_from_slice(&[0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, b'h', b'e', 0xce]);
+        src.extend_from_slice(&[0x03, 0x00, 0x01, 0x00, 0x00, 0x00, 0x03, b'l', b'l', b'o', 0xce]);
+        src
+    }
+
+    fn deliver(channel: u8) -> Vec<u8> {
+        vec![0x01, 0x00, channel, 0x00, 0x00, 0x00, 0x12,
+            0x00, 0x3c, 0x00, 0x3c, 0x01, b'c', 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x01, b'q', 0xce]
+    }
+
+    // channel.close 200 with an empty reply text
+    fn channel_close(channel: u8) -> Vec<u8> {
+        vec![0x01, 0x00, channel, 0x00, 0x00, 0x00, 0x0b,
+            0x00, 0x14, 0x00, 0x28, 0x00, 0xc8, 0x00, 0x00, 0x00, 0x00, 0x00, 0xce]
+    }
+
+    fn content_header(channel: u8, body_size: u8) -> Vec<u8> {
+        vec![0x02, 0x00, channel, 0x00, 0x00, 0x00, 0x0e,
+            0x00, 0x3c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, body_size, 0x00, 0x00, 0xce]
+    }
+
+    fn content_body(channel: u8, data: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0x03, 0x00, channel, 0x00, 0x00, 0x00, data.len() as u8];
+        frame.extend_from_slice(data);
+        frame.push(0xce);
+        frame
+    }
+
+    // decode everything after the protocol header, messages as (channel, body)
+    fn assemble(frames: &[Vec<u8>]) -> Vec<Result<(u16, Vec<u8>), FrameDecodeErr>> {
+        let mut src = BytesMut::from(&b"AMQP\x00\x00\x09\x01"[..]);
+        for frame in frames {
+            src.extend_from_slice(frame);
+        }
+        let mut codec = MessageCodec::new(FrameCodec::new());
+        let mut results = Vec::new();
+        while !src.is_empty() {
+            match codec.decode(&mut src) {
+                Ok(Some(AssembledFrame::Message(message))) => results.push(Ok((message.channel(), message.body().to_vec()))),
+                Ok(Some(_)) => {},
+                Ok(None) => break,
+                Err(e) => results.push(Err(e))
+            }
+        }
+        results
+    }
+
+    #[test]
+    fn test_assemble_message() {
+        let mut src = delivery();
+        let mut codec = MessageCodec::new(FrameCodec::new());
+        match codec.decode(&mut src) {
+            Ok(Some(AssembledFrame::ProtocolHeader(_))) => {},
+            _ => panic!("protocol header expected")
+        }
+        match codec.decode(&mut src) {
+            Ok(Some(AssembledFrame::Message(message))) => {
+                assert_eq!(message.channel(), 1);
+                assert_eq!(&message.body()[..], b"hello");
+            }
+            _ => panic!("message expected")
+        }
+        assert!(src.is_empty());
+    }
+
+    #[test]
+    fn test_stream_content() {
+        let mut src = delivery();
+        let mut codec = StreamingCodec::new(FrameCodec::new());
+        let mut body = Vec::new();
+        let mut events = Vec::new();
+        while let Some(item) = codec.decode(&mut src).unwrap() {
+            match item {
+                StreamedFrame::ProtocolHeader(_) => events.push("header"),
+                StreamedFrame::AmqpFrame(_) => events.push("method"),
+                StreamedFrame::ContentStart { body_size, .. } => {
+                    assert_eq!(body_size, 5);
+                    events.push("start");
+                }
+                StreamedFrame::ContentChunk { chunk, .. } => {
+                    body.extend_from_slice(&chunk);
+                    events.push("chunk");
+                }
+                StreamedFrame::ContentEnd { channel } => {
+                    assert_eq!(channel, 1);
+                    events.push("end");
+                }
+            }
+        }
+        assert_eq!(events, ["header", "method", "start", "chunk", "chunk", "end"]);
+        assert_eq!(&body[..], b"hello");
+    }
+
+    #[test]
+    fn test_body_size_mismatch() {
+        let results = assemble(&[deliver(1), content_header(1, 3), content_body(1, b"hello"),
+            deliver(1), content_header(1, 2), content_body(1, b"ok")]);
+        match &results[0] {
+            Err(FrameDecodeErr::BodySizeMismatch(1, 3, 5)) => {},
+            _ => panic!("body size not checked")
+        }
+        assert_eq!(results[1].as_ref().unwrap(), &(1, b"ok".to_vec()));
+        assert_eq!(results.len(), 2);
+    }
+
+    #[test]
+    fn test_interleaved_channels() {
+        let results = assemble(&[deliver(1), deliver(2), content_header(2, 5), content_header(1, 5),
+            content_body(1, b"he"), content_body(2, b"world"), content_body(1, b"llo")]);
+        let messages: Vec<(u16, Vec<u8>)> = results.into_iter().map(|result| result.unwrap()).collect();
+        assert_eq!(messages, [(2, b"world".to_vec()), (1, b"hello".to_vec())]);
+    }
+
+    #[test]
+    fn test_method_resets_content() {
+        // a new delivery cuts the first one short and starts the next message
+        let results = assemble(&[deliver(1), content_header(1, 5), content_body(1, b"he"),
+            deliver(1), content_header(1, 5), content_body(1, b"hello")]);
+        match &results[0] {
+            Err(FrameDecodeErr::UnexpectedFrame(1)) => {},
+            _ => panic!("method inside content accepted")
+        }
+        assert_eq!(results[1].as_ref().unwrap(), &(1, b"hello".to_vec()));
+        assert_eq!(results.len(), 2);
+
+        // a method without content still comes out after the error
+        let mut src = BytesMut::from(&b"AMQP\x00\x00\x09\x01"[..]);
+        for frame in &[deliver(1), content_header(1, 5), content_body(1, b"he"), channel_close(1)] {
+            src.extend_from_slice(frame);
+        }
+        let mut streamed = src.clone();
+        let mut codec = MessageCodec::new(FrameCodec::new());
+        assert!(matches!(codec.decode(&mut src), Ok(Some(AssembledFrame::ProtocolHeader(_)))));
+        assert!(matches!(codec.decode(&mut src), Err(FrameDecodeErr::UnexpectedFrame(1))));
+        match codec.decode(&mut src) {
+            Ok(Some(AssembledFrame::AmqpFrame(frame))) => match frame.payload() {
+                Payload::Method(method) => {
+                    assert_eq!(frame.channel(), 1);
+                    assert!(matches!(method.args(), Arguments::ChannelClose(_)));
+                }
+                _ => panic!("method frame expected")
+            },
+            _ => panic!("channel.close lost")
+        }
+        assert!(src.is_empty());
+
+        let mut codec = StreamingCodec::new(FrameCodec::new());
+        let mut events = Vec::new();
+        loop {
+            match codec.decode(&mut streamed) {
+                Ok(Some(StreamedFrame::AmqpFrame(_))) => events.push("method"),
+                Ok(Some(StreamedFrame::ContentChunk { .. })) => events.push("chunk"),
+                Ok(Some(_)) => {},
+                Ok(None) => break,
+                Err(_) => events.push("error")
+            }
+        }
+        assert_eq!(events, ["method", "chunk", "error", "method"]);
+    }
+}
@@ -1,20 +1,15 @@
 use std::result::Result;
-use amqp_types::frame::{ProtocolHeader, Arguments, Class, Method, ConnectionMethod, ChannelMethod, AccessMethod, ExchangeMethod, QueueMethod, BasicMethod, TxMethod, ConfirmMethod, MethodPayload, Payload};
+use amqp_types::frame::{ProtocolHeader, Class, MethodPayload, ContentHeaderPayload, ContentBodyPayload, Payload};
 use amqp_types::{Frame, FrameType};
 use nom::number::complete::{be_u16, be_u8, be_u64};
 use nom::bytes::streaming::{tag, take};
 use crate::error::FrameDecodeErr;
+use crate::encode::protocol_header;
 use nom::error::ErrorKind;
 use crate::common::{get_method_type, parse_channel_id_and_length};
-use crate::connection::{parse_connection_start, parse_connection_start_ok, parse_connection_tune, parse_connection_tune_ok, parse_connection_secure, parse_connection_secure_ok, parse_connection_open, parse_connection_open_ok, parse_connection_close, parse_connection_close_ok};
-use crate::channel::{parse_channel_open, parse_channel_open_ok, parse_channel_flow, parse_channel_flow_ok, parse_channel_close, parse_channel_close_ok};
-use crate::access::{parse_access_request, parse_access_request_ok};
-use crate::exchange::{parse_exchange_declare, parse_exchange_declare_ok, parse_exchange_bind, parse_exchange_bind_ok, parse_exchange_unbind, parse_exchange_unbind_ok, parse_exchange_delete, parse_exchange_delete_ok};
-use crate::queue::{parse_queue_delete, parse_queue_declare, parse_queue_declare_ok, parse_queue_bind, parse_queue_bind_ok, parse_queue_unbind, parse_queue_unbind_ok, parse_queue_purge, parse_queue_purge_ok, parse_queue_delete_ok};
-use crate::basic::{parse_basic_delivery, parse_basic_qos_ok, parse_basic_consume, parse_basic_consume_ok, parse_basic_cancel, parse_basic_cancel_ok, parse_basic_publish, parse_basic_return, parse_basic_get, parse_basic_get_ok, parse_basic_reject, parse_basic_recover_async, parse_basic_recover, parse_basic_recover_ok, parse_basic_ack, parse_basic_nack, parse_basic_qos, parse_basic_get_empty};
-use crate::tx::{parse_tx_select, parse_tx_select_ok, parse_tx_commit, parse_tx_commit_ok, parse_tx_rollback, parse_tx_rollback_ok};
-use crate::confirm::{parse_confirm_select, parse_confirm_select_ok};
+use crate::basic::parse_basic_properties;
 use nom::{Err, Needed};
+use bytes::BytesMut;
 
 pub const PROTOCOL_HEADER_SIZE: usize = 8;
 
@@ -23,6 +18,71 @@ pub const PROTOCOL_HEADER_SIZE: usize = 8;
 // +----------------+---------------------+---------------------+--------------+-------------+
 // size_of(frame_type + channel_id + length)
 pub const FRAME_PREFIX_LENGTH: u32 = 7;
+pub const FRAME_END: u8 = 0xce;
+// size_of(frame end)
+pub const FRAME_END_LENGTH: u32 = 1;
+// largest frame a peer has to accept before frame_max is negotiated
+pub const FRAME_MIN_SIZE: u32 = 4096;
+
+// what a peer announced with its protocol header
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ProtocolVersion {
+    // "AMQP" 0 0 9 1
+    Amqp091,
+    // "AMQP" 1 1 0 9
+    Amqp09,
+    // "AMQP" 1 1 8 0
+    Amqp08,
+    // "AMQP" 0 1 0 0, 3 1 0 0 and 2 1 0 0, the protocol id picks the layer
+    Amqp10,
+    Amqp10Sasl,
+    Amqp10Tls,
+    // "AMQP" followed by anything else
+    Unknown(u8, u8, u8, u8),
+    // the peer does not speak AMQP at all
+    NotAmqp
+}
+
+impl ProtocolVersion {
+    pub fn from_ids(major_id: u8, minor_id: u8, major_version: u8, minor_version: u8) -> ProtocolVersion {
+        match (major_id, minor_id, major_version, minor_version) {
+            (0, 0, 9, 1) => ProtocolVersion::Amqp091,
+            (1, 1, 0, 9) => ProtocolVersion::Amqp09,
+            (1, 1, 8, 0) => ProtocolVersion::Amqp08,
+            (0, 1, 0, 0) => ProtocolVersion::Amqp10,
+            (3, 1, 0, 0) => ProtocolVersion::Amqp10Sasl,
+            (2, 1, 0, 0) => ProtocolVersion::Amqp10Tls,
+            (a, b, c, d) => ProtocolVersion::Unknown(a, b, c, d)
+        }
+    }
+
+    pub fn is_supported(&self) -> bool {
+        *self == ProtocolVersion::Amqp091
+    }
+
+    // A server that does not support the version writes the header it does
+    // support and closes the socket, None when the version is accepted.
+    pub fn response_header(&self) -> Option<ProtocolHeader> {
+        if self.is_supported() {
+            None
+        } else {
+            Some(protocol_header(0, 0, 9, 1))
+        }
+    }
+}
+
+// classify the first bytes sent by a peer, anything not starting with "AMQP"
+// is known as soon as the first byte differs
+pub fn classify_protocol_header(buffer: &[u8]) -> Result<ProtocolVersion, FrameDecodeErr> {
+    let prefix_length = buffer.len().min(4);
+    if buffer[..prefix_length] != b"AMQP"[..prefix_length] {
+        return Ok(ProtocolVersion::NotAmqp);
+    }
+    if buffer.len() < PROTOCOL_HEADER_SIZE {
+        return Err(FrameDecodeErr::Incomplete);
+    }
+    Ok(ProtocolVersion::from_ids(buffer[4], buffer[5], buffer[6], buffer[7]))
+}
 
 // parse protocol header
 pub fn parse_amqp_protocal_header(buffer: &[u8]) -> Result<ProtocolHeader, FrameDecodeErr> {
@@ -59,269 +119,174 @@ pub fn parse_amqp_protocal_header(buffer: &[u8]) -> Result<ProtocolHeader, Frame
     Ok(header)
 }
 
-pub(crate) fn parse_arguments(method: Method, buffer: &[u8]) -> Result<Arguments, FrameDecodeErr> {
-    match method {
-        Method::ConnectionMethod(method_type) => {
-            match method_type {
-                ConnectionMethod::Start => parse_connection_start(buffer),
-                ConnectionMethod::StartOk => parse_connection_start_ok(buffer),
-                ConnectionMethod::Tune => parse_connection_tune(buffer),
-                ConnectionMethod::TuneOk => parse_connection_tune_ok(buffer),
-                ConnectionMethod::Secure => parse_connection_secure(buffer),
-                ConnectionMethod::SecureOk => parse_connection_secure_ok(buffer),
-                ConnectionMethod::Open => parse_connection_open(buffer),
-                ConnectionMethod::OpenOk => parse_connection_open_ok(buffer),
-                ConnectionMethod::Close => parse_connection_close(buffer),
-                ConnectionMethod::CloseOk => parse_connection_close_ok(buffer),
-                ConnectionMethod::Unknown => return Err(FrameDecodeErr::UnknownMethodType)
-            }
-        },
-        Method::ChannelMethod(method_type) => {
-            match method_type {
-                ChannelMethod::Open => parse_channel_open(buffer),
-                ChannelMethod::OpenOk => parse_channel_open_ok(buffer),
-                ChannelMethod::Flow => parse_channel_flow(buffer),
-                ChannelMethod::FlowOk => parse_channel_flow_ok(buffer),
-                ChannelMethod::Close => parse_channel_close(buffer),
-                ChannelMethod::CloseOk => parse_channel_close_ok(buffer),
-                ChannelMethod::Unknown => return Err(FrameDecodeErr::UnknownMethodType)
-            }
-        },
-        Method::AccessMethod(method_type) => {
-            match method_type {
-                AccessMethod::Request => parse_access_request(buffer),
-                AccessMethod::RequestOk => parse_access_request_ok(buffer),
-                AccessMethod::Unknown => return Err(FrameDecodeErr::UnknownMethodType)
-            }
-        },
-        Method::ExchangeMethod(method_type) => {
-            match method_type {
-                ExchangeMethod::Declare => parse_exchange_declare(buffer),
-                ExchangeMethod::DeclareOk => parse_exchange_declare_ok(buffer),
-                ExchangeMethod::Bind => parse_exchange_bind(buffer),
-                ExchangeMethod::BindOk => parse_exchange_bind_ok(buffer),
-                ExchangeMethod::Unbind => parse_exchange_unbind(buffer),
-                ExchangeMethod::UnbindOk => parse_exchange_unbind_ok(buffer),
-                ExchangeMethod::Delete => parse_exchange_delete(buffer),
-                ExchangeMethod::DeleteOk => parse_exchange_delete_ok(buffer),
-                ExchangeMethod::Unknown => return Err(FrameDecodeErr::UnknownMethodType)
-            }
-        },
-        Method::QueueMethod(method_type) => {
-            match method_type {
-                QueueMethod::Declare => parse_queue_declare(buffer),
-                QueueMethod::DeclareOk => parse_queue_declare_ok(buffer),
-                QueueMethod::Bind => parse_queue_bind(buffer),
-                QueueMethod::BindOk => parse_queue_bind_ok(buffer),
-                QueueMethod::Unbind => parse_queue_unbind(buffer),
-                QueueMethod::UnbindOk => parse_queue_unbind_ok(buffer),
-                QueueMethod::Purge => parse_queue_purge(buffer),
-                QueueMethod::PurgeOk => parse_queue_purge_ok(buffer),
-                QueueMethod::Delete => parse_queue_delete(buffer),
-                QueueMethod::DeleteOk => parse_queue_delete_ok(buffer),
-                QueueMethod::Unknown => return Err(FrameDecodeErr::UnknownMethodType)
-            }
-        },
-        Method::BasicMethod(method_type) => {
-            match method_type {
-                BasicMethod::Qos => parse_basic_qos(buffer),
-                BasicMethod::QosOk => parse_basic_qos_ok(buffer),
-                BasicMethod::Consume => parse_basic_consume(buffer),
-                BasicMethod::ConsumeOk => parse_basic_consume_ok(buffer),
-                BasicMethod::Cancel => parse_basic_cancel(buffer),
-                BasicMethod::CancelOk => parse_basic_cancel_ok(buffer),
-                BasicMethod::Publish => parse_basic_publish(buffer),
-                BasicMethod::Return => parse_basic_return(buffer),
-                BasicMethod::Deliver => parse_basic_delivery(buffer),
-                BasicMethod::Get => parse_basic_get(buffer),
-                BasicMethod::GetEmpty => parse_basic_get_empty(buffer),
-                BasicMethod::GetOk => parse_basic_get_ok(buffer),
-                BasicMethod::Reject => parse_basic_reject(buffer),
-                BasicMethod::RecoverAsync => parse_basic_recover_async(buffer),
-                BasicMethod::Recover => parse_basic_recover(buffer),
-                BasicMethod::RecoverOk => parse_basic_recover_ok(buffer),
-                BasicMethod::Ack => parse_basic_ack(buffer),
-                BasicMethod::Nack => parse_basic_nack(buffer),
-                BasicMethod::Unknown => return Err(FrameDecodeErr::UnknownMethodType)
-            }
-        },
-        Method::TxMethod(method_type) => {
-            match method_type {
-                TxMethod::Select => parse_tx_select(buffer),
-                TxMethod::SelectOk => parse_tx_select_ok(buffer),
-                TxMethod::Commit => parse_tx_commit(buffer),
-                TxMethod::CommitOk => parse_tx_commit_ok(buffer),
-                TxMethod::Rollback => parse_tx_rollback(buffer),
-                TxMethod::RollbackOk => parse_tx_rollback_ok(buffer),
-                TxMethod::Unknown => return Err(FrameDecodeErr::UnknownMethodType)
-            }
-        },
-        Method::ConfirmMethod(method_type) => {
-            match method_type {
-                ConfirmMethod::Select => parse_confirm_select(buffer),
-                ConfirmMethod::SelectOk => parse_confirm_select_ok(buffer),
-                ConfirmMethod::Unknown => return Err(FrameDecodeErr::UnknownMethodType)
-            }
-        }
-    }
-}
-
+// one parse_<class>_<method> per method, see build.rs
+include!(concat!(env!("OUT_DIR"), "/parse_arguments.rs"));
 
-pub(crate) fn parse_method_frame(buffer: &[u8]) -> Result<(u32, Frame), FrameDecodeErr> {
+// Split a complete frame into channel id and payload. The returned length
+// covers the whole frame, frame end included.
+pub(crate) fn parse_frame_payload(buffer: &[u8]) -> Result<(u16, &[u8], u32), FrameDecodeErr> {
     // skip frame type
     let (buffer, _) = match be_u8::<(_, ErrorKind)>(buffer) {
         Ok(ret) => ret,
-        Err(e) => {
-            match e {
-                nom::Err::Incomplete(_) => return Err(FrameDecodeErr::Incomplete),
-                _ => return Err(FrameDecodeErr::ParseFrameFailed)
-            }
-        }
+        Err(_) => return Err(FrameDecodeErr::Incomplete)
     };
-    let (buffer, channel_id, payload_length) = match parse_channel_id_and_length(buffer) {
-        Ok(ret) => ret,
-        Err(e) => {
-            match e {
-                FrameDecodeErr::Incomplete => return Err(e),
-                _ => return Err(FrameDecodeErr::ParseFrameFailed)
-            }
-        }
-    };
-    let frame_length = FRAME_PREFIX_LENGTH + payload_length as u32;
-
+    let (buffer, channel_id, payload_length) = parse_channel_id_and_length(buffer)?;
     let (buffer, payload) = match take::<_,_,(_, ErrorKind)>(payload_length)(buffer) {
         Ok(ret) => ret,
         Err(e) => {
             match e {
                 Err::Incomplete(Needed::Size(_)) => return Err(FrameDecodeErr::Incomplete),
-                _ => return Err(FrameDecodeErr::ParseFrameFailed)
+                _ => return Err(FrameDecodeErr::field("payload", buffer, e))
             }
         }
     };
-
-    // check length
-    if buffer.len() < frame_length as usize {
-        return Err(FrameDecodeErr::Incomplete)
+    let (_, frame_end) = match be_u8::<(_, ErrorKind)>(buffer) {
+        Ok(ret) => ret,
+        Err(_) => return Err(FrameDecodeErr::Incomplete)
+    };
+    if frame_end != FRAME_END {
+        return Err(FrameDecodeErr::InvalidFrameEnd(frame_end));
     }
+    Ok((channel_id, payload, FRAME_PREFIX_LENGTH + payload_length + FRAME_END_LENGTH))
+}
 
-    let (buffer, class_id) = match be_u16::<(_, ErrorKind)>(buffer) {
+pub(crate) fn parse_method_frame(buffer: &[u8]) -> Result<(u32, Frame), FrameDecodeErr> {
+    let (channel_id, payload, frame_length) = parse_frame_payload(buffer)?;
+
+    let (args_buffer, class_id) = match be_u16::<(_, ErrorKind)>(payload) {
         Ok(ret) => ret,
-        _ => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::field("class_id", payload, e).in_frame(channel_id, None, None, payload))
     };
-    let (buffer, method_id) = match be_u16::<(_, ErrorKind)>(buffer) {
+    let (args_buffer, method_id) = match be_u16::<(_, ErrorKind)>(args_buffer) {
         Ok(ret) => ret,
-        _ => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::field("method_id", args_buffer, e).in_frame(channel_id, Some(class_id), None, payload))
     };
 
     // check class type
     let class_type = Class::from(class_id);
     if let Class::Unknown = class_type {
-        return Err(FrameDecodeErr::UnknownClassType);
+        return Err(FrameDecodeErr::UnknownClassType(channel_id, class_id));
     }
 
     let method_type = match get_method_type(class_type.clone(), method_id) {
-        Ok(method_type) => method_type,
-        Err(e) => return Err(e)
+        Some(method_type) => method_type,
+        None => return Err(FrameDecodeErr::UnknownMethodType(channel_id, class_id, method_id))
     };
 
-    let args = match parse_arguments(method_type, payload) {
+    let args = match parse_arguments(method_type.clone(), args_buffer) {
         Ok(args) => args,
-        Err(e) => return Err(e)
+        Err(e) => return Err(e.in_frame(channel_id, Some(class_id), Some(method_id), payload))
     };
-    let payload = MethodPayload::default();
-    payload.set_class(class_type);
-    payload.set_method(method_type);
-    payload.set_args(args);
-    let payload = Payload::Method(payload);
+    let method_payload = MethodPayload::default();
+    method_payload.set_class(class_type);
+    method_payload.set_method(method_type);
+    method_payload.set_args(args);
 
     let frame = Frame::default();
     frame.set_frame_type(FrameType::METHOD);
     frame.set_channel(channel_id);
-    frame.set_length(payload_length);
-    frame.set_payload(payload);
+    frame.set_length(payload.len() as u32);
+    frame.set_payload(Payload::Method(method_payload));
 
     Ok((frame_length, frame))
 }
 
 pub(crate) fn parse_content_header_frame(buffer: &[u8]) -> Result<(u32, Frame), FrameDecodeErr> {
-    // skip frame type
-    let (buffer, _) = match be_u8::<(_, ErrorKind)>(buffer) {
-        Ok(ret) => ret,
-        Err(e) => {
-            match e {
-                nom::Err::Incomplete(_) => return Err(FrameDecodeErr::Incomplete),
-                _ => return Err(FrameDecodeErr::ParseFrameFailed)
-            }
-        }
-    };
-    let (buffer, channel_id, payload_length) = match parse_channel_id_and_length(buffer) {
-        Ok(ret) => ret,
-        Err(e) => {
-            match e {
-                FrameDecodeErr::Incomplete => return Err(e),
-                _ => return Err(FrameDecodeErr::ParseFrameFailed)
-            }
-        }
-    };
-
-    // take payload
-    let (buffer, payload) = match take::<_,_,(_, ErrorKind)>(payload_length)(buffer) {
-        Ok(ret) => ret,
-        Err(e) => {
-            match e {
-                Err::Incomplete(Needed::Size(_)) => return Err(FrameDecodeErr::Incomplete),
-                _ => return Err(FrameDecodeErr::ParseFrameFailed)
-            }
-        }
-    };
+    let (channel_id, payload, frame_length) = parse_frame_payload(buffer)?;
 
     // pase payload
-    let (remain, class_id) = match be_u16::<(_, ErrorKind)>(buffer) {
+    let (remain, class_id) = match be_u16::<(_, ErrorKind)>(payload) {
         Ok(ret) => ret,
-        Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::field("class_id", payload, e).in_frame(channel_id, None, None, payload))
     };
     let class_type = Class::from(class_id);
     if let Class::Unknown = class_type {
-        return Err(FrameDecodeErr::UnknownClassType);
+        return Err(FrameDecodeErr::UnknownClassType(channel_id, class_id));
     }
     let (remain, weight) = match be_u16::<(_, ErrorKind)>(remain) {
         Ok(ret) => ret,
-        Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::field("weight", remain, e).in_frame(channel_id, Some(class_id), None, payload))
     };
     let (remain, body_size) = match be_u64::<(_, ErrorKind)>(remain) {
         Ok(ret) => ret,
-        Err(e) => return Err(FrameDecodeErr::ParseFrameFailed)
+        Err(e) => return Err(FrameDecodeErr::field("body_size", remain, e).in_frame(channel_id, Some(class_id), None, payload))
     };
+    // only the basic class carries content
+    let properties = match class_type {
+        Class::Basic => {
+            match parse_basic_properties(remain) {
+                Ok(properties) => properties,
+                Err(e) => return Err(e.in_frame(channel_id, Some(class_id), None, payload))
+            }
+        }
+        _ => return Err(FrameDecodeErr::UnknownClassType(channel_id, class_id))
+    };
+    let header = ContentHeaderPayload::default();
+    header.set_class(class_type);
+    header.set_weight(weight);
+    header.set_body_size(body_size);
+    header.set_properties(properties);
 
-
-    let frame_length = FRAME_PREFIX_LENGTH + payload_length as u32;
     let frame = Frame::default();
     frame.set_frame_type(FrameType::HEADER);
     frame.set_channel(channel_id);
-    frame.set_length(payload_length);
+    frame.set_length(payload.len() as u32);
+    frame.set_payload(Payload::ContentHeader(header));
+
+    Ok((frame_length, frame))
 }
 
-pub(crate) fn parse_content_body_frame(buffer: &[u8]) -> Result<(u32, Frame), FrameDecodeErr> {
+// take a body frame from the front of the buffer, without copying the payload
+pub(crate) fn split_content_body_frame(src: &mut BytesMut) -> Result<Frame, FrameDecodeErr> {
+    let (channel_id, payload_length, frame_length) = {
+        let (channel_id, payload, frame_length) = parse_frame_payload(&src[..])?;
+        (channel_id, payload.len(), frame_length as usize)
+    };
+    let data = src.split_to(frame_length).freeze();
+    let payload = data.slice(FRAME_PREFIX_LENGTH as usize..FRAME_PREFIX_LENGTH as usize + payload_length);
+
+    let body = ContentBodyPayload::default();
+    body.set_payload(payload);
+
     let frame = Frame::default();
     frame.set_frame_type(FrameType::BODY);
+    frame.set_channel(channel_id);
+    frame.set_length(payload_length as u32);
+    frame.set_payload(Payload::ContentBody(body));
+    Ok(frame)
+}
+
+pub(crate) fn parse_content_body_frame(buffer: &[u8]) -> Result<(u32, Frame), FrameDecodeErr> {
+    let (_, _, frame_length) = parse_frame_payload(buffer)?;
+    // a borrowed buffer has no owner to share with, copy this frame only
+    let mut src = BytesMut::from(&buffer[..frame_length as usize]);
+    let frame = split_content_body_frame(&mut src)?;
+    Ok((frame_length, frame))
 }
 
 pub(crate) fn parse_heartbeat_frame(buffer: &[u8]) -> Result<(u32, Frame), FrameDecodeErr> {
+    let (channel_id, payload, frame_length) = parse_frame_payload(buffer)?;
+    // heartbeats belong to the connection and carry nothing
+    if channel_id != 0 || !payload.is_empty() {
+        return Err(FrameDecodeErr::InvalidHeartbeatFrame);
+    }
+
     let frame = Frame::default();
     frame.set_frame_type(FrameType::HEARTBEAT);
+    frame.set_channel(channel_id);
+    frame.set_length(0);
+    frame.set_payload(Payload::Heartbeat);
+    Ok((frame_length, frame))
 }
 
 pub fn parse_frame(buffer: &[u8]) -> Result<(u32, Frame), FrameDecodeErr> {
-    let (buffer, frame_type) = match be_u8::<(_, ErrorKind)>(buffer) {
+    // only fails on an empty buffer
+    let (_, frame_type) = match be_u8::<(_, ErrorKind)>(buffer) {
         Ok(ret) => ret,
-        Err(e) => {
-            match e {
-                nom::Err::Incomplete(_) => return Err(FrameDecodeErr::Incomplete),
-                _ => return Err(FrameDecodeErr::ParseFrameFailed)
-            }
-        }
+        Err(_) => return Err(FrameDecodeErr::Incomplete)
     };
     let frame_type = FrameType::from(frame_type);
+    // frame parsers start at the frame type octet
     match frame_type {
         FrameType::HEARTBEAT => parse_heartbeat_frame(buffer),
         FrameType::METHOD => parse_method_frame(buffer),
@@ -329,4 +294,213 @@ pub fn parse_frame(buffer: &[u8]) -> Result<(u32, Frame), FrameDecodeErr> {
         FrameType::BODY => parse_content_body_frame(buffer),
         FrameType::UNKNOWN => return Err(FrameDecodeErr::UnknowFrameType)
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use crate::parse::parse_frame;
+    use crate::encode::write_frame;
+    use crate::common::{get_class_id, get_method_id, method_name, pack_bits};
+    use crate::spec::{method_fields, Domain};
+    use amqp_types::frame::{Payload, Arguments};
+    use bytes::BytesMut;
+
+    // one method payload per (class, method): class id, method id, arguments
+    const SAMPLES: &[(u16, u16, &[u8])] = &[
+        (10, 10, b"\x00\x09\x00\x00\x00\x15\x07productS\x00\x00\x00\x08RabbitMQ\x00\x00\x00\x0ePLAIN AMQPLAIN\x00\x00\x00\x05en_US"),
+        (10, 11, b"\x00\x00\x00\x00\x05PLAIN\x00\x00\x00\x0c\x00guest\x00guest\x05en_US"),
+        (10, 20, b"\x00\x00\x00\x09challenge"),
+        (10, 21, b"\x00\x00\x00\x08response"),
+        (10, 30, b"\x07\xff\x00\x02\x00\x00\x00\x3c"),
+        (10, 31, b"\x07\xff\x00\x02\x00\x00\x00\x3c"),
+        (10, 40, b"\x01/\x00\x00"),
+        (10, 41, b"\x00"),
+        (10, 50, b"\x02\x12\x0bNOT_ALLOWED\x00\x0a\x00\x28"),
+        (10, 51, b""),
+        (10, 60, b"\x0dlow on memory"),
+        (10, 61, b""),
+        (10, 70, b"\x00\x00\x00\x05token\x07refresh"),
+        (10, 71, b""),
+        (20, 10, b"\x00"),
+        (20, 11, b"\x00\x00\x00\x00"),
+        (20, 20, b"\x01"),
+        (20, 21, b"\x01"),
+        (20, 40, b"\x01\x94\x09NOT_FOUND\x00\x32\x00\x0a"),
+        (20, 41, b""),
+        (30, 10, b"\x05/data\x1a"),
+        (30, 11, b"\x00\x01"),
+        (40, 10, b"\x00\x00\x04logs\x06fanout\x02\x00\x00\x00\x00"),
+        (40, 11, b""),
+        (40, 20, b"\x00\x00\x04logs\x01"),
+        (40, 21, b""),
+        (40, 30, b"\x00\x00\x03dst\x03src\x02rk\x00\x00\x00\x00\x00"),
+        (40, 31, b""),
+        (40, 40, b"\x00\x00\x03dst\x03src\x02rk\x00\x00\x00\x00\x00"),
+        (40, 51, b""),
+        (50, 10, b"\x00\x00\x05tasks\x02\x00\x00\x00\x00"),
+        (50, 11, b"\x05tasks\x00\x00\x00\x05\x00\x00\x00\x01"),
+        (50, 20, b"\x00\x00\x05tasks\x04logs\x02rk\x00\x00\x00\x00\x00"),
+        (50, 21, b""),
+        (50, 30, b"\x00\x00\x05tasks\x00"),
+        (50, 31, b"\x00\x00\x00\x03"),
+        (50, 40, b"\x00\x00\x05tasks\x06"),
+        (50, 41, b"\x00\x00\x00\x00"),
+        (50, 50, b"\x00\x00\x05tasks\x04logs\x02rk\x00\x00\x00\x00"),
+        (50, 51, b""),
+        (60, 10, b"\x00\x00\x00\x00\x00\x0a\x00"),
+        (60, 11, b""),
+        (60, 20, b"\x00\x00\x05tasks\x04ctag\x02\x00\x00\x00\x00"),
+        (60, 21, b"\x04ctag"),
+        (60, 30, b"\x04ctag\x00"),
+        (60, 31, b"\x04ctag"),
+        (60, 40, b"\x00\x00\x04logs\x02rk\x01"),
+        (60, 50, b"\x01\x38\x08NO_ROUTE\x04logs\x02rk"),
+        (60, 60, b"\x04ctag\x00\x00\x00\x00\x00\x00\x00\x01\x00\x04logs\x02rk"),
+        (60, 70, b"\x00\x00\x05tasks\x01"),
+        (60, 71, b"\x00\x00\x00\x00\x00\x00\x00\x02\x01\x00\x05tasks\x00\x00\x00\x02"),
+        (60, 72, b"\x00"),
+        (60, 80, b"\x00\x00\x00\x00\x00\x00\x00\x02\x01"),
+        (60, 90, b"\x00\x00\x00\x00\x00\x00\x00\x03\x01"),
+        (60, 100, b"\x01"),
+        (60, 110, b"\x01"),
+        (60, 111, b""),
+        (60, 120, b"\x00\x00\x00\x00\x00\x00\x00\x04\x03"),
+        (85, 10, b"\x00"),
+        (85, 11, b""),
+        (90, 10, b""),
+        (90, 11, b""),
+        (90, 20, b""),
+        (90, 21, b""),
+        (90, 30, b""),
+        (90, 31, b"")
+    ];
+
+    #[test]
+    fn test_method_samples() {
+        for (class_id, method_id, arguments) in SAMPLES {
+            let name = method_name(*class_id, *method_id).unwrap();
+            let channel: u16 = if *class_id == 10 { 0 } else { 1 };
+            let mut frame = vec![0x01u8];
+            frame.extend_from_slice(&channel.to_be_bytes());
+            frame.extend_from_slice(&(arguments.len() as u32 + 4).to_be_bytes());
+            frame.extend_from_slice(&class_id.to_be_bytes());
+            frame.extend_from_slice(&method_id.to_be_bytes());
+            frame.extend_from_slice(arguments);
+            frame.push(0xce);
+
+            let (length, decoded) = match parse_frame(&frame) {
+                Ok(ret) => ret,
+                Err(e) => panic!("{}: {}", name, e)
+            };
+            assert_eq!(length as usize, frame.len(), "{}", name);
+            match decoded.payload() {
+                Payload::Method(method) => {
+                    assert_eq!(get_class_id(method.class()).unwrap(), *class_id, "{}", name);
+                    assert_eq!(get_method_id(method.method()).unwrap(), *method_id, "{}", name);
+                }
+                _ => panic!("{}: not a method frame", name)
+            }
+            let mut dst = BytesMut::new();
+            write_frame(&decoded, &mut dst).unwrap();
+            assert_eq!(&dst[..], &frame[..], "{}", name);
+        }
+    }
+
+    // arguments with every field zero or empty and the bits taken from `bits`,
+    // lowest first
+    fn bit_arguments(fields: &[(&str, Domain)], bits: u8) -> Vec<u8> {
+        let mut arguments = Vec::new();
+        for (index, (_, domain)) in fields.iter().enumerate() {
+            match domain {
+                Domain::Octet | Domain::ShortStr => arguments.push(0),
+                Domain::Short => arguments.extend_from_slice(&[0; 2]),
+                Domain::Long | Domain::LongStr | Domain::Table => arguments.extend_from_slice(&[0; 4]),
+                Domain::LongLong => arguments.extend_from_slice(&[0; 8]),
+                // no method has more than one run of bits
+                Domain::Bit => {
+                    if index == 0 || fields[index - 1].1 != Domain::Bit {
+                        arguments.push(bits);
+                    }
+                }
+            }
+        }
+        arguments
+    }
+
+    // every bit accessor of a method, in spec order
+    fn bit_flags(args: &Arguments) -> Vec<bool> {
+        match args {
+            Arguments::ConnectionOpen(args) => vec![args.insist()],
+            Arguments::ChannelFlow(args) => vec![args.active()],
+            Arguments::ChannelFlowOk(args) => vec![args.active()],
+            Arguments::AccessRequest(args) => vec![args.exclusive(), args.passive(), args.active(), args.write(), args.read()],
+            Arguments::ExchangeDeclare(args) => vec![args.passive(), args.durable(), args.auto_delete(), args.internal(), args.no_wait()],
+            Arguments::ExchangeDelete(args) => vec![args.if_unused(), args.no_wait()],
+            Arguments::ExchangeBind(args) => vec![args.no_wait()],
+            Arguments::ExchangeUnbind(args) => vec![args.no_wait()],
+            Arguments::QueueDeclare(args) => vec![args.passive(), args.durable(), args.exclusive(), args.auto_delete(), args.no_wait()],
+            Arguments::QueueBind(args) => vec![args.no_wait()],
+            Arguments::QueuePurge(args) => vec![args.no_wait()],
+            Arguments::QueueDelete(args) => vec![args.if_unused(), args.if_empty(), args.no_wait()],
+            Arguments::BasicQos(args) => vec![args.global()],
+            Arguments::BasicConsume(args) => vec![args.no_local(), args.no_ack(), args.exclusive(), args.no_wait()],
+            Arguments::BasicCancel(args) => vec![args.no_wait()],
+            Arguments::BasicPublish(args) => vec![args.mandatory(), args.immediate()],
+            Arguments::BasicDeliver(args) => vec![args.redelivered()],
+            Arguments::BasicGet(args) => vec![args.no_ack()],
+            Arguments::BasicGetOk(args) => vec![args.redelivered()],
+            Arguments::BasicAck(args) => vec![args.multiple()],
+            Arguments::BasicReject(args) => vec![args.requeue()],
+            Arguments::BasicRecoverAsync(args) => vec![args.requeue()],
+            Arguments::BasicRecover(args) => vec![args.requeue()],
+            Arguments::BasicNack(args) => vec![args.multiple(), args.requeue()],
+            Arguments::ConfirmSelect(args) => vec![args.no_wait()],
+            _ => Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_bit_combinations() {
+        for class_id in 0..256 {
+            for method_id in 0..256 {
+                let fields = match method_fields(class_id, method_id) {
+                    Some(fields) => fields,
+                    None => continue
+                };
+                let count = fields.iter().filter(|(_, domain)| *domain == Domain::Bit).count();
+                for bits in 0..(1u8 << count) {
+                    let arguments = bit_arguments(fields, bits);
+                    let mut frame = vec![0x01u8, 0x00, 0x01];
+                    frame.extend_from_slice(&(arguments.len() as u32 + 4).to_be_bytes());
+                    frame.extend_from_slice(&class_id.to_be_bytes());
+                    frame.extend_from_slice(&method_id.to_be_bytes());
+                    frame.extend_from_slice(&arguments);
+                    frame.push(0xce);
+
+                    // each accessor reports the bit at its own position
+                    let (_, decoded) = parse_frame(&frame).unwrap();
+                    let flags = match decoded.payload() {
+                        Payload::Method(method) => bit_flags(&method.args()),
+                        _ => panic!("method frame expected")
+                    };
+                    assert_eq!(flags.len(), count, "{}.{} bit accessors", class_id, method_id);
+                    assert_eq!(pack_bits(&flags), bits, "{}.{} bits {:#04x}", class_id, method_id, bits);
+
+                    let mut dst = BytesMut::new();
+                    write_frame(&decoded, &mut dst).unwrap();
+                    assert_eq!(&dst[..], &frame[..], "{}.{} bits {:#04x}", class_id, method_id, bits);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_samples_cover_spec() {
+        for class_id in 0..256 {
+            for method_id in 0..256 {
+                if let Some(name) = method_name(class_id, method_id) {
+                    assert!(SAMPLES.iter().any(|(class, method, _)| (*class, *method) == (class_id, method_id)), "no sample for {}", name);
+                }
+            }
+        }
+    }
+}
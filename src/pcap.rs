@@ -0,0 +1,844 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use bytes::BytesMut;
+use tokio_util::codec::Decoder;
+use crate::error::PcapErr;
+use crate::frame_codec::{FrameCodec, DecodedFrame, Role};
+use crate::capture::{Direction, read_full};
+
+// Reading AMQP conversations out of tcpdump captures: packets come from pcap
+// or pcapng files, TCP segments are put back in order per direction and the
+// resulting byte streams go through FrameCodec.
+
+const PCAPNG_SECTION_HEADER: u32 = 0x0a0d_0d0a;
+const PCAPNG_BYTE_ORDER: u32 = 0x1a2b_3c4d;
+const PCAPNG_INTERFACE: u32 = 1;
+const PCAPNG_OBSOLETE_PACKET: u32 = 2;
+const PCAPNG_SIMPLE_PACKET: u32 = 3;
+const PCAPNG_ENHANCED_PACKET: u32 = 6;
+const PCAPNG_TSRESOL_OPTION: u16 = 9;
+
+const LINKTYPE_NULL: u32 = 0;
+const LINKTYPE_ETHERNET: u32 = 1;
+const LINKTYPE_RAW: u32 = 101;
+const LINKTYPE_LOOP: u32 = 108;
+const LINKTYPE_LINUX_SLL: u32 = 113;
+const LINKTYPE_IPV4: u32 = 228;
+const LINKTYPE_IPV6: u32 = 229;
+const LINKTYPE_LINUX_SLL2: u32 = 276;
+
+// tcpdump's largest snaplen, a pcap record claiming more is garbage
+const MAX_PACKET_SIZE: usize = 262_144;
+// the limit Wireshark puts on a pcapng block
+const MAX_BLOCK_SIZE: usize = 16 * 1024 * 1024;
+// segments held behind a gap before the missing one is taken as lost from the
+// capture, well past what a TCP window keeps unacknowledged in practice
+const MAX_PENDING_SIZE: usize = 16 * 1024 * 1024;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_RST: u8 = 0x04;
+const TCP_ACK: u8 = 0x10;
+
+pub struct Packet {
+    // microseconds since the unix epoch
+    timestamp: u64,
+    link_type: u32,
+    data: Vec<u8>
+}
+
+impl Packet {
+    pub fn new(timestamp: u64, link_type: u32, data: Vec<u8>) -> Self {
+        Packet { timestamp, link_type, data }
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    pub fn link_type(&self) -> u32 {
+        self.link_type
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Endian {
+    Little,
+    Big
+}
+
+impl Endian {
+    fn u16(&self, data: &[u8]) -> u16 {
+        let bytes = [data[0], data[1]];
+        match self {
+            Endian::Little => u16::from_le_bytes(bytes),
+            Endian::Big => u16::from_be_bytes(bytes)
+        }
+    }
+
+    fn u32(&self, data: &[u8]) -> u32 {
+        let bytes = [data[0], data[1], data[2], data[3]];
+        match self {
+            Endian::Little => u32::from_le_bytes(bytes),
+            Endian::Big => u32::from_be_bytes(bytes)
+        }
+    }
+}
+
+struct Interface {
+    link_type: u32,
+    snap_len: u32,
+    // timestamp units per second
+    resolution: u64
+}
+
+enum Format {
+    // link type, true for nanosecond timestamps, snaplen
+    Pcap(u32, bool, u32),
+    Pcapng(Vec<Interface>)
+}
+
+// Packets of a pcap or pcapng file, told apart by their magic number.
+// pcapng sections may switch byte order, every section header is honoured.
+pub struct PcapReader<R: Read> {
+    reader: R,
+    endian: Endian,
+    format: Format,
+    // for pcapng simple packets, which carry no timestamp of their own
+    last_timestamp: u64
+}
+
+impl<R: Read> PcapReader<R> {
+    pub fn new(mut reader: R) -> Result<Self, PcapErr> {
+        let mut magic = [0u8; 4];
+        if read_full(&mut reader, &mut magic)? < magic.len() {
+            return Err(PcapErr::InvalidHeader);
+        }
+        let (endian, nanos) = match u32::from_le_bytes(magic) {
+            0xa1b2_c3d4 => (Endian::Little, false),
+            0xa1b2_3c4d => (Endian::Little, true),
+            0xd4c3_b2a1 => (Endian::Big, false),
+            0x4d3c_b2a1 => (Endian::Big, true),
+            PCAPNG_SECTION_HEADER => {
+                let mut pcap = PcapReader { reader, endian: Endian::Little, format: Format::Pcapng(Vec::new()), last_timestamp: 0 };
+                pcap.section_header()?;
+                return Ok(pcap);
+            }
+            _ => return Err(PcapErr::InvalidHeader)
+        };
+        // version, thiszone, sigfigs, snaplen, network
+        let mut header = [0u8; 20];
+        read_exactly(&mut reader, &mut header)?;
+        let snap_len = endian.u32(&header[12..16]);
+        let link_type = endian.u32(&header[16..20]);
+        Ok(PcapReader { reader, endian, format: Format::Pcap(link_type, nanos, snap_len), last_timestamp: 0 })
+    }
+
+    // None at the end of the file
+    pub fn next_packet(&mut self) -> Result<Option<Packet>, PcapErr> {
+        match self.format {
+            Format::Pcap(link_type, nanos, snap_len) => self.next_pcap_packet(link_type, nanos, snap_len),
+            Format::Pcapng(_) => self.next_pcapng_packet()
+        }
+    }
+
+    fn next_pcap_packet(&mut self, link_type: u32, nanos: bool, snap_len: u32) -> Result<Option<Packet>, PcapErr> {
+        let mut header = [0u8; 16];
+        match read_full(&mut self.reader, &mut header)? {
+            0 => return Ok(None),
+            16 => {},
+            _ => return Err(PcapErr::Truncated)
+        }
+        let seconds = self.endian.u32(&header[0..4]) as u64;
+        let fraction = self.endian.u32(&header[4..8]) as u64;
+        let captured = self.endian.u32(&header[8..12]);
+        // a length past the snaplen means the record header is not one
+        if captured as usize > MAX_PACKET_SIZE || (snap_len != 0 && captured > snap_len) {
+            return Err(PcapErr::InvalidRecord(captured));
+        }
+        let mut data = vec![0u8; captured as usize];
+        read_exactly(&mut self.reader, &mut data)?;
+        let timestamp = seconds * 1_000_000 + if nanos { fraction / 1000 } else { fraction };
+        Ok(Some(Packet::new(timestamp, link_type, data)))
+    }
+
+    fn next_pcapng_packet(&mut self) -> Result<Option<Packet>, PcapErr> {
+        loop {
+            let mut block_type = [0u8; 4];
+            match read_full(&mut self.reader, &mut block_type)? {
+                0 => return Ok(None),
+                4 => {},
+                _ => return Err(PcapErr::Truncated)
+            }
+            // a new section may come with a different byte order
+            let block_type = match u32::from_le_bytes(block_type) {
+                PCAPNG_SECTION_HEADER => {
+                    self.section_header()?;
+                    continue;
+                }
+                _ => self.endian.u32(&block_type)
+            };
+            let body = self.block_body(block_type)?;
+            let endian = self.endian;
+            let interfaces = match &mut self.format {
+                Format::Pcapng(interfaces) => interfaces,
+                Format::Pcap(..) => return Ok(None)
+            };
+            match block_type {
+                PCAPNG_INTERFACE if body.len() >= 8 => {
+                    let mut interface = Interface {
+                        link_type: endian.u16(&body[0..2]) as u32,
+                        snap_len: endian.u32(&body[4..8]),
+                        resolution: 1_000_000
+                    };
+                    if let Some(resolution) = find_option(endian, &body[8..], PCAPNG_TSRESOL_OPTION) {
+                        if let Some(&exponent) = resolution.first() {
+                            interface.resolution = match exponent & 0x80 {
+                                0 => 10u64.saturating_pow((exponent & 0x7f) as u32),
+                                _ => 2u64.saturating_pow((exponent & 0x7f) as u32)
+                            };
+                        }
+                    }
+                    interfaces.push(interface);
+                }
+                PCAPNG_ENHANCED_PACKET | PCAPNG_OBSOLETE_PACKET if body.len() >= 20 => {
+                    // the obsolete block has a 16 bit interface id and a drop counter
+                    let index = match block_type {
+                        PCAPNG_ENHANCED_PACKET => endian.u32(&body[0..4]) as usize,
+                        _ => endian.u16(&body[0..2]) as usize
+                    };
+                    let interface = match interfaces.get(index) {
+                        Some(interface) => interface,
+                        None => return Err(PcapErr::InvalidBlock(block_type))
+                    };
+                    let units = (endian.u32(&body[4..8]) as u64) << 32 | endian.u32(&body[8..12]) as u64;
+                    let captured = endian.u32(&body[12..16]) as usize;
+                    let data = match body.get(20..20 + captured) {
+                        Some(data) => data.to_vec(),
+                        None => return Err(PcapErr::InvalidBlock(block_type))
+                    };
+                    let timestamp = to_micros(units, interface.resolution);
+                    self.last_timestamp = timestamp;
+                    return Ok(Some(Packet::new(timestamp, interface.link_type, data)));
+                }
+                PCAPNG_SIMPLE_PACKET if body.len() >= 4 => {
+                    let interface = match interfaces.first() {
+                        Some(interface) => interface,
+                        None => return Err(PcapErr::InvalidBlock(block_type))
+                    };
+                    let original = endian.u32(&body[0..4]) as usize;
+                    let mut captured = original.min(body.len() - 4);
+                    if interface.snap_len != 0 {
+                        captured = captured.min(interface.snap_len as usize);
+                    }
+                    return Ok(Some(Packet::new(self.last_timestamp, interface.link_type, body[4..4 + captured].to_vec())));
+                }
+                PCAPNG_INTERFACE | PCAPNG_ENHANCED_PACKET | PCAPNG_OBSOLETE_PACKET | PCAPNG_SIMPLE_PACKET => {
+                    return Err(PcapErr::InvalidBlock(block_type));
+                }
+                // statistics, name resolution and custom blocks
+                _ => {}
+            }
+        }
+    }
+
+    // the rest of a section header block, its type already read
+    fn section_header(&mut self) -> Result<(), PcapErr> {
+        let mut header = [0u8; 8];
+        read_exactly(&mut self.reader, &mut header)?;
+        self.endian = match u32::from_le_bytes([header[4], header[5], header[6], header[7]]) {
+            PCAPNG_BYTE_ORDER => Endian::Little,
+            order if order.swap_bytes() == PCAPNG_BYTE_ORDER => Endian::Big,
+            _ => return Err(PcapErr::InvalidHeader)
+        };
+        let length = self.endian.u32(&header[0..4]) as usize;
+        if !(28..=MAX_BLOCK_SIZE).contains(&length) || length & 3 != 0 {
+            return Err(PcapErr::InvalidBlock(PCAPNG_SECTION_HEADER));
+        }
+        // version, section length and options are of no use here
+        let mut rest = vec![0u8; length - 12];
+        read_exactly(&mut self.reader, &mut rest)?;
+        self.format = Format::Pcapng(Vec::new());
+        Ok(())
+    }
+
+    // body of a block whose type has been read, without the trailing length
+    fn block_body(&mut self, block_type: u32) -> Result<Vec<u8>, PcapErr> {
+        let mut length = [0u8; 4];
+        read_exactly(&mut self.reader, &mut length)?;
+        let length = self.endian.u32(&length) as usize;
+        if !(12..=MAX_BLOCK_SIZE).contains(&length) || length & 3 != 0 {
+            return Err(PcapErr::InvalidBlock(block_type));
+        }
+        let mut body = vec![0u8; length - 8];
+        read_exactly(&mut self.reader, &mut body)?;
+        body.truncate(length - 12);
+        Ok(body)
+    }
+}
+
+impl<R: Read> Iterator for PcapReader<R> {
+    type Item = Result<Packet, PcapErr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_packet().transpose()
+    }
+}
+
+fn read_exactly<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<(), PcapErr> {
+    if read_full(reader, buf)? < buf.len() {
+        return Err(PcapErr::Truncated);
+    }
+    Ok(())
+}
+
+fn to_micros(units: u64, resolution: u64) -> u64 {
+    if resolution == 0 {
+        return units;
+    }
+    (units as u128 * 1_000_000 / resolution as u128) as u64
+}
+
+// value of a pcapng option, options are padded to 32 bits
+fn find_option(endian: Endian, mut options: &[u8], code: u16) -> Option<&[u8]> {
+    while options.len() >= 4 {
+        let option = endian.u16(&options[0..2]);
+        let length = endian.u16(&options[2..4]) as usize;
+        if option == 0 {
+            break;
+        }
+        let value = options.get(4..4 + length)?;
+        if option == code {
+            return Some(value);
+        }
+        let padded = 4 + ((length + 3) & !3);
+        options = options.get(padded..)?;
+    }
+    None
+}
+
+struct TcpSegment<'a> {
+    source: SocketAddr,
+    destination: SocketAddr,
+    seq: u32,
+    flags: u8,
+    payload: &'a [u8]
+}
+
+// the IP packet inside a link layer frame
+fn ip_packet(link_type: u32, data: &[u8]) -> Option<&[u8]> {
+    match link_type {
+        LINKTYPE_ETHERNET => {
+            let mut offset = 12;
+            loop {
+                match data.get(offset..offset + 2)? {
+                    // 802.1Q and 802.1ad tags
+                    [0x81, 0x00] | [0x88, 0xa8] => offset += 4,
+                    [0x08, 0x00] | [0x86, 0xdd] => return data.get(offset + 2..),
+                    _ => return None
+                }
+            }
+        }
+        // the address family is in host byte order, the IP version says enough
+        LINKTYPE_NULL | LINKTYPE_LOOP => data.get(4..),
+        LINKTYPE_RAW | LINKTYPE_IPV4 | LINKTYPE_IPV6 => Some(data),
+        LINKTYPE_LINUX_SLL => match data.get(14..16)? {
+            [0x08, 0x00] | [0x86, 0xdd] => data.get(16..),
+            _ => None
+        },
+        LINKTYPE_LINUX_SLL2 => match data.get(0..2)? {
+            [0x08, 0x00] | [0x86, 0xdd] => data.get(20..),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+// TCP inside IPv4 or IPv6, fragments are not put back together
+fn tcp_segment(packet: &[u8]) -> Option<TcpSegment<'_>> {
+    let (source, destination, mut next_header, mut payload) = match packet.first()? >> 4 {
+        4 => {
+            if packet.len() < 20 {
+                return None;
+            }
+            let header_length = ((packet[0] & 0x0f) as usize) * 4;
+            let total_length = u16::from_be_bytes([packet[2], packet[3]]) as usize;
+            let fragment = u16::from_be_bytes([packet[6], packet[7]]);
+            if fragment & 0x3fff != 0 || header_length < 20 {
+                return None;
+            }
+            let source = IpAddr::V4(Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]));
+            let destination = IpAddr::V4(Ipv4Addr::new(packet[16], packet[17], packet[18], packet[19]));
+            // ethernet pads short packets, total_length tells where IP ends
+            (source, destination, packet[9], packet.get(header_length..total_length.min(packet.len()))?)
+        }
+        6 => {
+            let payload_length = u16::from_be_bytes([*packet.get(4)?, *packet.get(5)?]) as usize;
+            let mut source = [0u8; 16];
+            source.copy_from_slice(packet.get(8..24)?);
+            let mut destination = [0u8; 16];
+            destination.copy_from_slice(packet.get(24..40)?);
+            (IpAddr::V6(Ipv6Addr::from(source)), IpAddr::V6(Ipv6Addr::from(destination)), packet[6], packet.get(40..(40 + payload_length).min(packet.len()))?)
+        }
+        _ => return None
+    };
+    // IPv6 extension headers ahead of TCP
+    loop {
+        match next_header {
+            6 => break,
+            0 | 43 | 60 => {
+                let length = (*payload.get(1)? as usize + 1) * 8;
+                next_header = payload[0];
+                payload = payload.get(length..)?;
+            }
+            51 => {
+                let length = (*payload.get(1)? as usize + 2) * 4;
+                next_header = payload[0];
+                payload = payload.get(length..)?;
+            }
+            _ => return None
+        }
+    }
+
+    let data_offset = ((*payload.get(12)? >> 4) as usize) * 4;
+    if data_offset < 20 {
+        return None;
+    }
+    Some(TcpSegment {
+        source: SocketAddr::new(source, u16::from_be_bytes([payload[0], payload[1]])),
+        destination: SocketAddr::new(destination, u16::from_be_bytes([payload[2], payload[3]])),
+        seq: u32::from_be_bytes([payload[4], payload[5], payload[6], payload[7]]),
+        flags: *payload.get(13)?,
+        payload: payload.get(data_offset..)?
+    })
+}
+
+// one direction of a TCP connection
+struct HalfStream {
+    // first sequence number of the data, None until a segment was seen
+    isn: Option<u32>,
+    // bytes handed to the codec so far
+    delivered: u64,
+    // segments beyond a gap, by stream offset
+    pending: BTreeMap<u64, Vec<u8>>,
+    // bytes held in pending
+    pending_size: usize,
+    buffer: BytesMut,
+    codec: FrameCodec,
+    // the codec gave up or a gap was reported, nothing more is decoded this way
+    failed: bool
+}
+
+impl HalfStream {
+    fn new() -> Self {
+        HalfStream {
+            isn: None,
+            delivered: 0,
+            pending: BTreeMap::new(),
+            pending_size: 0,
+            buffer: BytesMut::new(),
+            codec: FrameCodec::new(),
+            failed: false
+        }
+    }
+
+    // A stream picked up after its SYN starts with frames rather than the
+    // protocol header, so its codec is told not to wait for one.
+    fn start(&mut self, isn: u32, direction: Direction, from_syn: bool) {
+        let role = match (direction, from_syn) {
+            (Direction::ClientToServer, true) => Role::Server,
+            _ => Role::Client
+        };
+        self.isn = Some(isn);
+        self.codec = FrameCodec::with_role(role);
+        // frames were accepted by the peers already, no limit to enforce
+        self.codec.set_frame_max(0);
+    }
+
+    // put a segment in place, true when new bytes reached the buffer
+    fn push(&mut self, seq: u32, payload: &[u8]) -> bool {
+        let isn = match self.isn {
+            Some(isn) => isn,
+            None => return false
+        };
+        if payload.is_empty() {
+            return false;
+        }
+        // stream offset of the segment, sequence numbers wrap
+        let next_seq = isn.wrapping_add(self.delivered as u32);
+        let delivered = self.delivered as i64;
+        let start = delivered + seq.wrapping_sub(next_seq) as i32 as i64;
+        if start + payload.len() as i64 <= delivered {
+            // a retransmit of bytes already delivered
+            return false;
+        }
+        if start > delivered {
+            let entry = self.pending.entry(start as u64).or_default();
+            if entry.len() < payload.len() {
+                self.pending_size += payload.len() - entry.len();
+                *entry = payload.to_vec();
+            }
+            return false;
+        }
+        let payload = &payload[(delivered - start) as usize..];
+        self.buffer.extend_from_slice(payload);
+        self.delivered += payload.len() as u64;
+
+        // whatever the segment made contiguous
+        while let Some(offset) = self.pending.keys().next().copied() {
+            if offset > self.delivered {
+                break;
+            }
+            let segment = self.pending.remove(&offset).unwrap_or_default();
+            self.pending_size -= segment.len();
+            let skip = (self.delivered - offset) as usize;
+            if let Some(rest) = segment.get(skip..) {
+                self.buffer.extend_from_slice(rest);
+                self.delivered += rest.len() as u64;
+            }
+        }
+        true
+    }
+
+    fn clear_pending(&mut self) {
+        self.pending.clear();
+        self.pending_size = 0;
+    }
+}
+
+struct TcpConnection {
+    id: u32,
+    client: SocketAddr,
+    server: SocketAddr,
+    to_server: HalfStream,
+    to_client: HalfStream
+}
+
+pub struct FrameEvent {
+    connection_id: u32,
+    client: SocketAddr,
+    server: SocketAddr,
+    direction: Direction,
+    // microseconds since the unix epoch, of the packet completing the frame
+    timestamp: u64,
+    frame: Result<DecodedFrame, PcapErr>
+}
+
+impl FrameEvent {
+    pub fn connection_id(&self) -> u32 {
+        self.connection_id
+    }
+
+    pub fn client(&self) -> SocketAddr {
+        self.client
+    }
+
+    pub fn server(&self) -> SocketAddr {
+        self.server
+    }
+
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    pub fn timestamp(&self) -> u64 {
+        self.timestamp
+    }
+
+    // an error ends decoding for this direction of the connection, either
+    // PcapErr::Decode or PcapErr::Gap
+    pub fn frame(&self) -> &Result<DecodedFrame, PcapErr> {
+        &self.frame
+    }
+
+    pub fn into_frame(self) -> Result<DecodedFrame, PcapErr> {
+        self.frame
+    }
+}
+
+// Follows every TCP connection to or from one of `ports`. Events come out in
+// packet order, each connection numbered from 0 in order of appearance.
+pub struct TcpReassembler {
+    ports: Vec<u16>,
+    connections: HashMap<(SocketAddr, SocketAddr), TcpConnection>,
+    next_id: u32
+}
+
+impl TcpReassembler {
+    pub fn new(ports: &[u16]) -> Self {
+        TcpReassembler {
+            ports: ports.to_vec(),
+            connections: HashMap::new(),
+            next_id: 0
+        }
+    }
+
+    // anything that is not TCP to or from an AMQP port is ignored
+    pub fn push(&mut self, packet: &Packet) -> Vec<FrameEvent> {
+        let segment = match ip_packet(packet.link_type, &packet.data).and_then(tcp_segment) {
+            Some(segment) => segment,
+            None => return Vec::new()
+        };
+        let (client, server, direction) = if self.ports.contains(&segment.destination.port()) {
+            (segment.source, segment.destination, Direction::ClientToServer)
+        } else if self.ports.contains(&segment.source.port()) {
+            (segment.destination, segment.source, Direction::ServerToClient)
+        } else {
+            return Vec::new();
+        };
+
+        let syn = segment.flags & TCP_SYN != 0;
+        // a new SYN on a known address pair is a new connection, unless it
+        // repeats the one that opened the current connection
+        let reopened = syn && segment.flags & TCP_ACK == 0 && match self.connections.get(&(client, server)) {
+            Some(connection) => connection.to_server.isn != Some(segment.seq.wrapping_add(1)),
+            None => false
+        };
+        if reopened || !self.connections.contains_key(&(client, server)) {
+            let connection = TcpConnection {
+                id: self.next_id,
+                client,
+                server,
+                to_server: HalfStream::new(),
+                to_client: HalfStream::new()
+            };
+            self.next_id += 1;
+            self.connections.insert((client, server), connection);
+        }
+        let connection = match self.connections.get_mut(&(client, server)) {
+            Some(connection) => connection,
+            None => return Vec::new()
+        };
+        let stream = match direction {
+            Direction::ClientToServer => &mut connection.to_server,
+            Direction::ServerToClient => &mut connection.to_client
+        };
+
+        if syn {
+            if stream.isn.is_none() || stream.delivered == 0 {
+                stream.start(segment.seq.wrapping_add(1), direction, true);
+            }
+        } else if stream.isn.is_none() {
+            stream.start(segment.seq, direction, false);
+        }
+        let seq = if syn { segment.seq.wrapping_add(1) } else { segment.seq };
+        if stream.failed {
+            return Vec::new();
+        }
+        let (connection_id, client, server) = (connection.id, connection.client, connection.server);
+        let event = |frame| FrameEvent {
+            connection_id,
+            client,
+            server,
+            direction,
+            timestamp: packet.timestamp,
+            frame
+        };
+
+        let mut events = Vec::new();
+        if stream.push(seq, segment.payload) {
+            loop {
+                let frame = match stream.codec.decode(&mut stream.buffer) {
+                    Ok(Some(frame)) => Ok(frame),
+                    Ok(None) => break,
+                    Err(err) => {
+                        stream.failed = true;
+                        Err(PcapErr::Decode(err))
+                    }
+                };
+                let failed = frame.is_err();
+                events.push(event(frame));
+                if failed {
+                    break;
+                }
+            }
+        }
+        // a segment missing from the capture holds back everything after it,
+        // once the peer is done or too much piles up that direction is lost
+        let closed = segment.flags & (TCP_FIN | TCP_RST) != 0;
+        if !stream.failed && !stream.pending.is_empty() && (closed || stream.pending_size > MAX_PENDING_SIZE) {
+            stream.failed = true;
+            events.push(event(Err(PcapErr::Gap(stream.delivered))));
+        }
+        if closed || stream.failed {
+            // keep the connection around for late retransmits, but let go
+            // of data that can never become contiguous
+            stream.clear_pending();
+        }
+        events
+    }
+}
+
+// every frame of every AMQP connection in a pcap or pcapng capture
+pub fn read_pcap<R: Read>(reader: R, ports: &[u16]) -> Result<Vec<FrameEvent>, PcapErr> {
+    let mut reassembler = TcpReassembler::new(ports);
+    let mut events = Vec::new();
+    for packet in PcapReader::new(reader)? {
+        events.extend(reassembler.push(&packet?));
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::frame_codec::DecodedFrame;
+    use crate::capture::Direction;
+    use crate::error::PcapErr;
+    use crate::pcap::{read_pcap, FrameEvent, Packet, PcapReader, TcpReassembler};
+
+    const CLIENT: [u8; 4] = [10, 0, 0, 1];
+    const SERVER: [u8; 4] = [10, 0, 0, 2];
+    const HEARTBEAT: &[u8] = b"\x08\x00\x00\x00\x00\x00\x00\xce";
+
+    // ethernet, IPv4 and TCP around `payload`
+    fn packet(from_client: bool, seq: u32, flags: u8, payload: &[u8]) -> Vec<u8> {
+        let (source, destination, ports) = if from_client { (CLIENT, SERVER, [0xc3, 0x50, 0x16, 0x28]) } else { (SERVER, CLIENT, [0x16, 0x28, 0xc3, 0x50]) };
+        let mut data = vec![0u8; 12];
+        data.extend_from_slice(&[0x08, 0x00]);
+        data.extend_from_slice(&[0x45, 0x00]);
+        data.extend_from_slice(&((40 + payload.len()) as u16).to_be_bytes());
+        data.extend_from_slice(&[0x00, 0x00, 0x40, 0x00, 0x40, 0x06, 0x00, 0x00]);
+        data.extend_from_slice(&source);
+        data.extend_from_slice(&destination);
+        data.extend_from_slice(&ports);
+        data.extend_from_slice(&seq.to_be_bytes());
+        data.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x50, flags, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00]);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    // client sends its header and a heartbeat out of order, with a
+    // retransmit, the server answers with a heartbeat
+    fn conversation() -> Vec<(u64, Vec<u8>)> {
+        let stream = [&b"AMQP\x00\x00\x09\x01"[..], HEARTBEAT].concat();
+        vec![
+            (1_000_000, packet(true, 99, 0x02, b"")),
+            (1_000_100, packet(false, 499, 0x12, b"")),
+            (1_000_200, packet(true, 112, 0x18, &stream[12..])),
+            (1_000_300, packet(true, 100, 0x18, &stream[..5])),
+            (1_000_400, packet(true, 100, 0x18, &stream[..5])),
+            (1_000_500, packet(true, 105, 0x18, &stream[5..12])),
+            (1_000_600, packet(false, 500, 0x18, HEARTBEAT))
+        ]
+    }
+
+    fn check(events: Vec<FrameEvent>) {
+        let summary: Vec<(u32, Direction, u64, bool)> = events.iter().map(|event| {
+            let header = match event.frame() {
+                Ok(DecodedFrame::ProtocolHeader(_)) => true,
+                Ok(DecodedFrame::AmqpFrame(_)) => false,
+                Err(err) => panic!("{}", err)
+            };
+            (event.connection_id(), event.direction(), event.timestamp(), header)
+        }).collect();
+        assert_eq!(summary, vec![
+            (0, Direction::ClientToServer, 1_000_500, true),
+            (0, Direction::ClientToServer, 1_000_500, false),
+            (0, Direction::ServerToClient, 1_000_600, false)
+        ]);
+        assert_eq!(events[0].server().port(), 5672);
+    }
+
+    #[test]
+    fn test_pcap() {
+        let mut file = Vec::new();
+        file.extend_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+        file.extend_from_slice(&[0x02, 0x00, 0x04, 0x00]);
+        file.extend_from_slice(&[0u8; 8]);
+        file.extend_from_slice(&65535u32.to_le_bytes());
+        file.extend_from_slice(&1u32.to_le_bytes());
+        for (timestamp, data) in conversation() {
+            file.extend_from_slice(&((timestamp / 1_000_000) as u32).to_le_bytes());
+            file.extend_from_slice(&((timestamp % 1_000_000) as u32).to_le_bytes());
+            file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            file.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            file.extend_from_slice(&data);
+        }
+        check(read_pcap(&file[..], &[5672]).unwrap());
+        assert!(read_pcap(&file[..], &[5671]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_pcapng() {
+        // big endian section, interface with nanosecond timestamps
+        let mut file = Vec::new();
+        file.extend_from_slice(&[0x0a, 0x0d, 0x0d, 0x0a, 0x00, 0x00, 0x00, 0x1c, 0x1a, 0x2b, 0x3c, 0x4d, 0x00, 0x01, 0x00, 0x00]);
+        file.extend_from_slice(&[0xff; 8]);
+        file.extend_from_slice(&28u32.to_be_bytes());
+        file.extend_from_slice(&[0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x20, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        file.extend_from_slice(&[0x00, 0x09, 0x00, 0x01, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00]);
+        file.extend_from_slice(&32u32.to_be_bytes());
+        for (timestamp, data) in conversation() {
+            let padded = (data.len() + 3) & !3;
+            let length = (32 + padded) as u32;
+            let nanos = timestamp * 1000;
+            file.extend_from_slice(&6u32.to_be_bytes());
+            file.extend_from_slice(&length.to_be_bytes());
+            file.extend_from_slice(&0u32.to_be_bytes());
+            file.extend_from_slice(&((nanos >> 32) as u32).to_be_bytes());
+            file.extend_from_slice(&(nanos as u32).to_be_bytes());
+            file.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            file.extend_from_slice(&(data.len() as u32).to_be_bytes());
+            file.extend_from_slice(&data);
+            file.resize(file.len() + padded - data.len(), 0);
+            file.extend_from_slice(&length.to_be_bytes());
+        }
+        check(read_pcap(&file[..], &[5672]).unwrap());
+    }
+
+    #[test]
+    fn test_oversized_lengths() {
+        // pcap record claiming 4 GiB
+        let mut file = Vec::new();
+        file.extend_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+        file.extend_from_slice(&[0x02, 0x00, 0x04, 0x00]);
+        file.extend_from_slice(&[0u8; 8]);
+        file.extend_from_slice(&65535u32.to_le_bytes());
+        file.extend_from_slice(&1u32.to_le_bytes());
+        file.extend_from_slice(&[0u8; 8]);
+        file.extend_from_slice(&[0xff; 8]);
+        match PcapReader::new(&file[..]).unwrap().next_packet() {
+            Err(PcapErr::InvalidRecord(0xffff_ffff)) => (),
+            _ => panic!("oversized pcap record accepted")
+        }
+
+        // little endian section followed by an enhanced packet block of 4 GiB
+        let mut file = Vec::new();
+        file.extend_from_slice(&[0x0a, 0x0d, 0x0d, 0x0a, 0x1c, 0x00, 0x00, 0x00, 0x4d, 0x3c, 0x2b, 0x1a, 0x01, 0x00, 0x00, 0x00]);
+        file.extend_from_slice(&[0xff; 8]);
+        file.extend_from_slice(&28u32.to_le_bytes());
+        file.extend_from_slice(&6u32.to_le_bytes());
+        file.extend_from_slice(&0xffff_fffcu32.to_le_bytes());
+        match PcapReader::new(&file[..]).unwrap().next_packet() {
+            Err(PcapErr::InvalidBlock(6)) => (),
+            _ => panic!("oversized pcapng block accepted")
+        }
+    }
+
+    #[test]
+    fn test_gap() {
+        // the client's second segment never made it into the capture
+        let stream = [&b"AMQP\x00\x00\x09\x01"[..], HEARTBEAT, HEARTBEAT].concat();
+        let packets = vec![
+            packet(true, 99, 0x02, b""),
+            packet(true, 100, 0x18, &stream[..8]),
+            packet(true, 116, 0x18, &stream[16..]),
+            packet(true, 124, 0x11, b""),
+            // too late, the direction was given up on
+            packet(true, 108, 0x18, &stream[8..16])
+        ];
+        let mut reassembler = TcpReassembler::new(&[5672]);
+        let events: Vec<FrameEvent> = packets.into_iter().flat_map(|data| reassembler.push(&Packet::new(0, 1, data))).collect();
+        assert_eq!(events.len(), 2);
+        assert!(events[0].frame().is_ok());
+        match events[1].frame() {
+            Err(PcapErr::Gap(8)) => (),
+            _ => panic!("gap not reported")
+        }
+    }
+}
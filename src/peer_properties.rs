@@ -0,0 +1,374 @@
+use amqp_types::{FieldTable, FieldValue, FieldName};
+use amqp_types::basic_types::FieldValueKind;
+use crate::error::ArgumentErr;
+use crate::common::{read_table_string, read_table_bool, table_string, insert_table_entry};
+
+// Typed view over the server_properties of Connection.Start and the
+// client_properties of Connection.StartOk. Keys not modelled here stay in
+// `others`, both for the properties and for the nested capabilities table.
+
+// what the peer says it supports, None when it did not mention the key
+#[derive(Debug, Clone, Default)]
+pub struct Capabilities {
+    publisher_confirms: Option<bool>,
+    exchange_exchange_bindings: Option<bool>,
+    basic_nack: Option<bool>,
+    consumer_cancel_notify: Option<bool>,
+    connection_blocked: Option<bool>,
+    consumer_priorities: Option<bool>,
+    authentication_failure_close: Option<bool>,
+    per_consumer_qos: Option<bool>,
+    direct_reply_to: Option<bool>,
+    others: FieldTable
+}
+
+impl Capabilities {
+    pub fn new() -> Capabilities {
+        Capabilities::default()
+    }
+
+    // what a client built on this crate can handle
+    pub fn client() -> Capabilities {
+        let mut capabilities = Capabilities::new();
+        capabilities.set_publisher_confirms(Some(true));
+        capabilities.set_exchange_exchange_bindings(Some(true));
+        capabilities.set_basic_nack(Some(true));
+        capabilities.set_consumer_cancel_notify(Some(true));
+        capabilities.set_connection_blocked(Some(true));
+        capabilities.set_authentication_failure_close(Some(true));
+        capabilities
+    }
+
+    pub fn from_table(table: &FieldTable) -> Result<Capabilities, ArgumentErr> {
+        let mut capabilities = Capabilities::new();
+        for (name, value) in table.iter() {
+            let key = match std::str::from_utf8(name.as_bytes()) {
+                Ok(key) => key,
+                Err(_) => {
+                    capabilities.others.insert(name.clone(), value.clone());
+                    continue;
+                }
+            };
+            match key {
+                "publisher_confirms" => capabilities.publisher_confirms = Some(read_table_bool(key, value)?),
+                "exchange_exchange_bindings" => capabilities.exchange_exchange_bindings = Some(read_table_bool(key, value)?),
+                "basic.nack" => capabilities.basic_nack = Some(read_table_bool(key, value)?),
+                "consumer_cancel_notify" => capabilities.consumer_cancel_notify = Some(read_table_bool(key, value)?),
+                "connection.blocked" => capabilities.connection_blocked = Some(read_table_bool(key, value)?),
+                "consumer_priorities" => capabilities.consumer_priorities = Some(read_table_bool(key, value)?),
+                "authentication_failure_close" => capabilities.authentication_failure_close = Some(read_table_bool(key, value)?),
+                "per_consumer_qos" => capabilities.per_consumer_qos = Some(read_table_bool(key, value)?),
+                "direct_reply_to" => capabilities.direct_reply_to = Some(read_table_bool(key, value)?),
+                _ => {
+                    capabilities.others.insert(name.clone(), value.clone());
+                }
+            }
+        }
+        Ok(capabilities)
+    }
+
+    pub fn to_field_table(&self) -> Result<FieldTable, ArgumentErr> {
+        let known = [
+            ("publisher_confirms", self.publisher_confirms),
+            ("exchange_exchange_bindings", self.exchange_exchange_bindings),
+            ("basic.nack", self.basic_nack),
+            ("consumer_cancel_notify", self.consumer_cancel_notify),
+            ("connection.blocked", self.connection_blocked),
+            ("consumer_priorities", self.consumer_priorities),
+            ("authentication_failure_close", self.authentication_failure_close),
+            ("per_consumer_qos", self.per_consumer_qos),
+            ("direct_reply_to", self.direct_reply_to)
+        ];
+        let mut table = FieldTable::new();
+        for (key, supported) in known.iter() {
+            if let Some(supported) = supported {
+                insert_table_entry(&mut table, key, FieldValue::from_bool(*supported))?;
+            }
+        }
+        for (name, value) in self.others.iter() {
+            table.insert(name.clone(), value.clone());
+        }
+        Ok(table)
+    }
+
+    pub fn publisher_confirms(&self) -> Option<bool> {
+        self.publisher_confirms
+    }
+
+    pub fn set_publisher_confirms(&mut self, publisher_confirms: Option<bool>) {
+        self.publisher_confirms = publisher_confirms;
+    }
+
+    pub fn exchange_exchange_bindings(&self) -> Option<bool> {
+        self.exchange_exchange_bindings
+    }
+
+    pub fn set_exchange_exchange_bindings(&mut self, exchange_exchange_bindings: Option<bool>) {
+        self.exchange_exchange_bindings = exchange_exchange_bindings;
+    }
+
+    // "basic.nack"
+    pub fn basic_nack(&self) -> Option<bool> {
+        self.basic_nack
+    }
+
+    pub fn set_basic_nack(&mut self, basic_nack: Option<bool>) {
+        self.basic_nack = basic_nack;
+    }
+
+    pub fn consumer_cancel_notify(&self) -> Option<bool> {
+        self.consumer_cancel_notify
+    }
+
+    pub fn set_consumer_cancel_notify(&mut self, consumer_cancel_notify: Option<bool>) {
+        self.consumer_cancel_notify = consumer_cancel_notify;
+    }
+
+    // "connection.blocked"
+    pub fn connection_blocked(&self) -> Option<bool> {
+        self.connection_blocked
+    }
+
+    pub fn set_connection_blocked(&mut self, connection_blocked: Option<bool>) {
+        self.connection_blocked = connection_blocked;
+    }
+
+    pub fn consumer_priorities(&self) -> Option<bool> {
+        self.consumer_priorities
+    }
+
+    pub fn set_consumer_priorities(&mut self, consumer_priorities: Option<bool>) {
+        self.consumer_priorities = consumer_priorities;
+    }
+
+    pub fn authentication_failure_close(&self) -> Option<bool> {
+        self.authentication_failure_close
+    }
+
+    pub fn set_authentication_failure_close(&mut self, authentication_failure_close: Option<bool>) {
+        self.authentication_failure_close = authentication_failure_close;
+    }
+
+    pub fn per_consumer_qos(&self) -> Option<bool> {
+        self.per_consumer_qos
+    }
+
+    pub fn set_per_consumer_qos(&mut self, per_consumer_qos: Option<bool>) {
+        self.per_consumer_qos = per_consumer_qos;
+    }
+
+    pub fn direct_reply_to(&self) -> Option<bool> {
+        self.direct_reply_to
+    }
+
+    pub fn set_direct_reply_to(&mut self, direct_reply_to: Option<bool>) {
+        self.direct_reply_to = direct_reply_to;
+    }
+
+    pub fn others(&self) -> &FieldTable {
+        &self.others
+    }
+
+    pub fn set_other(&mut self, name: FieldName, value: FieldValue) {
+        self.others.insert(name, value);
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct PeerProperties {
+    product: Option<String>,
+    version: Option<String>,
+    platform: Option<String>,
+    cluster_name: Option<String>,
+    copyright: Option<String>,
+    information: Option<String>,
+    capabilities: Option<Capabilities>,
+    others: FieldTable
+}
+
+impl PeerProperties {
+    pub fn new() -> PeerProperties {
+        PeerProperties::default()
+    }
+
+    // client_properties naming this crate, with the capabilities of
+    // Capabilities::client, ready for ClientConfig::set_client_properties
+    // once turned into a table
+    pub fn client() -> PeerProperties {
+        let mut properties = PeerProperties::new();
+        properties.set_product(Some(env!("CARGO_PKG_NAME").to_string()));
+        properties.set_version(Some(env!("CARGO_PKG_VERSION").to_string()));
+        properties.set_platform(Some("Rust".to_string()));
+        properties.set_capabilities(Some(Capabilities::client()));
+        properties
+    }
+
+    pub fn from_table(table: &FieldTable) -> Result<PeerProperties, ArgumentErr> {
+        let mut properties = PeerProperties::new();
+        for (name, value) in table.iter() {
+            let key = match std::str::from_utf8(name.as_bytes()) {
+                Ok(key) => key,
+                Err(_) => {
+                    properties.others.insert(name.clone(), value.clone());
+                    continue;
+                }
+            };
+            match key {
+                "product" => properties.product = Some(read_table_string(key, value)?),
+                "version" => properties.version = Some(read_table_string(key, value)?),
+                "platform" => properties.platform = Some(read_table_string(key, value)?),
+                "cluster_name" => properties.cluster_name = Some(read_table_string(key, value)?),
+                "copyright" => properties.copyright = Some(read_table_string(key, value)?),
+                "information" => properties.information = Some(read_table_string(key, value)?),
+                "capabilities" => match value.kind() {
+                    FieldValueKind::FieldTable => properties.capabilities = Some(Capabilities::from_table(value.as_field_table())?),
+                    _ => return Err(ArgumentErr::InvalidType(key.to_string(), "a table"))
+                },
+                _ => {
+                    properties.others.insert(name.clone(), value.clone());
+                }
+            }
+        }
+        Ok(properties)
+    }
+
+    pub fn to_field_table(&self) -> Result<FieldTable, ArgumentErr> {
+        let known = [
+            ("product", &self.product),
+            ("version", &self.version),
+            ("platform", &self.platform),
+            ("cluster_name", &self.cluster_name),
+            ("copyright", &self.copyright),
+            ("information", &self.information)
+        ];
+        let mut table = FieldTable::new();
+        for (key, value) in known.iter() {
+            if let Some(value) = value {
+                insert_table_entry(&mut table, key, table_string(value)?)?;
+            }
+        }
+        if let Some(capabilities) = &self.capabilities {
+            insert_table_entry(&mut table, "capabilities", FieldValue::from_field_table(capabilities.to_field_table()?))?;
+        }
+        for (name, value) in self.others.iter() {
+            table.insert(name.clone(), value.clone());
+        }
+        Ok(table)
+    }
+
+    pub fn product(&self) -> Option<&str> {
+        self.product.as_deref()
+    }
+
+    pub fn set_product(&mut self, product: Option<String>) {
+        self.product = product;
+    }
+
+    pub fn version(&self) -> Option<&str> {
+        self.version.as_deref()
+    }
+
+    pub fn set_version(&mut self, version: Option<String>) {
+        self.version = version;
+    }
+
+    pub fn platform(&self) -> Option<&str> {
+        self.platform.as_deref()
+    }
+
+    pub fn set_platform(&mut self, platform: Option<String>) {
+        self.platform = platform;
+    }
+
+    pub fn cluster_name(&self) -> Option<&str> {
+        self.cluster_name.as_deref()
+    }
+
+    pub fn set_cluster_name(&mut self, cluster_name: Option<String>) {
+        self.cluster_name = cluster_name;
+    }
+
+    pub fn copyright(&self) -> Option<&str> {
+        self.copyright.as_deref()
+    }
+
+    pub fn set_copyright(&mut self, copyright: Option<String>) {
+        self.copyright = copyright;
+    }
+
+    pub fn information(&self) -> Option<&str> {
+        self.information.as_deref()
+    }
+
+    pub fn set_information(&mut self, information: Option<String>) {
+        self.information = information;
+    }
+
+    pub fn capabilities(&self) -> Option<&Capabilities> {
+        self.capabilities.as_ref()
+    }
+
+    pub fn set_capabilities(&mut self, capabilities: Option<Capabilities>) {
+        self.capabilities = capabilities;
+    }
+
+    pub fn others(&self) -> &FieldTable {
+        &self.others
+    }
+
+    pub fn set_other(&mut self, name: FieldName, value: FieldValue) {
+        self.others.insert(name, value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amqp_types::{FieldTable, FieldValue};
+    use crate::error::ArgumentErr;
+    use crate::peer_properties::{PeerProperties, Capabilities};
+    use crate::common::{insert_table_entry, table_string};
+
+    #[test]
+    fn test_server_properties() {
+        let mut capabilities = FieldTable::new();
+        insert_table_entry(&mut capabilities, "publisher_confirms", FieldValue::from_bool(true)).unwrap();
+        insert_table_entry(&mut capabilities, "basic.nack", FieldValue::from_bool(true)).unwrap();
+        insert_table_entry(&mut capabilities, "per_consumer_qos", FieldValue::from_bool(false)).unwrap();
+        insert_table_entry(&mut capabilities, "stream_filtering", FieldValue::from_bool(true)).unwrap();
+        let mut table = FieldTable::new();
+        insert_table_entry(&mut table, "product", table_string("RabbitMQ").unwrap()).unwrap();
+        insert_table_entry(&mut table, "version", table_string("3.12.0").unwrap()).unwrap();
+        insert_table_entry(&mut table, "cluster_name", table_string("rabbit@localhost").unwrap()).unwrap();
+        insert_table_entry(&mut table, "capabilities", FieldValue::from_field_table(capabilities)).unwrap();
+
+        let properties = PeerProperties::from_table(&table).unwrap();
+        assert_eq!(properties.product(), Some("RabbitMQ"));
+        assert_eq!(properties.cluster_name(), Some("rabbit@localhost"));
+        assert_eq!(properties.platform(), None);
+        let capabilities = properties.capabilities().unwrap();
+        assert_eq!(capabilities.publisher_confirms(), Some(true));
+        assert_eq!(capabilities.basic_nack(), Some(true));
+        assert_eq!(capabilities.per_consumer_qos(), Some(false));
+        assert_eq!(capabilities.direct_reply_to(), None);
+        assert_eq!(capabilities.others().iter().count(), 1);
+
+        let mut table = FieldTable::new();
+        insert_table_entry(&mut table, "product", FieldValue::from_i32(1)).unwrap();
+        match PeerProperties::from_table(&table) {
+            Err(ArgumentErr::InvalidType(key, _)) => assert_eq!(key, "product"),
+            _ => panic!("product must be a string")
+        }
+    }
+
+    #[test]
+    fn test_client_properties() {
+        let mut properties = PeerProperties::client();
+        properties.set_information(Some("integration tests".to_string()));
+        let rebuilt = PeerProperties::from_table(&properties.to_field_table().unwrap()).unwrap();
+        assert_eq!(rebuilt.product(), Some(env!("CARGO_PKG_NAME")));
+        assert_eq!(rebuilt.information(), Some("integration tests"));
+        let capabilities = rebuilt.capabilities().unwrap();
+        assert_eq!(capabilities.connection_blocked(), Capabilities::client().connection_blocked());
+        assert_eq!(capabilities.consumer_priorities(), None);
+    }
+}
@@ -0,0 +1,198 @@
+use amqp_types::{FieldTable, ShortStr, LongStr};
+use bytes::{BytesMut, BufMut};
+use crate::error::SaslErr;
+use crate::common::{parse_field_table, write_field_table, read_table_string, table_string, insert_table_entry};
+
+// Responses carried by Connection.StartOk and Connection.SecureOk for the
+// mechanisms RabbitMQ speaks out of the box.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mechanism {
+    // "\0user\0password", RFC 4616
+    Plain,
+    // LOGIN and PASSWORD as a field table without its length prefix
+    AmqPlain,
+    // the identity comes from the TLS client certificate
+    External
+}
+
+impl Mechanism {
+    pub fn from_name(name: &[u8]) -> Option<Mechanism> {
+        match name {
+            b"PLAIN" => Some(Mechanism::Plain),
+            b"AMQPLAIN" => Some(Mechanism::AmqPlain),
+            b"EXTERNAL" => Some(Mechanism::External),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Mechanism::Plain => "PLAIN",
+            Mechanism::AmqPlain => "AMQPLAIN",
+            Mechanism::External => "EXTERNAL"
+        }
+    }
+
+    // for ConnectionStartOk::set_mechanism
+    pub fn short_str(&self) -> ShortStr {
+        ShortStr::with_bytes(self.name().as_bytes()).unwrap()
+    }
+
+    pub fn response(&self, credentials: &Credentials) -> Result<LongStr, SaslErr> {
+        match self {
+            Mechanism::Plain => {
+                let password = credentials.password.as_ref().ok_or(SaslErr::MissingPassword)?;
+                let mut response = Vec::with_capacity(credentials.username.len() + password.len() + 2);
+                response.push(0);
+                response.extend_from_slice(credentials.username.as_bytes());
+                response.push(0);
+                response.extend_from_slice(password.as_bytes());
+                Ok(LongStr::with_bytes(&response)?)
+            }
+            Mechanism::AmqPlain => {
+                let password = credentials.password.as_ref().ok_or(SaslErr::MissingPassword)?;
+                let mut table = FieldTable::new();
+                let amqplain = |_| SaslErr::MalformedResponse("AMQPLAIN");
+                insert_table_entry(&mut table, "LOGIN", table_string(&credentials.username).map_err(amqplain)?).map_err(amqplain)?;
+                insert_table_entry(&mut table, "PASSWORD", table_string(password).map_err(amqplain)?).map_err(amqplain)?;
+                let mut data = BytesMut::new();
+                write_field_table(&table, &mut data).map_err(|_| SaslErr::MalformedResponse("AMQPLAIN"))?;
+                // the table goes out without its length
+                Ok(LongStr::with_bytes(&data[4..])?)
+            }
+            Mechanism::External => Ok(LongStr::with_bytes(credentials.username.as_bytes())?)
+        }
+    }
+
+    pub fn parse_response(&self, response: &LongStr) -> Result<Credentials, SaslErr> {
+        let response = response.as_bytes();
+        match self {
+            Mechanism::Plain => {
+                // the authorization identity in front is not used by brokers
+                let parts: Vec<&[u8]> = response.split(|c| *c == 0).collect();
+                match parts.as_slice() {
+                    [_, username, password] => Ok(Credentials::new(plain_string(username)?, plain_string(password)?)),
+                    _ => Err(SaslErr::MalformedResponse("PLAIN"))
+                }
+            }
+            Mechanism::AmqPlain => {
+                let mut data = BytesMut::with_capacity(response.len() + 4);
+                data.put_u32(response.len() as u32);
+                data.put_slice(response);
+                let table = match parse_field_table(&data) {
+                    Ok((remain, table)) if remain.is_empty() => table,
+                    _ => return Err(SaslErr::MalformedResponse("AMQPLAIN"))
+                };
+                let (mut username, mut password) = (None, None);
+                for (name, value) in table.iter() {
+                    match name.as_bytes() {
+                        b"LOGIN" => username = Some(read_table_string("LOGIN", value).map_err(|_| SaslErr::MalformedResponse("AMQPLAIN"))?),
+                        b"PASSWORD" => password = Some(read_table_string("PASSWORD", value).map_err(|_| SaslErr::MalformedResponse("AMQPLAIN"))?),
+                        _ => ()
+                    }
+                }
+                match (username, password) {
+                    (Some(username), Some(password)) => Ok(Credentials::new(username, password)),
+                    _ => Err(SaslErr::MalformedResponse("AMQPLAIN"))
+                }
+            }
+            Mechanism::External => {
+                let identity = String::from_utf8(response.to_vec()).map_err(|_| SaslErr::MalformedResponse("EXTERNAL"))?;
+                Ok(Credentials::external(identity))
+            }
+        }
+    }
+}
+
+fn plain_string(bytes: &[u8]) -> Result<String, SaslErr> {
+    String::from_utf8(bytes.to_vec()).map_err(|_| SaslErr::MalformedResponse("PLAIN"))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Credentials {
+    // the authorization identity for EXTERNAL, usually empty
+    username: String,
+    password: Option<String>
+}
+
+impl Credentials {
+    pub fn new(username: String, password: String) -> Self {
+        Credentials { username, password: Some(password) }
+    }
+
+    pub fn external(identity: String) -> Self {
+        Credentials { username: identity, password: None }
+    }
+
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
+    pub fn password(&self) -> Option<&str> {
+        self.password.as_deref()
+    }
+}
+
+// the first of `preferred` the server lists in Connection.Start
+pub fn select_mechanism(offered: &LongStr, preferred: &[Mechanism]) -> Option<Mechanism> {
+    preferred.iter().copied().find(|mechanism| {
+        offered.as_bytes().split(|c| *c == b' ').any(|name| name == mechanism.name().as_bytes())
+    })
+}
+
+// space separated list for ServerConfig::set_mechanisms
+pub fn mechanism_list(mechanisms: &[Mechanism]) -> LongStr {
+    let names: Vec<&str> = mechanisms.iter().map(|mechanism| mechanism.name()).collect();
+    LongStr::with_bytes(names.join(" ").as_bytes()).unwrap()
+}
+
+// server side, decode whatever the client answered with
+pub fn parse_response(mechanism: &ShortStr, response: &LongStr) -> Result<Credentials, SaslErr> {
+    match Mechanism::from_name(mechanism.as_bytes()) {
+        Some(mechanism) => mechanism.parse_response(response),
+        None => Err(SaslErr::UnknownMechanism(String::from_utf8_lossy(mechanism.as_bytes()).into_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use amqp_types::LongStr;
+    use crate::error::SaslErr;
+    use crate::sasl::{Mechanism, Credentials, select_mechanism, mechanism_list};
+
+    #[test]
+    fn test_responses() {
+        let credentials = Credentials::new(String::from("guest"), String::from("secret"));
+        let plain = Mechanism::Plain.response(&credentials).unwrap();
+        assert_eq!(plain.as_bytes(), &b"\x00guest\x00secret"[..]);
+
+        let amqplain = Mechanism::AmqPlain.response(&credentials).unwrap();
+        // 5LOGINS<u32>guest 8PASSWORDS<u32>secret, no table length in front
+        assert_eq!(amqplain.as_bytes().len(), 36);
+
+        for (mechanism, response) in [(Mechanism::Plain, plain), (Mechanism::AmqPlain, amqplain)].iter() {
+            assert_eq!(mechanism.parse_response(response).unwrap(), credentials);
+        }
+        let external = Credentials::external(String::new());
+        assert_eq!(Mechanism::External.parse_response(&Mechanism::External.response(&external).unwrap()).unwrap(), external);
+
+        match Mechanism::Plain.parse_response(&LongStr::with_bytes(b"guest").unwrap()) {
+            Err(SaslErr::MalformedResponse("PLAIN")) => (),
+            _ => panic!("PLAIN layout not checked")
+        }
+        match Mechanism::Plain.response(&external) {
+            Err(SaslErr::MissingPassword) => (),
+            _ => panic!("password required")
+        }
+    }
+
+    #[test]
+    fn test_select_mechanism() {
+        let offered = LongStr::with_bytes(b"AMQPLAIN PLAIN").unwrap();
+        assert_eq!(select_mechanism(&offered, &[Mechanism::External, Mechanism::Plain]), Some(Mechanism::Plain));
+        assert_eq!(select_mechanism(&offered, &[Mechanism::AmqPlain, Mechanism::Plain]), Some(Mechanism::AmqPlain));
+        assert_eq!(select_mechanism(&offered, &[Mechanism::External]), None);
+        assert_eq!(mechanism_list(&[Mechanism::Plain, Mechanism::External]).as_bytes(), &b"PLAIN EXTERNAL"[..]);
+    }
+}
@@ -0,0 +1,560 @@
+use amqp_types::{FieldValue, FieldTable, FieldName, LongStr, Decimal, FrameType};
+use amqp_types::basic_types::FieldValueKind;
+use amqp_types::frame::{Arguments, Class};
+use bytes::{Bytes, BytesMut};
+use nom::IResult;
+use nom::error::ErrorKind;
+use nom::number::complete::{be_i64, be_u32, be_i32, be_u16, be_i16, be_u8, be_i8, be_u64, be_f32, be_f64};
+use crate::error::{FrameDecodeErr, DecodeCause};
+use crate::common::{get_method_type, method_name};
+use crate::parse::{parse_arguments, parse_frame_payload, FRAME_PREFIX_LENGTH};
+use crate::spec::{method_fields, Domain};
+
+// Decoding into views of the frame buffer. Strings, byte arrays and tables
+// stay slices of the Bytes they were read from, to_* methods make owned
+// amqp_types values when a copy is really wanted.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SharedFieldValue {
+    Boolean(bool),
+    I8(i8),
+    U8(u8),
+    I16(i16),
+    U16(u16),
+    I32(i32),
+    U32(u32),
+    I64(i64),
+    U64(u64),
+    F32(f32),
+    F64(f64),
+    Timestamp(u64),
+    // scale, value
+    Decimal(u8, u32),
+    LongStr(Bytes),
+    ByteArray(Bytes),
+    FieldArray(Vec<SharedFieldValue>),
+    FieldTable(SharedFieldTable),
+    Void
+}
+
+impl SharedFieldValue {
+    pub fn to_field_value(&self) -> Result<FieldValue, amqp_types::error::Error> {
+        let value = match self {
+            SharedFieldValue::Boolean(value) => FieldValue::from_bool(*value),
+            SharedFieldValue::I8(value) => FieldValue::from_i8(*value),
+            SharedFieldValue::U8(value) => FieldValue::from_u8(*value),
+            SharedFieldValue::I16(value) => FieldValue::from_i16(*value),
+            SharedFieldValue::U16(value) => FieldValue::from_u16(*value),
+            SharedFieldValue::I32(value) => FieldValue::from_i32(*value),
+            SharedFieldValue::U32(value) => FieldValue::from_u32(*value),
+            SharedFieldValue::I64(value) => FieldValue::from_i64(*value),
+            SharedFieldValue::U64(value) => FieldValue::from_u64(*value),
+            SharedFieldValue::F32(value) => FieldValue::from_f32(*value),
+            SharedFieldValue::F64(value) => FieldValue::from_f64(*value),
+            SharedFieldValue::Timestamp(value) => FieldValue::from_timestamp(*value),
+            SharedFieldValue::Decimal(scale, value) => FieldValue::from_decimal(Decimal::new(*scale, *value)),
+            SharedFieldValue::LongStr(value) => FieldValue::from_long_string(LongStr::with_bytes(value)?),
+            SharedFieldValue::ByteArray(value) => FieldValue::from_bytes_array(LongStr::with_bytes(value)?),
+            SharedFieldValue::FieldArray(values) => {
+                let mut arr = Vec::with_capacity(values.len());
+                for value in values.iter() {
+                    arr.push(value.to_field_value()?);
+                }
+                FieldValue::from_field_array(arr)
+            }
+            SharedFieldValue::FieldTable(table) => FieldValue::from_field_table(table.to_field_table()?),
+            SharedFieldValue::Void => FieldValue::from_void()
+        };
+        Ok(value)
+    }
+}
+
+// entries in wire order, names are views like the values
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct SharedFieldTable {
+    entries: Vec<(Bytes, SharedFieldValue)>
+}
+
+impl SharedFieldTable {
+    pub fn get(&self, name: &[u8]) -> Option<&SharedFieldValue> {
+        self.entries.iter().find(|(key, _)| &key[..] == name).map(|(_, value)| value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &(Bytes, SharedFieldValue)> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn to_field_table(&self) -> Result<FieldTable, amqp_types::error::Error> {
+        let mut table = FieldTable::new();
+        for (name, value) in self.entries.iter() {
+            table.insert(FieldName::with_bytes(name)?, value.to_field_value()?);
+        }
+        Ok(table)
+    }
+}
+
+// what is left of `buffer` once the parser stopped at `remain`
+fn advance(buffer: &Bytes, remain: &[u8]) -> Bytes {
+    buffer.slice(buffer.len() - remain.len()..)
+}
+
+// `length` bytes of `buffer` following a length prefix that ended at `remain`
+fn take_shared(buffer: &Bytes, remain: &[u8], length: usize) -> Result<(Bytes, Bytes), FrameDecodeErr> {
+    if remain.len() < length {
+        return Err(FrameDecodeErr::syntax(&buffer[..], DecodeCause::UnexpectedEnd));
+    }
+    let start = buffer.len() - remain.len();
+    Ok((buffer.slice(start + length..), buffer.slice(start..start + length)))
+}
+
+pub fn parse_shared_short_string(buffer: &Bytes) -> Result<(Bytes, Bytes), FrameDecodeErr> {
+    let (remain, length) = match be_u8::<(_, ErrorKind)>(&buffer[..]) {
+        Ok(ret) => ret,
+        Err(e) => return Err(FrameDecodeErr::syntax(&buffer[..], e))
+    };
+    take_shared(buffer, remain, length as usize)
+}
+
+pub fn parse_shared_long_string(buffer: &Bytes) -> Result<(Bytes, Bytes), FrameDecodeErr> {
+    let (remain, length) = match be_u32::<(_, ErrorKind)>(&buffer[..]) {
+        Ok(ret) => ret,
+        Err(e) => return Err(FrameDecodeErr::syntax(&buffer[..], e))
+    };
+    take_shared(buffer, remain, length as usize)
+}
+
+pub fn parse_shared_field_array(buffer: &Bytes) -> Result<(Bytes, Vec<SharedFieldValue>), FrameDecodeErr> {
+    let (remain, data) = parse_shared_long_string(buffer)?;
+    let mut arr = Vec::new();
+
+    // errors inside the array are positioned from the end of the array
+    let mut data = data;
+    while !data.is_empty() {
+        let (next, value) = parse_shared_field_value(&data).map_err(|e| e.behind(&remain))?;
+        arr.push(value);
+        data = next;
+    }
+    Ok((remain, arr))
+}
+
+pub fn parse_shared_field_table(buffer: &Bytes) -> Result<(Bytes, SharedFieldTable), FrameDecodeErr> {
+    let (remain, data) = parse_shared_long_string(buffer)?;
+    let mut entries = Vec::new();
+
+    let mut data = data;
+    while !data.is_empty() {
+        let (next, name) = parse_shared_short_string(&data).map_err(|e| e.behind(&remain))?;
+        let (next, value) = parse_shared_field_value(&next).map_err(|e| e.behind(&remain))?;
+        entries.push((name, value));
+        data = next;
+    }
+    Ok((remain, SharedFieldTable { entries }))
+}
+
+pub fn parse_shared_field_value(buffer: &Bytes) -> Result<(Bytes, SharedFieldValue), FrameDecodeErr> {
+    let (data, field_value_type) = match be_u8::<(_, ErrorKind)>(&buffer[..]) {
+        Ok(ret) => ret,
+        Err(e) => return Err(FrameDecodeErr::syntax(&buffer[..], e))
+    };
+    let parsed: IResult<&[u8], SharedFieldValue, (&[u8], ErrorKind)> = match FieldValueKind::from(field_value_type) {
+        FieldValueKind::Boolean => be_u8(data).map(|(remain, value)| (remain, SharedFieldValue::Boolean(value != 0))),
+        FieldValueKind::I8 => be_i8(data).map(|(remain, value)| (remain, SharedFieldValue::I8(value))),
+        FieldValueKind::U8 => be_u8(data).map(|(remain, value)| (remain, SharedFieldValue::U8(value))),
+        FieldValueKind::I16 => be_i16(data).map(|(remain, value)| (remain, SharedFieldValue::I16(value))),
+        FieldValueKind::U16 => be_u16(data).map(|(remain, value)| (remain, SharedFieldValue::U16(value))),
+        FieldValueKind::I32 => be_i32(data).map(|(remain, value)| (remain, SharedFieldValue::I32(value))),
+        FieldValueKind::U32 => be_u32(data).map(|(remain, value)| (remain, SharedFieldValue::U32(value))),
+        FieldValueKind::I64 => be_i64(data).map(|(remain, value)| (remain, SharedFieldValue::I64(value))),
+        FieldValueKind::U64 => be_u64(data).map(|(remain, value)| (remain, SharedFieldValue::U64(value))),
+        FieldValueKind::F32 => be_f32(data).map(|(remain, value)| (remain, SharedFieldValue::F32(value))),
+        FieldValueKind::F64 => be_f64(data).map(|(remain, value)| (remain, SharedFieldValue::F64(value))),
+        FieldValueKind::Timestamp => be_u64(data).map(|(remain, value)| (remain, SharedFieldValue::Timestamp(value))),
+        FieldValueKind::Decimal => {
+            be_u8(data).and_then(|(remain, scale)| {
+                be_u32(remain).map(|(remain, value)| (remain, SharedFieldValue::Decimal(scale, value)))
+            })
+        }
+        FieldValueKind::Void => Ok((data, SharedFieldValue::Void)),
+        FieldValueKind::LongStr => {
+            let (remain, value) = parse_shared_long_string(&advance(buffer, data))?;
+            return Ok((remain, SharedFieldValue::LongStr(value)));
+        }
+        FieldValueKind::ByteArray => {
+            let (remain, value) = parse_shared_long_string(&advance(buffer, data))?;
+            return Ok((remain, SharedFieldValue::ByteArray(value)));
+        }
+        FieldValueKind::FieldArray => {
+            let (remain, value) = parse_shared_field_array(&advance(buffer, data))?;
+            return Ok((remain, SharedFieldValue::FieldArray(value)));
+        }
+        FieldValueKind::FieldTable => {
+            let (remain, value) = parse_shared_field_table(&advance(buffer, data))?;
+            return Ok((remain, SharedFieldValue::FieldTable(value)));
+        }
+        _ => return Err(FrameDecodeErr::syntax(&buffer[..], DecodeCause::UnknownFieldValueType(field_value_type)))
+    };
+    match parsed {
+        Ok((remain, value)) => Ok((advance(buffer, remain), value)),
+        Err(e) => Err(FrameDecodeErr::syntax(data, e))
+    }
+}
+
+// a fixed size number read from the front of buffer
+fn take_number<'a, T>(buffer: &'a Bytes, field: &'static str, parsed: IResult<&'a [u8], T, (&'a [u8], ErrorKind)>) -> Result<(Bytes, T), FrameDecodeErr> {
+    match parsed {
+        Ok((remain, value)) => Ok((advance(buffer, remain), value)),
+        Err(e) => Err(FrameDecodeErr::field(field, &buffer[..], e))
+    }
+}
+
+// SharedBasicProperties and parse_shared_basic_properties, see build.rs
+include!(concat!(env!("OUT_DIR"), "/shared_properties.rs"));
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SharedArgument {
+    Octet(u8),
+    Short(u16),
+    Long(u32),
+    LongLong(u64),
+    Bit(bool),
+    ShortStr(Bytes),
+    LongStr(Bytes),
+    Table(SharedFieldTable)
+}
+
+// method arguments in wire order, laid out by spec::method_fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedMethod {
+    channel: u16,
+    class_id: u16,
+    method_id: u16,
+    arguments: Bytes,
+    fields: Vec<(&'static str, SharedArgument)>
+}
+
+impl SharedMethod {
+    pub fn channel(&self) -> u16 {
+        self.channel
+    }
+
+    pub fn class_id(&self) -> u16 {
+        self.class_id
+    }
+
+    pub fn method_id(&self) -> u16 {
+        self.method_id
+    }
+
+    pub fn name(&self) -> Option<&'static str> {
+        method_name(self.class_id, self.method_id)
+    }
+
+    // the encoded arguments, for forwarding
+    pub fn arguments(&self) -> &Bytes {
+        &self.arguments
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = &(&'static str, SharedArgument)> {
+        self.fields.iter()
+    }
+
+    pub fn field(&self, name: &str) -> Option<&SharedArgument> {
+        self.fields.iter().find(|(field, _)| *field == name).map(|(_, value)| value)
+    }
+
+    pub fn short_str(&self, name: &str) -> Option<&Bytes> {
+        match self.field(name) {
+            Some(SharedArgument::ShortStr(value)) => Some(value),
+            _ => None
+        }
+    }
+
+    pub fn table(&self, name: &str) -> Option<&SharedFieldTable> {
+        match self.field(name) {
+            Some(SharedArgument::Table(value)) => Some(value),
+            _ => None
+        }
+    }
+
+    pub fn exchange(&self) -> Option<&Bytes> {
+        self.short_str("exchange")
+    }
+
+    pub fn routing_key(&self) -> Option<&Bytes> {
+        self.short_str("routing_key")
+    }
+
+    pub fn queue(&self) -> Option<&Bytes> {
+        self.short_str("queue")
+    }
+
+    // the owned amqp-types form, parsed again from the arguments
+    pub fn to_arguments(&self) -> Result<Arguments, FrameDecodeErr> {
+        match get_method_type(Class::from(self.class_id), self.method_id) {
+            Some(method) => parse_arguments(method, &self.arguments),
+            None => Err(FrameDecodeErr::UnknownMethodType(self.channel, self.class_id, self.method_id))
+        }
+    }
+}
+
+fn parse_shared_arguments(buffer: &Bytes, layout: &'static [(&'static str, Domain)]) -> Result<Vec<(&'static str, SharedArgument)>, FrameDecodeErr> {
+    let mut buffer = buffer.clone();
+    let mut fields = Vec::with_capacity(layout.len());
+    // octet holding the current run of bits and the next bit to read
+    let mut bits: Option<(u8, u8)> = None;
+    for &(name, domain) in layout.iter() {
+        if domain != Domain::Bit {
+            bits = None;
+        }
+        let (remain, value) = match domain {
+            Domain::Octet => take_number(&buffer, name, be_u8(&buffer[..])).map(|(remain, value)| (remain, SharedArgument::Octet(value)))?,
+            Domain::Short => take_number(&buffer, name, be_u16(&buffer[..])).map(|(remain, value)| (remain, SharedArgument::Short(value)))?,
+            Domain::Long => take_number(&buffer, name, be_u32(&buffer[..])).map(|(remain, value)| (remain, SharedArgument::Long(value)))?,
+            Domain::LongLong => take_number(&buffer, name, be_u64(&buffer[..])).map(|(remain, value)| (remain, SharedArgument::LongLong(value)))?,
+            Domain::Bit => match bits {
+                Some((octet, bit)) if bit < 8 => {
+                    bits = Some((octet, bit + 1));
+                    (buffer.clone(), SharedArgument::Bit(octet & (1 << bit) != 0))
+                }
+                // a fresh octet of bits, this field is its lowest one
+                _ => {
+                    let (remain, octet) = take_number(&buffer, name, be_u8(&buffer[..]))?;
+                    bits = Some((octet, 1));
+                    (remain, SharedArgument::Bit(octet & 1 != 0))
+                }
+            },
+            Domain::ShortStr => parse_shared_short_string(&buffer).map(|(remain, value)| (remain, SharedArgument::ShortStr(value)))
+                .map_err(|e| e.in_field(name, &buffer))?,
+            Domain::LongStr => parse_shared_long_string(&buffer).map(|(remain, value)| (remain, SharedArgument::LongStr(value)))
+                .map_err(|e| e.in_field(name, &buffer))?,
+            Domain::Table => parse_shared_field_table(&buffer).map(|(remain, value)| (remain, SharedArgument::Table(value)))
+                .map_err(|e| e.in_field(name, &buffer))?
+        };
+        fields.push((name, value));
+        buffer = remain;
+    }
+    Ok(fields)
+}
+
+pub fn parse_shared_method(channel_id: u16, payload: &Bytes) -> Result<SharedMethod, FrameDecodeErr> {
+    let (remain, class_id) = match be_u16::<(_, ErrorKind)>(&payload[..]) {
+        Ok(ret) => ret,
+        Err(e) => return Err(FrameDecodeErr::field("class_id", &payload[..], e).in_frame(channel_id, None, None, &payload[..]))
+    };
+    let (remain, method_id) = match be_u16::<(_, ErrorKind)>(remain) {
+        Ok(ret) => ret,
+        Err(e) => return Err(FrameDecodeErr::field("method_id", remain, e).in_frame(channel_id, Some(class_id), None, &payload[..]))
+    };
+    if let Class::Unknown = Class::from(class_id) {
+        return Err(FrameDecodeErr::UnknownClassType(channel_id, class_id));
+    }
+    let layout = match method_fields(class_id, method_id) {
+        Some(layout) => layout,
+        None => return Err(FrameDecodeErr::UnknownMethodType(channel_id, class_id, method_id))
+    };
+    let arguments = advance(payload, remain);
+    let fields = match parse_shared_arguments(&arguments, layout) {
+        Ok(fields) => fields,
+        Err(e) => return Err(e.in_frame(channel_id, Some(class_id), Some(method_id), &payload[..]))
+    };
+    Ok(SharedMethod { channel: channel_id, class_id, method_id, arguments, fields })
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SharedContentHeader {
+    class_id: u16,
+    weight: u16,
+    body_size: u64,
+    properties: SharedBasicProperties
+}
+
+impl SharedContentHeader {
+    pub fn class_id(&self) -> u16 {
+        self.class_id
+    }
+
+    pub fn weight(&self) -> u16 {
+        self.weight
+    }
+
+    pub fn body_size(&self) -> u64 {
+        self.body_size
+    }
+
+    pub fn properties(&self) -> &SharedBasicProperties {
+        &self.properties
+    }
+}
+
+pub fn parse_shared_content_header(channel_id: u16, payload: &Bytes) -> Result<SharedContentHeader, FrameDecodeErr> {
+    let (remain, class_id) = match be_u16::<(_, ErrorKind)>(&payload[..]) {
+        Ok(ret) => ret,
+        Err(e) => return Err(FrameDecodeErr::field("class_id", &payload[..], e).in_frame(channel_id, None, None, &payload[..]))
+    };
+    // only the basic class carries content
+    if class_id != 60 {
+        return Err(FrameDecodeErr::UnknownClassType(channel_id, class_id));
+    }
+    let (remain, weight) = match be_u16::<(_, ErrorKind)>(remain) {
+        Ok(ret) => ret,
+        Err(e) => return Err(FrameDecodeErr::field("weight", remain, e).in_frame(channel_id, Some(class_id), None, &payload[..]))
+    };
+    let (remain, body_size) = match be_u64::<(_, ErrorKind)>(remain) {
+        Ok(ret) => ret,
+        Err(e) => return Err(FrameDecodeErr::field("body_size", remain, e).in_frame(channel_id, Some(class_id), None, &payload[..]))
+    };
+    let properties = match parse_shared_basic_properties(&advance(payload, remain)) {
+        Ok(properties) => properties,
+        Err(e) => return Err(e.in_frame(channel_id, Some(class_id), None, &payload[..]))
+    };
+    Ok(SharedContentHeader { class_id, weight, body_size, properties })
+}
+
+// a frame whose strings, tables and body point into the buffer it was read from
+#[derive(Debug, Clone, PartialEq)]
+pub enum SharedFrame {
+    Method(u16, SharedMethod),
+    ContentHeader(u16, SharedContentHeader),
+    ContentBody(u16, Bytes),
+    Heartbeat
+}
+
+// take a frame from the front of the buffer, a frame that fails to parse
+// once it is complete is consumed with the error
+pub fn split_shared_frame(src: &mut BytesMut) -> Result<SharedFrame, FrameDecodeErr> {
+    let frame_type = match src.first() {
+        Some(frame_type) => FrameType::from(*frame_type),
+        None => return Err(FrameDecodeErr::Incomplete)
+    };
+    if let FrameType::UNKNOWN = frame_type {
+        return Err(FrameDecodeErr::UnknowFrameType);
+    }
+    let (channel_id, payload_length, frame_length) = {
+        let (channel_id, payload, frame_length) = parse_frame_payload(&src[..])?;
+        (channel_id, payload.len(), frame_length as usize)
+    };
+    let data = src.split_to(frame_length).freeze();
+    let payload = data.slice(FRAME_PREFIX_LENGTH as usize..FRAME_PREFIX_LENGTH as usize + payload_length);
+
+    match frame_type {
+        FrameType::METHOD => Ok(SharedFrame::Method(channel_id, parse_shared_method(channel_id, &payload)?)),
+        FrameType::HEADER => Ok(SharedFrame::ContentHeader(channel_id, parse_shared_content_header(channel_id, &payload)?)),
+        FrameType::BODY => Ok(SharedFrame::ContentBody(channel_id, payload)),
+        FrameType::HEARTBEAT => {
+            // heartbeats belong to the connection and carry nothing
+            if channel_id != 0 || !payload.is_empty() {
+                return Err(FrameDecodeErr::InvalidHeartbeatFrame);
+            }
+            Ok(SharedFrame::Heartbeat)
+        }
+        FrameType::UNKNOWN => Err(FrameDecodeErr::UnknowFrameType)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shared::{split_shared_frame, SharedFrame, SharedFieldValue, SharedArgument, SharedMethod};
+    use crate::frame_codec::{SharedFrameCodec, SharedDecodedFrame};
+    use crate::error::FrameDecodeErr;
+    use amqp_types::frame::Arguments;
+    use tokio_util::codec::Decoder;
+    use bytes::{Bytes, BytesMut};
+
+    #[test]
+    fn test_shared_content_header() {
+        // content_type "text/plain" and headers {"k": S "value"}
+        let mut src = BytesMut::from(&[0x02, 0x00, 0x01, 0x00, 0x00, 0x00, 0x29,
+            0x00, 0x3c, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x05, 0xa0, 0x00][..]);
+        src.extend_from_slice(b"\x0atext/plain");
+        src.extend_from_slice(b"\x00\x00\x00\x0c\x01kS\x00\x00\x00\x05value");
+        src.extend_from_slice(&[0xce]);
+
+        let frame_start = src.as_ptr() as usize;
+        let header = match split_shared_frame(&mut src).unwrap() {
+            SharedFrame::ContentHeader(1, header) => header,
+            _ => panic!("content header on channel 1 expected")
+        };
+        assert_eq!(header.body_size(), 5);
+        assert!(src.is_empty());
+
+        let content_type = header.properties().content_type().unwrap();
+        assert_eq!(&content_type[..], b"text/plain");
+        // a view into the frame, not a copy
+        assert_eq!(content_type.as_ptr() as usize, frame_start + 22);
+        match header.properties().headers().unwrap().get(b"k") {
+            Some(SharedFieldValue::LongStr(value)) => assert_eq!(&value[..], b"value"),
+            _ => panic!("header k missing")
+        }
+        let owned = header.properties().to_basic_properties().unwrap();
+        assert_eq!(owned.content_type().unwrap().as_bytes(), b"text/plain");
+    }
+
+    #[test]
+    fn test_shared_frame_codec() {
+        let mut src = BytesMut::from(&b"AMQP\x00\x00\x09\x01"[..]);
+        // connection.start with server_properties {"product": S "amq"}
+        src.extend_from_slice(&[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x0a, 0x00, 0x0a, 0x00, 0x09]);
+        src.extend_from_slice(b"\x00\x00\x00\x10\x07productS\x00\x00\x00\x03amq");
+        src.extend_from_slice(b"\x00\x00\x00\x05PLAIN\x00\x00\x00\x05en_US\xce");
+        // basic.deliver on channel 1: consumer tag "ctag", delivery tag 1,
+        // redelivered, exchange "ex", routing key "rk"
+        src.extend_from_slice(&[0x01, 0x00, 0x01, 0x00, 0x00, 0x00, 0x18, 0x00, 0x3c, 0x00, 0x3c]);
+        src.extend_from_slice(b"\x04ctag\x00\x00\x00\x00\x00\x00\x00\x01\x01\x02ex\x02rk\xce");
+        let buffer = src.as_ptr() as usize..src.as_ptr() as usize + src.len();
+
+        let mut codec = SharedFrameCodec::new();
+        match codec.decode(&mut src) {
+            Ok(Some(SharedDecodedFrame::ProtocolHeader(_))) => {},
+            _ => panic!("protocol header expected")
+        }
+        match codec.decode(&mut src) {
+            Ok(Some(SharedDecodedFrame::Frame(SharedFrame::Method(0, start)))) => {
+                assert_eq!(start.name(), Some("connection.start"));
+                match start.table("server_properties").unwrap().get(b"product") {
+                    Some(SharedFieldValue::LongStr(product)) => {
+                        assert_eq!(&product[..], b"amq");
+                        assert!(buffer.contains(&(product.as_ptr() as usize)));
+                    }
+                    _ => panic!("product missing")
+                }
+                assert_eq!(start.field("mechanisms"), Some(&SharedArgument::LongStr(Bytes::from_static(b"PLAIN"))));
+            }
+            _ => panic!("connection.start expected")
+        }
+        match codec.decode(&mut src) {
+            Ok(Some(SharedDecodedFrame::Frame(SharedFrame::Method(1, deliver)))) => {
+                assert_eq!(deliver.channel(), 1);
+                let consumer_tag = deliver.short_str("consumer_tag").unwrap();
+                assert_eq!(&consumer_tag[..], b"ctag");
+                // a view into the read buffer, not a copy
+                assert!(buffer.contains(&(consumer_tag.as_ptr() as usize)));
+                assert_eq!(&deliver.exchange().unwrap()[..], b"ex");
+                assert_eq!(&deliver.routing_key().unwrap()[..], b"rk");
+                assert_eq!(deliver.field("delivery_tag"), Some(&SharedArgument::LongLong(1)));
+                assert_eq!(deliver.field("redelivered"), Some(&SharedArgument::Bit(true)));
+                match deliver.to_arguments().unwrap() {
+                    Arguments::BasicDeliver(args) => assert_eq!(args.routing_key().as_bytes(), b"rk"),
+                    _ => panic!("basic.deliver arguments expected")
+                }
+            }
+            _ => panic!("basic.deliver expected")
+        }
+        assert!(src.is_empty());
+        assert!(codec.decode(&mut src).unwrap().is_none());
+
+        // errors from the owned form name the frame's channel
+        let unknown = SharedMethod { channel: 3, class_id: 60, method_id: 99, arguments: Bytes::new(), fields: Vec::new() };
+        match unknown.to_arguments() {
+            Err(FrameDecodeErr::UnknownMethodType(3, 60, 99)) => {},
+            _ => panic!("unknown method not reported on its channel")
+        }
+    }
+}
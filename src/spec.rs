@@ -0,0 +1,18 @@
+// Argument layout of every method, in wire order, generated from the spec.
+// Reserved fields carry the names they had before 0-9-1 reserved them.
+
+// how a field is encoded on the wire
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Domain {
+    Octet,
+    Short,
+    Long,
+    LongLong,
+    // consecutive bits share octets, lowest bit first
+    Bit,
+    ShortStr,
+    LongStr,
+    Table
+}
+
+include!(concat!(env!("OUT_DIR"), "/method_fields.rs"));
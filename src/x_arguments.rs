@@ -0,0 +1,457 @@
+use std::convert::TryFrom;
+use amqp_types::{FieldTable, FieldValue, FieldName};
+use amqp_types::basic_types::FieldValueKind;
+use crate::error::ArgumentErr;
+use crate::common::{read_table_string, read_table_bool, table_string, insert_table_entry};
+
+// Typed view over the argument tables of queue.declare, exchange.declare and
+// basic.consume. Well known keys are checked against the type the broker
+// expects, everything else is kept untouched in `others`.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Overflow {
+    DropHead,
+    RejectPublish,
+    RejectPublishDlx
+}
+
+impl Overflow {
+    pub fn from_name(name: &str) -> Option<Overflow> {
+        match name {
+            "drop-head" => Some(Overflow::DropHead),
+            "reject-publish" => Some(Overflow::RejectPublish),
+            "reject-publish-dlx" => Some(Overflow::RejectPublishDlx),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Overflow::DropHead => "drop-head",
+            Overflow::RejectPublish => "reject-publish",
+            Overflow::RejectPublishDlx => "reject-publish-dlx"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueueType {
+    Classic,
+    Quorum,
+    Stream
+}
+
+impl QueueType {
+    pub fn from_name(name: &str) -> Option<QueueType> {
+        match name {
+            "classic" => Some(QueueType::Classic),
+            "quorum" => Some(QueueType::Quorum),
+            "stream" => Some(QueueType::Stream),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            QueueType::Classic => "classic",
+            QueueType::Quorum => "quorum",
+            QueueType::Stream => "stream"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum QueueMode {
+    Default,
+    Lazy
+}
+
+impl QueueMode {
+    pub fn from_name(name: &str) -> Option<QueueMode> {
+        match name {
+            "default" => Some(QueueMode::Default),
+            "lazy" => Some(QueueMode::Lazy),
+            _ => None
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            QueueMode::Default => "default",
+            QueueMode::Lazy => "lazy"
+        }
+    }
+}
+
+// where a stream consumer starts reading
+#[derive(Debug, Clone, PartialEq)]
+pub enum StreamOffset {
+    First,
+    Last,
+    Next,
+    Offset(u64),
+    // seconds since the epoch
+    Timestamp(u64),
+    // relative to now, a number followed by one of Y M D h m s, like "7D"
+    Interval(String)
+}
+
+impl StreamOffset {
+    fn from_name(name: &str) -> Option<StreamOffset> {
+        match name {
+            "first" => Some(StreamOffset::First),
+            "last" => Some(StreamOffset::Last),
+            "next" => Some(StreamOffset::Next),
+            _ => {
+                let (number, unit) = name.split_at(name.len().saturating_sub(1));
+                let valid = !number.is_empty() && number.bytes().all(|c| c.is_ascii_digit())
+                    && ["Y", "M", "D", "h", "m", "s"].contains(&unit);
+                if valid { Some(StreamOffset::Interval(name.to_string())) } else { None }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct XArguments {
+    // milliseconds
+    message_ttl: Option<u32>,
+    // milliseconds
+    expires: Option<u32>,
+    max_length: Option<u64>,
+    max_length_bytes: Option<u64>,
+    overflow: Option<Overflow>,
+    dead_letter_exchange: Option<String>,
+    dead_letter_routing_key: Option<String>,
+    max_priority: Option<u8>,
+    queue_type: Option<QueueType>,
+    queue_mode: Option<QueueMode>,
+    single_active_consumer: Option<bool>,
+    // consumer priority of basic.consume
+    priority: Option<i32>,
+    stream_offset: Option<StreamOffset>,
+    // exchange.declare
+    alternate_exchange: Option<String>,
+    others: FieldTable
+}
+
+impl XArguments {
+    pub fn new() -> XArguments {
+        XArguments::default()
+    }
+
+    // fails on the first well known key holding the wrong type
+    pub fn from_table(table: &FieldTable) -> Result<XArguments, ArgumentErr> {
+        let mut args = XArguments::new();
+        for (name, value) in table.iter() {
+            let key = match std::str::from_utf8(name.as_bytes()) {
+                Ok(key) => key,
+                Err(_) => {
+                    args.others.insert(name.clone(), value.clone());
+                    continue;
+                }
+            };
+            match key {
+                "x-message-ttl" => args.message_ttl = Some(read_number(key, value)?),
+                "x-expires" => args.expires = Some(read_number(key, value)?),
+                "x-max-length" => args.max_length = Some(read_number(key, value)?),
+                "x-max-length-bytes" => args.max_length_bytes = Some(read_number(key, value)?),
+                "x-overflow" => {
+                    let name = read_table_string(key, value)?;
+                    args.overflow = Some(Overflow::from_name(&name).ok_or_else(|| ArgumentErr::InvalidValue(key.to_string(), name))?);
+                }
+                "x-dead-letter-exchange" => args.dead_letter_exchange = Some(read_table_string(key, value)?),
+                "x-dead-letter-routing-key" => args.dead_letter_routing_key = Some(read_table_string(key, value)?),
+                "x-max-priority" => args.max_priority = Some(read_number(key, value)?),
+                "x-queue-type" => {
+                    let name = read_table_string(key, value)?;
+                    args.queue_type = Some(QueueType::from_name(&name).ok_or_else(|| ArgumentErr::InvalidValue(key.to_string(), name))?);
+                }
+                "x-queue-mode" => {
+                    let name = read_table_string(key, value)?;
+                    args.queue_mode = Some(QueueMode::from_name(&name).ok_or_else(|| ArgumentErr::InvalidValue(key.to_string(), name))?);
+                }
+                "x-single-active-consumer" => args.single_active_consumer = Some(read_table_bool(key, value)?),
+                "x-priority" => args.priority = Some(read_number(key, value)?),
+                "x-stream-offset" => args.stream_offset = Some(read_stream_offset(key, value)?),
+                "alternate-exchange" => args.alternate_exchange = Some(read_table_string(key, value)?),
+                _ => {
+                    args.others.insert(name.clone(), value.clone());
+                }
+            }
+        }
+        Ok(args)
+    }
+
+    // well known keys first, then the others
+    pub fn to_field_table(&self) -> Result<FieldTable, ArgumentErr> {
+        let mut table = FieldTable::new();
+        if let Some(ttl) = self.message_ttl {
+            insert_table_entry(&mut table, "x-message-ttl", FieldValue::from_i64(ttl as i64))?;
+        }
+        if let Some(expires) = self.expires {
+            insert_table_entry(&mut table, "x-expires", FieldValue::from_i64(expires as i64))?;
+        }
+        if let Some(max_length) = self.max_length {
+            insert_table_entry(&mut table, "x-max-length", FieldValue::from_i64(to_i64("x-max-length", max_length)?))?;
+        }
+        if let Some(max_length_bytes) = self.max_length_bytes {
+            insert_table_entry(&mut table, "x-max-length-bytes", FieldValue::from_i64(to_i64("x-max-length-bytes", max_length_bytes)?))?;
+        }
+        if let Some(overflow) = self.overflow {
+            insert_table_entry(&mut table, "x-overflow", table_string(overflow.name())?)?;
+        }
+        if let Some(exchange) = &self.dead_letter_exchange {
+            insert_table_entry(&mut table, "x-dead-letter-exchange", table_string(exchange)?)?;
+        }
+        if let Some(routing_key) = &self.dead_letter_routing_key {
+            insert_table_entry(&mut table, "x-dead-letter-routing-key", table_string(routing_key)?)?;
+        }
+        if let Some(max_priority) = self.max_priority {
+            insert_table_entry(&mut table, "x-max-priority", FieldValue::from_i32(max_priority as i32))?;
+        }
+        if let Some(queue_type) = self.queue_type {
+            insert_table_entry(&mut table, "x-queue-type", table_string(queue_type.name())?)?;
+        }
+        if let Some(queue_mode) = self.queue_mode {
+            insert_table_entry(&mut table, "x-queue-mode", table_string(queue_mode.name())?)?;
+        }
+        if let Some(single_active_consumer) = self.single_active_consumer {
+            insert_table_entry(&mut table, "x-single-active-consumer", FieldValue::from_bool(single_active_consumer))?;
+        }
+        if let Some(priority) = self.priority {
+            insert_table_entry(&mut table, "x-priority", FieldValue::from_i32(priority))?;
+        }
+        if let Some(offset) = &self.stream_offset {
+            let value = match offset {
+                StreamOffset::First => table_string("first")?,
+                StreamOffset::Last => table_string("last")?,
+                StreamOffset::Next => table_string("next")?,
+                StreamOffset::Offset(offset) => FieldValue::from_i64(to_i64("x-stream-offset", *offset)?),
+                StreamOffset::Timestamp(seconds) => FieldValue::from_timestamp(*seconds),
+                StreamOffset::Interval(interval) => table_string(interval)?
+            };
+            insert_table_entry(&mut table, "x-stream-offset", value)?;
+        }
+        if let Some(exchange) = &self.alternate_exchange {
+            insert_table_entry(&mut table, "alternate-exchange", table_string(exchange)?)?;
+        }
+        for (name, value) in self.others.iter() {
+            table.insert(name.clone(), value.clone());
+        }
+        Ok(table)
+    }
+
+    pub fn message_ttl(&self) -> Option<u32> {
+        self.message_ttl
+    }
+
+    pub fn set_message_ttl(&mut self, message_ttl: Option<u32>) {
+        self.message_ttl = message_ttl;
+    }
+
+    pub fn expires(&self) -> Option<u32> {
+        self.expires
+    }
+
+    pub fn set_expires(&mut self, expires: Option<u32>) {
+        self.expires = expires;
+    }
+
+    pub fn max_length(&self) -> Option<u64> {
+        self.max_length
+    }
+
+    pub fn set_max_length(&mut self, max_length: Option<u64>) {
+        self.max_length = max_length;
+    }
+
+    pub fn max_length_bytes(&self) -> Option<u64> {
+        self.max_length_bytes
+    }
+
+    pub fn set_max_length_bytes(&mut self, max_length_bytes: Option<u64>) {
+        self.max_length_bytes = max_length_bytes;
+    }
+
+    pub fn overflow(&self) -> Option<Overflow> {
+        self.overflow
+    }
+
+    pub fn set_overflow(&mut self, overflow: Option<Overflow>) {
+        self.overflow = overflow;
+    }
+
+    pub fn dead_letter_exchange(&self) -> Option<&str> {
+        self.dead_letter_exchange.as_deref()
+    }
+
+    pub fn set_dead_letter_exchange(&mut self, dead_letter_exchange: Option<String>) {
+        self.dead_letter_exchange = dead_letter_exchange;
+    }
+
+    pub fn dead_letter_routing_key(&self) -> Option<&str> {
+        self.dead_letter_routing_key.as_deref()
+    }
+
+    pub fn set_dead_letter_routing_key(&mut self, dead_letter_routing_key: Option<String>) {
+        self.dead_letter_routing_key = dead_letter_routing_key;
+    }
+
+    pub fn max_priority(&self) -> Option<u8> {
+        self.max_priority
+    }
+
+    pub fn set_max_priority(&mut self, max_priority: Option<u8>) {
+        self.max_priority = max_priority;
+    }
+
+    pub fn queue_type(&self) -> Option<QueueType> {
+        self.queue_type
+    }
+
+    pub fn set_queue_type(&mut self, queue_type: Option<QueueType>) {
+        self.queue_type = queue_type;
+    }
+
+    pub fn queue_mode(&self) -> Option<QueueMode> {
+        self.queue_mode
+    }
+
+    pub fn set_queue_mode(&mut self, queue_mode: Option<QueueMode>) {
+        self.queue_mode = queue_mode;
+    }
+
+    pub fn single_active_consumer(&self) -> Option<bool> {
+        self.single_active_consumer
+    }
+
+    pub fn set_single_active_consumer(&mut self, single_active_consumer: Option<bool>) {
+        self.single_active_consumer = single_active_consumer;
+    }
+
+    pub fn priority(&self) -> Option<i32> {
+        self.priority
+    }
+
+    pub fn set_priority(&mut self, priority: Option<i32>) {
+        self.priority = priority;
+    }
+
+    pub fn stream_offset(&self) -> Option<&StreamOffset> {
+        self.stream_offset.as_ref()
+    }
+
+    pub fn set_stream_offset(&mut self, stream_offset: Option<StreamOffset>) {
+        self.stream_offset = stream_offset;
+    }
+
+    pub fn alternate_exchange(&self) -> Option<&str> {
+        self.alternate_exchange.as_deref()
+    }
+
+    pub fn set_alternate_exchange(&mut self, alternate_exchange: Option<String>) {
+        self.alternate_exchange = alternate_exchange;
+    }
+
+    // keys this model does not know about
+    pub fn others(&self) -> &FieldTable {
+        &self.others
+    }
+
+    pub fn set_other(&mut self, name: FieldName, value: FieldValue) {
+        self.others.insert(name, value);
+    }
+}
+
+// clients send numbers in whatever integer type their language has at hand,
+// so any of them is accepted as long as the value fits
+fn read_integer(key: &str, value: &FieldValue) -> Result<i64, ArgumentErr> {
+    let number = match value.kind() {
+        FieldValueKind::I8 => value.as_i8() as i64,
+        FieldValueKind::U8 => value.as_u8() as i64,
+        FieldValueKind::I16 => value.as_i16() as i64,
+        FieldValueKind::U16 => value.as_u16() as i64,
+        FieldValueKind::I32 => value.as_i32() as i64,
+        FieldValueKind::U32 => value.as_u32() as i64,
+        FieldValueKind::I64 => value.as_i64(),
+        FieldValueKind::U64 => i64::try_from(value.as_u64()).map_err(|_| ArgumentErr::InvalidValue(key.to_string(), value.as_u64().to_string()))?,
+        _ => return Err(ArgumentErr::InvalidType(key.to_string(), "an integer"))
+    };
+    Ok(number)
+}
+
+fn read_number<T: TryFrom<i64>>(key: &str, value: &FieldValue) -> Result<T, ArgumentErr> {
+    let number = read_integer(key, value)?;
+    T::try_from(number).map_err(|_| ArgumentErr::InvalidValue(key.to_string(), number.to_string()))
+}
+
+fn read_stream_offset(key: &str, value: &FieldValue) -> Result<StreamOffset, ArgumentErr> {
+    match value.kind() {
+        FieldValueKind::LongStr => {
+            let name = read_table_string(key, value)?;
+            StreamOffset::from_name(&name).ok_or_else(|| ArgumentErr::InvalidValue(key.to_string(), name))
+        }
+        FieldValueKind::Timestamp => Ok(StreamOffset::Timestamp(value.as_timestamp())),
+        _ => match read_integer(key, value) {
+            Ok(offset) => u64::try_from(offset).map(StreamOffset::Offset).map_err(|_| ArgumentErr::InvalidValue(key.to_string(), offset.to_string())),
+            Err(_) => Err(ArgumentErr::InvalidType(key.to_string(), "a string, an integer or a timestamp"))
+        }
+    }
+}
+
+fn to_i64(key: &str, number: u64) -> Result<i64, ArgumentErr> {
+    i64::try_from(number).map_err(|_| ArgumentErr::InvalidValue(key.to_string(), number.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use amqp_types::{FieldTable, FieldValue};
+    use crate::error::ArgumentErr;
+    use crate::x_arguments::{XArguments, Overflow, QueueType, StreamOffset};
+    use crate::common::{insert_table_entry, table_string};
+
+    #[test]
+    fn test_queue_arguments() {
+        let mut table = FieldTable::new();
+        insert_table_entry(&mut table, "x-message-ttl", FieldValue::from_u16(60000)).unwrap();
+        insert_table_entry(&mut table, "x-max-length", FieldValue::from_i32(1000)).unwrap();
+        insert_table_entry(&mut table, "x-overflow", table_string("reject-publish-dlx").unwrap()).unwrap();
+        insert_table_entry(&mut table, "x-dead-letter-exchange", table_string("dlx").unwrap()).unwrap();
+        insert_table_entry(&mut table, "x-queue-type", table_string("stream").unwrap()).unwrap();
+        insert_table_entry(&mut table, "x-stream-offset", table_string("7D").unwrap()).unwrap();
+        insert_table_entry(&mut table, "x-custom", FieldValue::from_bool(true)).unwrap();
+
+        let args = XArguments::from_table(&table).unwrap();
+        assert_eq!(args.message_ttl(), Some(60000));
+        assert_eq!(args.max_length(), Some(1000));
+        assert_eq!(args.overflow(), Some(Overflow::RejectPublishDlx));
+        assert_eq!(args.dead_letter_exchange(), Some("dlx"));
+        assert_eq!(args.queue_type(), Some(QueueType::Stream));
+        assert_eq!(args.stream_offset(), Some(&StreamOffset::Interval("7D".to_string())));
+        assert_eq!(args.others().iter().count(), 1);
+
+        let rebuilt = XArguments::from_table(&args.to_field_table().unwrap()).unwrap();
+        assert_eq!(rebuilt.message_ttl(), Some(60000));
+        assert_eq!(rebuilt.overflow(), Some(Overflow::RejectPublishDlx));
+        assert_eq!(rebuilt.stream_offset(), Some(&StreamOffset::Interval("7D".to_string())));
+        assert_eq!(rebuilt.others().iter().count(), 1);
+    }
+
+    #[test]
+    fn test_invalid_arguments() {
+        let mut table = FieldTable::new();
+        insert_table_entry(&mut table, "x-expires", table_string("1000").unwrap()).unwrap();
+        match XArguments::from_table(&table) {
+            Err(ArgumentErr::InvalidType(key, _)) => assert_eq!(key, "x-expires"),
+            _ => panic!("x-expires must be an integer")
+        }
+
+        let mut table = FieldTable::new();
+        insert_table_entry(&mut table, "x-max-priority", FieldValue::from_i32(256)).unwrap();
+        match XArguments::from_table(&table) {
+            Err(ArgumentErr::InvalidValue(key, value)) => assert_eq!((key.as_str(), value.as_str()), ("x-max-priority", "256")),
+            _ => panic!("x-max-priority must fit in an octet")
+        }
+    }
+}